@@ -0,0 +1,68 @@
+//! Times sem on generated programs of doubling size, inference should take about
+//! the same time per definition (or parameter) at every size.
+//!
+//! Run with `cargo bench --bench inference`.
+use std::time::{Duration, Instant};
+
+use llamac::{lex::IntoLexer, parse::IntoParser, pass::sem::sem, scan::Scanner};
+
+/// Makes a program of size `n`.
+type Generator = fn(usize) -> String;
+
+/// A function whose parameters are all made equal, one after the other, which
+/// makes a chain of `n` unknowns.
+fn equality_chain(n: usize) -> String {
+    let params: Vec<_> = (0..n).map(|i| format!("x{}", i)).collect();
+    let equalities: Vec<_> = (1..n).map(|i| format!("x{} = x{}", i - 1, i)).collect();
+    format!(
+        "let chain {} = ({})\n",
+        params.join(" "),
+        equalities.join(", ")
+    )
+}
+
+/// `n` generic functions, each one using the one before it.
+fn generic_calls(n: usize) -> String {
+    let mut program = String::from("let f0 x y = (x, y)\n");
+    for i in 1..n {
+        program += &format!("let f{} x y = f{} y x\n", i, i - 1);
+    }
+    program
+}
+
+/// How long sem takes on `source`, the best of a few runs.
+fn time_sem(name: &str, source: &str) -> Duration {
+    let path = std::env::temp_dir().join(format!("llamac-bench-{}.lla", name));
+    std::fs::write(&path, source).expect("failed to write the generated program");
+    let path = path.to_str().unwrap();
+    (0..3)
+        .map(|_| {
+            let ast = Scanner::new(path)
+                .expect("failed to read the generated program")
+                .preprocess()
+                .into_lexer(true)
+                .into_parser()
+                .program()
+                .expect("the generated program should parse");
+            let start = Instant::now();
+            sem(&ast, false).expect("the generated program should pass sem");
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let shapes: [(&str, Generator); 2] = [
+        ("equality_chain", equality_chain),
+        ("generic_calls", generic_calls),
+    ];
+    for (name, generate) in shapes {
+        println!("{}:", name);
+        println!("{:>8} {:>12} {:>12}", "n", "total", "per n");
+        for n in [1000, 2000, 4000, 8000] {
+            let elapsed = time_sem(name, &generate(n));
+            println!("{:>8} {:>12.2?} {:>12.2?}", n, elapsed, elapsed / n as u32);
+        }
+    }
+}
@@ -2,35 +2,120 @@ use std::{fs::File, io::BufWriter, io::Write, path::Path};
 
 use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
-use log::{error, warn};
+use log::warn;
 use thiserror::Error;
 
+use crate::diagnostic::{
+    self,
+    codes::{self, Code},
+    Diagnostic, ErrorFormat, Level, Levels, Severity, Sources,
+};
+
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 pub struct Cli {
     /// Path to source file
-    pub filename: String,
+    #[arg(required = true)]
+    pub filename: Option<String>,
 
     /// Run and stop after specified step
-    #[arg(long, short, value_enum, value_name = "step", default_value_t = StopAfter::IrGen)]
+    #[arg(long, short, value_enum, value_name = "step", default_value_t = StopAfter::Codegen)]
     pub stop_after: StopAfter,
 
-    /// Specify binary output filename
-    #[arg(long, short, value_name = "file", default_value = "a.out")]
-    pub out: String,
+    /// Specify output filename [default: a.out, or the source file with a .c extension
+    /// with --emit c]
+    #[arg(long, short, value_name = "file")]
+    pub out: Option<String>,
+
+    /// Kind of output to write to the output file
+    #[arg(long, value_enum, default_value_t = Emit::Exe)]
+    pub emit: Emit,
+
+    /// Keep the float operators distinct, make `**` exponentiation and default
+    /// ambiguous numeric types to int
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+
+    #[command(flatten)]
+    pub warnings: WarningArgs,
+
+    /// How to print errors and warnings
+    #[arg(long, value_enum, value_name = "format", global = true, default_value_t = ErrorFormat::Human)]
+    pub error_format: ErrorFormat,
+
+    /// Print a longer description of an error or warning code, with an example
+    #[arg(long, value_name = "CODE", exclusive = true)]
+    pub explain: Option<String>,
 
     // TODO: Use this, or remove it.
     #[arg(long, short, default_value_t = false)]
     pub verbose: bool,
 
     #[command(subcommand)]
-    pub print: Option<Printer>,
+    pub command: Option<Command>,
 }
 
 #[derive(Subcommand)]
-pub enum Printer {
+pub enum Command {
     /// Use to request intermediate output from the compilation process
     Print(PrintCalls),
+    /// Interpret a program instead of compiling it
+    Run(RunArgs),
+    /// Read, check and evaluate definitions and expressions interactively
+    Repl,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Path to source file
+    pub filename: String,
+
+    /// Same as for compiling
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+
+    #[command(flatten)]
+    pub warnings: WarningArgs,
+}
+
+/// The level of each warning, by name or code. A warning given to several of these flags gets
+/// the strictest level.
+#[derive(Args, Clone, Default)]
+pub struct WarningArgs {
+    /// Report the warning, even with --deny-warnings
+    #[arg(short = 'W', value_name = "name")]
+    pub warn: Vec<String>,
+
+    /// Don't report the warning
+    #[arg(short = 'A', value_name = "name")]
+    pub allow: Vec<String>,
+
+    /// Report the warning as an error
+    #[arg(short = 'D', value_name = "name")]
+    pub deny: Vec<String>,
+
+    /// Report the warnings not given to -W or -A as errors
+    #[arg(long, default_value_t = false)]
+    pub deny_warnings: bool,
+}
+impl WarningArgs {
+    pub fn levels(&self) -> CliResult<Levels> {
+        let mut levels = Levels::new(self.deny_warnings);
+        // Later levels override earlier ones, so the strictest is set last.
+        for (names, level) in [
+            (&self.allow, Level::Allow),
+            (&self.warn, Level::Warn),
+            (&self.deny, Level::Deny),
+        ] {
+            for name in names {
+                match Code::lookup(name) {
+                    Some(code) if code.severity == Severity::Warning => levels.set(code, level),
+                    _ => return Err(CliErr::UnknownWarning(name.clone())),
+                }
+            }
+        }
+        Ok(levels)
+    }
 }
 
 #[derive(Args)]
@@ -58,10 +143,11 @@ pub struct PrintCalls {
     #[arg(long, value_name = "file")]
     asm: Option<Option<String>>,
 }
-impl Printer {
-    pub fn to_print_calls(&self) -> &PrintCalls {
+impl Command {
+    pub fn to_print_calls(&self) -> Option<&PrintCalls> {
         match self {
-            Printer::Print(calls) => calls,
+            Command::Print(calls) => Some(calls),
+            Command::Run(_) | Command::Repl => None,
         }
     }
 }
@@ -73,30 +159,67 @@ pub enum StopAfter {
     Parsing,
     Sem,
     IrGen,
+    Codegen,
+}
+
+#[derive(PartialEq, ValueEnum, Clone, Copy)]
+pub enum Emit {
+    /// A native executable, built through the x86-64 backend
+    Exe,
+    /// A self-contained C source file
+    C,
 }
 
 impl Cli {
     pub fn parse() -> Self {
-        <Self as Parser>::parse().validate()
+        let cli = <Self as Parser>::parse();
+        // Before validating, so that its errors are printed in the format too.
+        diagnostic::set_error_format(cli.error_format);
+        cli.validate()
+    }
+    /// The file the output is written to, see `out`.
+    pub fn out_file(&self) -> String {
+        match (&self.out, self.emit, &self.filename) {
+            (Some(out), _, _) => out.clone(),
+            (None, Emit::C, Some(filename)) => Path::new(filename)
+                .with_extension("c")
+                .to_string_lossy()
+                .into_owned(),
+            (None, _, _) => "a.out".to_string(),
+        }
     }
     /// Warns on some errors, exits on unrecoverable ones.
     fn validate(self) -> Self {
-        if !Path::new(&self.filename).exists() {
-            let filename_str = self.filename.underline();
-            error!("File {} not found", filename_str);
+        if self.explain.is_some() {
+            return self;
+        }
+        let filename = match &self.command {
+            Some(Command::Run(RunArgs { filename, .. })) => Some(filename),
+            Some(Command::Repl) => return self,
+            _ => self.filename.as_ref(),
+        };
+        let error = match filename {
+            None => Some(CliErr::NoSourceFile),
+            Some(filename) if !Path::new(filename).exists() => {
+                Some(CliErr::FileNotFound(filename.clone()))
+            }
+            Some(_) => None,
+        };
+        if let Some(error) = error {
+            Diagnostic::from(&error).log(&mut Sources::default());
             std::process::exit(1);
         }
-        if self.stop_after != StopAfter::IrGen && self.out != "a.out" {
-            warn!("no executable produced when --stop-after is not 'ir-gen'");
+        if self.stop_after != StopAfter::Codegen && self.out.is_some() {
+            warn!("no executable produced when --stop-after is not 'codegen'");
         }
-        if let Some(Printer::Print(PrintCalls {
+        if let Some(Command::Print(PrintCalls {
             preprocessed: _,
             tokens,
             ast,
             types,
             ir,
             asm,
-        })) = &self.print
+        })) = &self.command
         {
             // if self.stop_after < StopAfter::Preprocessing && preprocessed.is_some() {
             //     eprintln!("Warning: will stop before preprocessing, 'print --preprocessed' ignored");
@@ -113,8 +236,11 @@ impl Cli {
             if self.stop_after < StopAfter::IrGen && ir.is_some() {
                 warn!("Warning: will stop before producing IR, print --ir ignored");
             }
-            if self.stop_after < StopAfter::IrGen && asm.is_some() {
-                warn!("Warning: will stop before producing IR, print --asm ignored");
+            if self.stop_after < StopAfter::Codegen && asm.is_some() {
+                warn!("Warning: will stop before producing assembly, print --asm ignored");
+            }
+            if self.emit == Emit::C && asm.is_some() {
+                warn!("Warning: no assembly produced with --emit c, print --asm ignored");
             }
         }
         self
@@ -130,33 +256,38 @@ pub trait PrintWriterHelpers {
     fn get_asm_writer(&self) -> CliResult<Option<Box<dyn Write>>>;
     fn get_any_writer(
         &self,
-        mapper: impl FnOnce(&Printer) -> CliResult<Option<Box<dyn Write>>>,
+        mapper: impl FnOnce(&PrintCalls) -> CliResult<Option<Box<dyn Write>>>,
     ) -> CliResult<Option<Box<dyn Write>>>;
 }
-impl PrintWriterHelpers for Option<Printer> {
+impl PrintWriterHelpers for Option<Command> {
     fn get_preprocessor_writer(&self) -> CliResult<Option<Box<dyn Write>>> {
-        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.to_print_calls().preprocessed))
+        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.preprocessed))
     }
     fn get_token_writer(&self) -> CliResult<Option<Box<dyn Write>>> {
-        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.to_print_calls().tokens))
+        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.tokens))
     }
     fn get_ast_writer(&self) -> CliResult<Option<Box<dyn Write>>> {
-        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.to_print_calls().ast))
+        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.ast))
     }
     fn get_types_writer(&self) -> CliResult<Option<Box<dyn Write>>> {
-        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.to_print_calls().types))
+        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.types))
     }
     fn get_ir_writer(&self) -> CliResult<Option<Box<dyn Write>>> {
-        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.to_print_calls().ir))
+        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.ir))
     }
     fn get_asm_writer(&self) -> CliResult<Option<Box<dyn Write>>> {
-        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.to_print_calls().asm))
+        self.get_any_writer(|p| PrintCalls::out_target_helper(&p.asm))
     }
     fn get_any_writer(
         &self,
-        mapper: impl FnOnce(&Printer) -> CliResult<Option<Box<dyn Write>>>,
+        mapper: impl FnOnce(&PrintCalls) -> CliResult<Option<Box<dyn Write>>>,
     ) -> CliResult<Option<Box<dyn Write>>> {
-        Ok(self.as_ref().map(mapper).transpose()?.flatten())
+        Ok(self
+            .as_ref()
+            .and_then(Command::to_print_calls)
+            .map(mapper)
+            .transpose()?
+            .flatten())
     }
 }
 impl PrintCalls {
@@ -164,7 +295,7 @@ impl PrintCalls {
         Ok(match p {
             None => None,
             Some(None) => Some(Box::new(BufWriter::new(std::io::stdout()))),
-            Some(Some(path)) => Some(Box::new(BufWriter::new(File::create(&path)?))),
+            Some(Some(path)) => Some(Box::new(BufWriter::new(File::create(path)?))),
         })
     }
 }
@@ -174,4 +305,40 @@ type CliResult<T> = Result<T, CliErr>;
 pub enum CliErr {
     #[error("IO error: {0}")]
     IO(#[from] std::io::Error),
+    #[error("{0} is not the name or code of a warning")]
+    UnknownWarning(String),
+    #[error("No source file given")]
+    NoSourceFile,
+    #[error("File {} not found", .0.underline())]
+    FileNotFound(String),
+}
+impl From<&CliErr> for Diagnostic {
+    fn from(error: &CliErr) -> Self {
+        let code = match error {
+            CliErr::IO(_) => &codes::IO_ERROR,
+            CliErr::UnknownWarning(_) => &codes::UNKNOWN_WARNING,
+            CliErr::NoSourceFile => &codes::NO_SOURCE_FILE,
+            CliErr::FileNotFound(_) => &codes::SOURCE_FILE_NOT_FOUND,
+        };
+        Diagnostic::new(code, error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn c_is_written_next_to_the_source_unless_told_otherwise() {
+        let out = |args: &[&str]| Cli::try_parse_from(args).unwrap().out_file();
+        assert_eq!(out(&["llamac", "dir/prog.lla"]), "a.out");
+        assert_eq!(
+            out(&["llamac", "dir/prog.lla", "--emit", "c"]),
+            "dir/prog.c"
+        );
+        assert_eq!(
+            out(&["llamac", "dir/prog.lla", "--emit", "c", "-o", "x.c"]),
+            "x.c"
+        );
+    }
 }
@@ -0,0 +1,523 @@
+use super::Severity;
+
+// ! Implementation notes:
+// !   Codes are stable, a code is never reused for something else once it is released.
+// !   The hundreds digit is the phase: 0 the scanner, 1 the lexer, 2 the parser, 3 sem
+// !   4 the interpreter, whose errors happen while the program runs, and 5 the command line.
+// !   Warnings are numbered apart, 0 for the ones sem finds and 1 for the lint pass.
+// !   Warnings are also known by their name, which is what attributes and `-W`, `-A`
+// !   and `-D` refer to them by (either works).
+
+/// What every diagnostic of one kind has in common.
+#[derive(Debug, PartialEq)]
+pub struct Code {
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Warnings may be allowed or denied, errors are always errors.
+    pub severity: Severity,
+    /// What `--explain` prints, a longer description followed by an example.
+    pub explanation: &'static str,
+}
+impl Code {
+    /// The code known by `code_or_name`, ignoring case (of either).
+    pub fn lookup(code_or_name: &str) -> Option<&'static Code> {
+        CODES.iter().copied().find(|code| {
+            code.code.eq_ignore_ascii_case(code_or_name)
+                || code.name.eq_ignore_ascii_case(code_or_name)
+        })
+    }
+}
+
+pub const CODES: &[&Code] = &[
+    &IO_ERROR,
+    &INVALID_DIRECTIVE,
+    &INCLUDE_CYCLE,
+    &UNTERMINATED_COMMENT,
+    &INVALID_CHAR_LITERAL,
+    &INVALID_STRING_LITERAL,
+    &INVALID_NUMBER_LITERAL,
+    &UNEXPECTED_TOKEN,
+    &UNCLOSED_DELIMITER,
+    &LET_WITHOUT_IN,
+    &MISSING_KEYWORD,
+    &UNKNOWN_IDENTIFIER,
+    &TYPE_MISMATCH,
+    &UNKNOWN_TYPE,
+    &INVALID_TYPE,
+    &INVALID_CONSTRUCTOR_PATTERN,
+    &REDEFINITION,
+    &DIVISION_BY_ZERO,
+    &INDEX_OUT_OF_BOUNDS,
+    &INVALID_DIMENSION,
+    &INVALID_ARRAY_SIZE,
+    &USE_AFTER_DELETE,
+    &INVALID_DELETE,
+    &UNINITIALIZED_VALUE,
+    &MATCH_FAILURE,
+    &UNKNOWN_WARNING,
+    &NO_SOURCE_FILE,
+    &SOURCE_FILE_NOT_FOUND,
+    &NON_EXHAUSTIVE_MATCH,
+    &UNREACHABLE_CLAUSE,
+    &NUMERIC_DEFAULT,
+    &UNUSED_BINDING,
+    &UNUSED_PARAMETER,
+    &UNUSED_CONSTRUCTOR,
+    &UNUSED_TYPE,
+    &UNASSIGNED_MUTABLE,
+    &UNREACHABLE_FUNCTION,
+];
+
+pub const IO_ERROR: Code = Code {
+    code: "E0001",
+    name: "io_error",
+    severity: Severity::Error,
+    explanation: "\
+A source file, or a file it includes, could not be opened or read.
+
+Check that the path exists and is readable. Included paths are relative to
+the directory the compiler is run from, not to the including file.
+
+    #include \"missing.lla\"",
+};
+pub const INVALID_DIRECTIVE: Code = Code {
+    code: "E0002",
+    name: "invalid_directive",
+    severity: Severity::Error,
+    explanation: "\
+A line starting with `#` is not a directive the preprocessor knows, or has
+the wrong arguments. The only directive is `#include`, followed by exactly
+one quoted path.
+
+    #include                  (* no path *)
+    #include \"a.lla\" \"b.lla\"  (* more than one *)
+    #define N 10              (* not a directive *)",
+};
+pub const INCLUDE_CYCLE: Code = Code {
+    code: "E0003",
+    name: "include_cycle",
+    severity: Severity::Error,
+    explanation: "\
+A file is included again while it is already being included, directly or
+through other files, which would include it forever. A file can't include
+the source file given to the compiler either.
+
+    (* a.lla *)
+    #include \"b.lla\"
+
+    (* b.lla *)
+    #include \"a.lla\"",
+};
+pub const UNTERMINATED_COMMENT: Code = Code {
+    code: "E0101",
+    name: "unterminated_comment",
+    severity: Severity::Error,
+    explanation: "\
+A block comment is still open at the end of the file. Block comments nest,
+so every `(*` inside one needs its own `*)` too.
+
+    (* the outer comment (* the inner one *)
+    let x = 1",
+};
+pub const INVALID_CHAR_LITERAL: Code = Code {
+    code: "E0102",
+    name: "invalid_char_literal",
+    severity: Severity::Error,
+    explanation: "\
+A character literal is empty, holds more than one character or uses an
+escape sequence that does not exist. The escapes are `\\n`, `\\t`, `\\r`,
+`\\\\`, `\\'`, `\\\"` and `\\xNN` with two hexadecimal digits.
+
+    let a = ''
+    let b = 'ab'
+    let c = '\\q'",
+};
+pub const INVALID_STRING_LITERAL: Code = Code {
+    code: "E0103",
+    name: "invalid_string_literal",
+    severity: Severity::Error,
+    explanation: "\
+A string literal is empty, not closed on the line it starts in, or uses an
+escape sequence that does not exist (see E0102 for the escapes).
+
+    let s = \"abc\\q\"
+    let t = \"never closed",
+};
+pub const INVALID_NUMBER_LITERAL: Code = Code {
+    code: "E0104",
+    name: "invalid_number_literal",
+    severity: Severity::Error,
+    explanation: "\
+A number literal does not fit its type, e.g. an integer too large for 64 bits.
+
+    let n = 99999999999999999999",
+};
+pub const UNEXPECTED_TOKEN: Code = Code {
+    code: "E0201",
+    name: "unexpected_token",
+    severity: Severity::Error,
+    explanation: "\
+The parser found a token that can't appear where it is, or the input ended
+early. The message lists the tokens that could have appeared instead. The
+parser skips ahead to the next definition and goes on, so the errors after
+the first one may be caused by it.
+
+    let f x = x +
+    let g = 2",
+};
+pub const UNCLOSED_DELIMITER: Code = Code {
+    code: "E0202",
+    name: "unclosed_delimiter",
+    severity: Severity::Error,
+    explanation: "\
+A `(`, `[`, `begin`, `match`, `while` or `for` is never closed by its `)`,
+`]`, `end` or `done`. The error points at where the closing token was
+expected and at the token it should close.
+
+    let a = (1 + 2
+    let b = begin print_int a",
+};
+pub const LET_WITHOUT_IN: Code = Code {
+    code: "E0203",
+    name: "let_without_in",
+    severity: Severity::Error,
+    explanation: "\
+A `let` inside an expression defines a local binding, which must be followed
+by `in` and the expression it is visible in. Definitions without `in` can
+only appear at the top level.
+
+    let f x =
+      let y = x + 1
+      y * 2
+
+Write `let y = x + 1 in` instead.",
+};
+pub const MISSING_KEYWORD: Code = Code {
+    code: "E0204",
+    name: "missing_keyword",
+    severity: Severity::Error,
+    explanation: "\
+A construct is missing one of the keywords between its parts: `then` after
+the condition of an `if`, `do` after the condition of a `while` or the bounds
+of a `for`, `=` after the counter of a `for`, or `with` after the expression
+a `match` matches.
+
+    let f x = if x > 0 print_int x
+
+Write `if x > 0 then print_int x` instead.",
+};
+pub const UNKNOWN_IDENTIFIER: Code = Code {
+    code: "E0301",
+    name: "unknown_identifier",
+    severity: Severity::Error,
+    explanation: "\
+A name is used that is not defined where it is used. Top level definitions
+are only visible after they are defined, unless they are defined together
+with `let rec ... and ...`.
+
+    let f x = g x
+    let g x = x",
+};
+pub const TYPE_MISMATCH: Code = Code {
+    code: "E0302",
+    name: "type_mismatch",
+    severity: Severity::Error,
+    explanation: "\
+Two types that must be the same are not. The error points at the expression
+that requires them to be equal, and at what gave each of them its type.
+Types are inferred, so the mistake may be at either of those places.
+
+    let r = new int
+    let a = r := 1
+    let b = r := true",
+};
+pub const UNKNOWN_TYPE: Code = Code {
+    code: "E0303",
+    name: "unknown_type",
+    severity: Severity::Error,
+    explanation: "\
+A type annotation or a constructor names a type that is not defined.
+
+    let f (x : colour) = x
+    type colour = Red | Green",
+};
+pub const INVALID_TYPE: Code = Code {
+    code: "E0304",
+    name: "invalid_type",
+    severity: Severity::Error,
+    explanation: "\
+A type annotation names a type the language forbids: arrays of arrays and
+functions that return arrays.
+
+    let f (a : array of array of int) = 1
+    let g (h : int -> array of int) = 1",
+};
+pub const INVALID_CONSTRUCTOR_PATTERN: Code = Code {
+    code: "E0305",
+    name: "invalid_constructor_pattern",
+    severity: Severity::Error,
+    explanation: "\
+A constructor in a pattern is given fewer arguments than it takes, or
+arguments it does not take. Patterns must match constructors fully.
+
+    type shape = Circle of float | Empty
+    let f s = match s with Circle -> 0 | Empty x -> 1 end",
+};
+pub const REDEFINITION: Code = Code {
+    code: "E0306",
+    name: "redefinition",
+    severity: Severity::Error,
+    explanation: "\
+A name is defined twice where it has to be unique: the parameters of a
+function, the definitions of one `let ... and ...`, the types of one
+`type ... and ...`, or constructors anywhere in the program.
+
+    let add x y x = x + y
+    type color = Red | Green
+    type light = Red | Off",
+};
+pub const DIVISION_BY_ZERO: Code = Code {
+    code: "E0401",
+    name: "division_by_zero",
+    severity: Severity::Error,
+    explanation: "\
+An integer was divided by zero, with `/` or `mod`, while the program ran.
+Float division by zero is not an error, it gives infinity or NaN.
+
+    let average sum n = sum / n
+    let main = print_int (average 10 0)",
+};
+pub const INDEX_OUT_OF_BOUNDS: Code = Code {
+    code: "E0402",
+    name: "index_out_of_bounds",
+    severity: Severity::Error,
+    explanation: "\
+An array was indexed past the size of one of its dimensions, while the
+program ran. Indices start at 0, so the last one is the size minus 1.
+
+    let mutable a [3]
+    let main = a[3] := 1",
+};
+pub const INVALID_DIMENSION: Code = Code {
+    code: "E0403",
+    name: "invalid_dimension",
+    severity: Severity::Error,
+    explanation: "\
+`dim` asked for a dimension the array does not have. Dimensions are
+numbered from 1, the ones past the array's dimension count are type errors.
+
+    let mutable a [3, 4]
+    let main = print_int (dim 0 a)",
+};
+pub const INVALID_ARRAY_SIZE: Code = Code {
+    code: "E0404",
+    name: "invalid_array_size",
+    severity: Severity::Error,
+    explanation: "\
+A mutable array was defined with a dimension that is zero or negative, while
+the program ran.
+
+    let main = let mutable a [0] in a[0] := 1",
+};
+pub const USE_AFTER_DELETE: Code = Code {
+    code: "E0405",
+    name: "use_after_delete",
+    severity: Severity::Error,
+    explanation: "\
+A reference was read, assigned or deleted again after it was deleted.
+
+    let main =
+      let r = new int in
+      delete r;
+      print_int !r",
+};
+pub const INVALID_DELETE: Code = Code {
+    code: "E0406",
+    name: "invalid_delete",
+    severity: Severity::Error,
+    explanation: "\
+`delete` was given a reference to an array element. Only references made by
+`new` can be deleted, the elements belong to their array.
+
+    let mutable a [3]
+    let main = delete a[0]",
+};
+pub const UNINITIALIZED_VALUE: Code = Code {
+    code: "E0407",
+    name: "uninitialized_value",
+    severity: Severity::Error,
+    explanation: "\
+A value was used before it was given one, e.g. a recursive definition that
+uses itself while it is still being defined.
+
+    let rec x = x + 1",
+};
+pub const MATCH_FAILURE: Code = Code {
+    code: "E0408",
+    name: "match_failure",
+    severity: Severity::Error,
+    explanation: "\
+None of the clauses of a `match` matched the value, while the program ran.
+Warning W0001 (non_exhaustive_match) points out matches that may do this.
+
+    type shape = Circle of float | Square of float
+    let area s = match s with Circle r -> r *. r end
+    let main = area (Square 1.0)",
+};
+pub const UNKNOWN_WARNING: Code = Code {
+    code: "E0501",
+    name: "unknown_warning",
+    severity: Severity::Error,
+    explanation: "\
+`-W`, `-A` or `-D` was given something that is neither the code nor the name
+of a warning. Errors can't be allowed or denied, only warnings can.
+
+    llamac -A unused_variable program.lla
+    llamac -A E0302 program.lla",
+};
+pub const NO_SOURCE_FILE: Code = Code {
+    code: "E0502",
+    name: "no_source_file",
+    severity: Severity::Error,
+    explanation: "\
+No source file was given to compile or run. Only `repl` and `--explain` work
+without one.
+
+    llamac print --ast",
+};
+pub const SOURCE_FILE_NOT_FOUND: Code = Code {
+    code: "E0503",
+    name: "source_file_not_found",
+    severity: Severity::Error,
+    explanation: "\
+The source file given to compile or run does not exist. It is relative to the
+directory the compiler is run from.
+
+    llamac missing.lla",
+};
+pub const NON_EXHAUSTIVE_MATCH: Code = Code {
+    code: "W0001",
+    name: "non_exhaustive_match",
+    severity: Severity::Warning,
+    explanation: "\
+A `match` does not cover every value it may be given, the warning shows one
+that it misses. Matching it stops the program with a runtime error.
+
+    type color = Red | Green | Blue
+    let name c = match c with Red -> 'r' | Green -> 'g' end",
+};
+pub const UNREACHABLE_CLAUSE: Code = Code {
+    code: "W0002",
+    name: "unreachable_clause",
+    severity: Severity::Warning,
+    explanation: "\
+A clause of a `match` can never be chosen, the clauses before it match every
+value it does.
+
+    let f n = match n with x -> 1 | 0 -> 2 end",
+};
+pub const NUMERIC_DEFAULT: Code = Code {
+    code: "W0003",
+    name: "numeric_default",
+    severity: Severity::Warning,
+    explanation: "\
+In strict mode, a definition's type has a numeric type that nothing pins
+down to int or float, so it was defaulted to int. Annotate it to choose.
+
+    let double x = x + x",
+};
+pub const UNUSED_BINDING: Code = Code {
+    code: "W0101",
+    name: "unused_binding",
+    severity: Severity::Warning,
+    explanation: "\
+A definition is never used. Uses from inside its own definition, like a
+recursive call, don't count. Constants of type unit and a top level `main`
+are run for their effects and never reported. Put
+`(*@ allow(unused_binding) *)` before it to keep it anyway.
+
+    let main =
+      let unused = 42 in
+      print_int 1",
+};
+pub const UNUSED_PARAMETER: Code = Code {
+    code: "W0102",
+    name: "unused_parameter",
+    severity: Severity::Warning,
+    explanation: "\
+A function never uses one of its parameters. Put
+`(*@ allow(unused_parameter) *)` before the function if the parameter is only
+there to give it its type.
+
+    let first a b = a",
+};
+pub const UNUSED_CONSTRUCTOR: Code = Code {
+    code: "W0103",
+    name: "unused_constructor",
+    severity: Severity::Warning,
+    explanation: "\
+A constructor is never used, neither to build a value nor in a pattern.
+If none of a type's constructors is used, the type is reported instead
+(see W0104).
+
+    type color = Red | Green
+    let _favorite = Red",
+};
+pub const UNUSED_TYPE: Code = Code {
+    code: "W0104",
+    name: "unused_type",
+    severity: Severity::Warning,
+    explanation: "\
+A type is never used, neither in an annotation nor through any of its
+constructors.
+
+    type unused = A | B
+    let main = print_int 1",
+};
+pub const UNASSIGNED_MUTABLE: Code = Code {
+    code: "W0105",
+    name: "unassigned_mutable",
+    severity: Severity::Warning,
+    explanation: "\
+A mutable is only ever read, through `!` or `dim`, so it could as well be
+an immutable binding.
+
+    let mutable counter
+    let main = print_int !counter",
+};
+pub const UNREACHABLE_FUNCTION: Code = Code {
+    code: "W0106",
+    name: "unreachable_function",
+    severity: Severity::Warning,
+    explanation: "\
+A top level function is used, but only by functions the program never
+reaches. The program starts from the top level definitions that are not
+functions.
+
+    let debug n = print_int n
+    let dump n = debug n
+    let main = print_int 1",
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn codes_are_unique_and_found_by_code_or_name() {
+        for (i, code) in CODES.iter().enumerate() {
+            assert!(CODES[i + 1..]
+                .iter()
+                .all(|other| other.code != code.code && other.name != code.name));
+            assert_eq!(
+                code.code.starts_with('W'),
+                code.severity == Severity::Warning
+            );
+            assert_eq!(Code::lookup(code.code), Some(*code));
+            assert_eq!(Code::lookup(code.name), Some(*code));
+        }
+        assert_eq!(Code::lookup("w0101"), Some(&UNUSED_BINDING));
+        assert_eq!(Code::lookup("Unused_Binding"), Some(&UNUSED_BINDING));
+        assert_eq!(Code::lookup("E9999"), None);
+    }
+}
@@ -0,0 +1,176 @@
+use serde_json::{json, Value};
+
+use crate::parse::ast::Span;
+
+use super::{Diagnostic, Label, Severity};
+
+// ! Implementation notes:
+// !   The objects follow rustc's `--error-format json` loosely: `code`, `name`, `severity`,
+// !   `message`, `spans`, `notes`, `help` and `suggestions` are always there. Lines and
+// !   columns are 1-based, columns count bytes and the end column is exclusive, as in `Span`.
+// !   Messages that are not diagnostics (e.g. a missing file) have the same fields, with a
+// !   `null` code and name and no spans, so that tools can read every line the same way.
+
+impl Diagnostic {
+    /// The diagnostic as one JSON object, for editors and CI to read.
+    pub fn to_json(&self) -> Value {
+        let spans: Vec<Value> = self
+            .primary
+            .iter()
+            .map(|label| (label, true))
+            .chain(self.secondary.iter().map(|label| (label, false)))
+            // Positions on line 0 are defaults, they point nowhere.
+            .filter(|(label, _)| label.span.start.line != 0)
+            .map(|(Label { span, message }, primary)| {
+                let mut span = span_json(span);
+                span["is_primary"] = json!(primary);
+                span["label"] = json!(message);
+                span
+            })
+            .collect();
+        let suggestions: Vec<Value> = self
+            .suggestions
+            .iter()
+            .map(|suggestion| {
+                json!({
+                    "message": suggestion.message,
+                    "span": span_json(&suggestion.span),
+                    "replacement": suggestion.replacement,
+                })
+            })
+            .collect();
+        json!({
+            "code": self.code.code,
+            "name": self.code.name,
+            "severity": severity(self.severity),
+            "message": self.message,
+            "spans": spans,
+            "notes": self.notes,
+            "help": self.help,
+            "suggestions": suggestions,
+        })
+    }
+}
+
+/// A message without a code or a place in the source, in the same shape as a diagnostic.
+pub fn message(level: log::Level, message: &str) -> Value {
+    let severity = match level {
+        log::Level::Error => "error",
+        log::Level::Warn => "warning",
+        log::Level::Info => "info",
+        log::Level::Debug => "debug",
+        log::Level::Trace => "trace",
+    };
+    json!({
+        "code": null,
+        "name": null,
+        "severity": severity,
+        "message": message,
+        "spans": [],
+        "notes": [],
+        "help": null,
+        "suggestions": [],
+    })
+}
+
+fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    }
+}
+
+fn span_json(span: &Span) -> Value {
+    json!({
+        "file": span.start.filename.as_str(),
+        "line_start": span.start.line,
+        "column_start": span.start.column,
+        "line_end": span.end.line,
+        "column_end": span.end.column,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{diagnostic::codes, lex::token::Position};
+
+    fn span(line: usize, from: usize, to: usize) -> Span {
+        let filename = Rc::new("<test>".to_string());
+        Span::new(
+            Position::new(line, from, Rc::clone(&filename)),
+            Position::new(line, to, filename),
+        )
+    }
+
+    #[test]
+    fn diagnostics_keep_their_spans_notes_and_suggestions() {
+        let diagnostic =
+            Diagnostic::new(&codes::UNCLOSED_DELIMITER, "\"(\" is never closed by \")\"")
+                .with_primary(&span(3, 1, 4), "expected \")\" before this")
+                .with_secondary(&span(1, 9, 10), "\"(\" opened here")
+                .with_secondary(&span(0, 0, 0), "nowhere")
+                .with_note("a note")
+                .with_suggestion(&span(3, 1, 1), ") ", "insert \")\"");
+        assert_eq!(
+            diagnostic.to_json(),
+            json!({
+                "code": "E0202",
+                "name": "unclosed_delimiter",
+                "severity": "error",
+                "message": "\"(\" is never closed by \")\"",
+                "spans": [
+                    {
+                        "file": "<test>",
+                        "line_start": 3,
+                        "column_start": 1,
+                        "line_end": 3,
+                        "column_end": 4,
+                        "is_primary": true,
+                        "label": "expected \")\" before this",
+                    },
+                    {
+                        "file": "<test>",
+                        "line_start": 1,
+                        "column_start": 9,
+                        "line_end": 1,
+                        "column_end": 10,
+                        "is_primary": false,
+                        "label": "\"(\" opened here",
+                    },
+                ],
+                "notes": ["a note"],
+                "help": null,
+                "suggestions": [{
+                    "message": "insert \")\"",
+                    "span": {
+                        "file": "<test>",
+                        "line_start": 3,
+                        "column_start": 1,
+                        "line_end": 3,
+                        "column_end": 1,
+                    },
+                    "replacement": ") ",
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn messages_have_the_shape_of_diagnostics() {
+        let message = message(log::Level::Error, "File a.lla not found");
+        assert_eq!(message["code"], Value::Null);
+        assert_eq!(message["severity"], "error");
+        assert_eq!(message["spans"], json!([]));
+        assert_eq!(
+            message.as_object().unwrap().len(),
+            Diagnostic::new(&codes::IO_ERROR, "")
+                .to_json()
+                .as_object()
+                .unwrap()
+                .len()
+        );
+    }
+}
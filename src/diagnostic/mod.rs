@@ -0,0 +1,462 @@
+pub mod codes;
+pub mod json;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::ValueEnum;
+use colored::{ColoredString, Colorize};
+use log::log;
+use thiserror::Error;
+
+use crate::{lex::token::Position, parse::ast::Span};
+
+use self::codes::Code;
+
+// ! Implementation notes:
+// !   Every phase's errors and warnings convert into a `Diagnostic` (`From<&Error>`, next to
+// !   each error type), their `Display` stays the one-line form the tests compare against.
+// !   Rendering reads the source lines the labels point at through `Sources`, lazily from
+// !   disk by the filename in their positions. Input that is not a file (the REPL's) has to
+// !   be inserted beforehand, labels whose line can't be found are printed without one.
+// !   Columns count bytes, like the lexer's. A label spanning several lines is underlined
+// !   up to the end of its first line.
+// !   A rendered diagnostic starts with its own severity and code, so it is logged to
+// !   `LOG_TARGET`, which the logger prints without a prefix (see `init_logger`).
+// !   With `--error-format json` they are logged as one JSON object per line instead (see
+// !   `json`), and so is every other logged message. The format is set once for the whole
+// !   process, like the logger, so that each place that logs does not need to be given it.
+// !   Suggestions are only part of the JSON output, the help says the same thing to people.
+
+/// The log target of rendered diagnostics.
+pub const LOG_TARGET: &str = "diagnostic";
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Rendered with the source lines they point at
+    Human,
+    /// One JSON object per line, without colors
+    Json,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Sets how every diagnostic and logged message is printed from now on.
+pub fn set_error_format(format: ErrorFormat) {
+    JSON.store(format == ErrorFormat::Json, Ordering::Relaxed);
+    if format == ErrorFormat::Json {
+        colored::control::set_override(false);
+    }
+}
+pub fn error_format() -> ErrorFormat {
+    match JSON.load(Ordering::Relaxed) {
+        true => ErrorFormat::Json,
+        false => ErrorFormat::Human,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A span and what to say about it, printed under the source line it starts in.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// An edit of the source that may fix the problem, for tools to offer.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    /// The source to replace, inserted at its start if it is empty.
+    pub span: Span,
+    pub replacement: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// The code's severity, unless the warning is denied.
+    pub severity: Severity,
+    pub code: &'static Code,
+    pub message: String,
+    /// Where the problem is, if it is anywhere in the source.
+    pub primary: Option<Label>,
+    /// Other places that explain it.
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+impl Diagnostic {
+    pub fn new(code: &'static Code, message: impl Into<String>) -> Self {
+        Self {
+            severity: code.severity,
+            code,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+            suggestions: Vec::new(),
+        }
+    }
+    pub fn with_primary(mut self, span: &Span, message: impl Into<String>) -> Self {
+        self.primary = Some(Label {
+            span: span.clone(),
+            message: message.into(),
+        });
+        self
+    }
+    /// *Note: A secondary label on the primary span would only repeat its underline,
+    /// so it becomes a note instead.
+    pub fn with_secondary(mut self, span: &Span, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match &self.primary {
+            Some(primary) if same_span(&primary.span, span) => self.notes.push(message),
+            _ => self.secondary.push(Label {
+                span: span.clone(),
+                message,
+            }),
+        }
+        self
+    }
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+    pub fn with_help(mut self, help: Option<String>) -> Self {
+        self.help = help;
+        self
+    }
+    pub fn with_suggestion(
+        mut self,
+        span: &Span,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            span: span.clone(),
+            replacement: replacement.into(),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Logs the diagnostic at its severity, rendered or as JSON depending on the error format.
+    pub fn log(&self, sources: &mut Sources) {
+        let level = match self.severity {
+            Severity::Error => log::Level::Error,
+            Severity::Warning => log::Level::Warn,
+        };
+        match error_format() {
+            ErrorFormat::Human => log!(target: LOG_TARGET, level, "{}", self.render(sources)),
+            ErrorFormat::Json => log!(target: LOG_TARGET, level, "{}", self.to_json()),
+        }
+    }
+    /// The severity, code and message, followed by the source lines the labels point at,
+    /// then the notes and help.
+    pub fn render(&self, sources: &mut Sources) -> String {
+        let severity = match self.severity {
+            Severity::Error => format!("error[{}]", self.code.code).red(),
+            Severity::Warning => format!("warning[{}]", self.code.code).yellow(),
+        };
+        let mut out = format!("{}{} {}", severity.bold(), ":".bold(), self.message.bold());
+        let mut labels: Vec<(&Label, bool)> = self
+            .primary
+            .iter()
+            .map(|label| (label, true))
+            .chain(self.secondary.iter().map(|label| (label, false)))
+            // Positions on line 0 are defaults, they point nowhere.
+            .filter(|(label, _)| label.span.start.line != 0)
+            .collect();
+        let width = labels
+            .iter()
+            .map(|(label, _)| label.span.start.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = |line: &str| format!("{:>width$} |", line).blue().bold();
+        // The primary label's file goes first, labels in the same file go in line order.
+        let location = labels.first().map(|(label, _)| label.span.start.clone());
+        if let Some(file) = location.as_ref().map(|start| start.filename.clone()) {
+            labels.sort_by_key(|(label, _)| {
+                let start = &label.span.start;
+                (
+                    start.filename != file,
+                    start.filename.clone(),
+                    start.line,
+                    start.column,
+                )
+            });
+        }
+        let mut prev: Option<&Position> = None;
+        for (label, primary) in labels {
+            let start = &label.span.start;
+            match prev {
+                Some(prev) if prev.filename == start.filename => (),
+                None => {
+                    let location = location.as_ref().unwrap_or(start);
+                    out += &format!("\n{:width$}{} {}", "", "-->".blue().bold(), location);
+                    out += &format!("\n{}", gutter(""));
+                }
+                Some(_) => {
+                    out += &format!("\n{:width$}{} {}", "", ":::".blue().bold(), start);
+                    out += &format!("\n{}", gutter(""));
+                }
+            }
+            let Some(text) = sources.line(start) else {
+                let line = format!("{} {}", start, label.message);
+                out += &format!("\n{} {}", gutter(""), line.trim_end());
+                prev = Some(start);
+                continue;
+            };
+            match prev {
+                Some(prev) if prev.filename == start.filename && prev.line == start.line => (),
+                Some(prev) if prev.filename == start.filename && prev.line + 1 < start.line => {
+                    out += &format!("\n{}", "...".blue().bold());
+                    out += &format!("\n{} {}", gutter(&start.line.to_string()), text);
+                }
+                _ => out += &format!("\n{} {}", gutter(&start.line.to_string()), text),
+            }
+            let end = &label.span.end;
+            let len = if end.line == start.line && end.filename == start.filename {
+                end.column.saturating_sub(start.column)
+            } else {
+                (text.len() + 1).saturating_sub(start.column)
+            };
+            // Tabs are kept, so that the markers line up however wide they are shown.
+            let padding: String = text
+                .bytes()
+                .take(start.column.saturating_sub(1))
+                .map(|b| if b == b'\t' { '\t' } else { ' ' })
+                .collect();
+            let marker = if primary { "^" } else { "-" };
+            let underline = format!("{} {}", marker.repeat(len.max(1)), label.message);
+            out += &format!(
+                "\n{} {}{}",
+                gutter(""),
+                padding,
+                self.paint(underline.trim_end(), primary)
+            );
+            prev = Some(start);
+        }
+        for note in &self.notes {
+            out += &format!("\n{:width$} {} note: {}", "", "=".blue().bold(), note);
+        }
+        if let Some(help) = &self.help {
+            out += &format!("\n{:width$} {} help: {}", "", "=".blue().bold(), help);
+        }
+        out
+    }
+    fn paint(&self, text: &str, primary: bool) -> ColoredString {
+        match (primary, self.severity) {
+            (false, _) => text.blue().bold(),
+            (true, Severity::Error) => text.red().bold(),
+            (true, Severity::Warning) => text.yellow().bold(),
+        }
+    }
+}
+
+fn same_span(lhs: &Span, rhs: &Span) -> bool {
+    let same = |lhs: &Position, rhs: &Position| {
+        (lhs.line, lhs.column, &lhs.filename) == (rhs.line, rhs.column, &rhs.filename)
+    };
+    same(&lhs.start, &rhs.start) && same(&lhs.end, &rhs.end)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The level each warning is reported at, errors are always errors.
+#[derive(Debug, Default)]
+pub struct Levels {
+    /// The level of the warnings that are not given one.
+    deny_warnings: bool,
+    levels: HashMap<&'static str, Level>,
+}
+impl Levels {
+    pub fn new(deny_warnings: bool) -> Self {
+        Self {
+            deny_warnings,
+            levels: HashMap::new(),
+        }
+    }
+    pub fn set(&mut self, code: &'static Code, level: Level) {
+        self.levels.insert(code.code, level);
+    }
+    pub fn level(&self, code: &Code) -> Level {
+        match self.levels.get(code.code) {
+            Some(level) => *level,
+            None if self.deny_warnings => Level::Deny,
+            None => Level::Warn,
+        }
+    }
+    /// The diagnostic as it should be reported, `None` if it is an allowed warning.
+    /// A denied warning is reported as an error.
+    pub fn apply(&self, mut diagnostic: Diagnostic) -> Option<Diagnostic> {
+        if diagnostic.code.severity == Severity::Error {
+            return Some(diagnostic);
+        }
+        match self.level(diagnostic.code) {
+            Level::Allow => None,
+            Level::Warn => Some(diagnostic),
+            Level::Deny => {
+                diagnostic.severity = Severity::Error;
+                diagnostic
+                    .notes
+                    .push(format!("the warning {} is denied", diagnostic.code.name));
+                Some(diagnostic)
+            }
+        }
+    }
+}
+
+/// An error of a phase that goes on past it, and reports every one it finds in `Errors`.
+pub trait PhaseError: std::error::Error {
+    /// What the errors are called in the summary, e.g. "syntax".
+    const KIND: &'static str;
+    /// Printed on a line of its own after the error.
+    fn hint(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Every error a phase found, in the order it reports them.
+#[derive(Debug)]
+pub struct Errors<E>(Vec<E>);
+impl<E: PhaseError> Errors<E> {
+    pub fn new(errors: Vec<E>) -> Self {
+        Self(errors)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.0.iter()
+    }
+    /// How many errors were found, in words.
+    pub fn summary(&self) -> String {
+        match self.0.len() {
+            1 => format!("found 1 {} error", E::KIND),
+            n => format!("found {} {} errors", n, E::KIND),
+        }
+    }
+}
+impl<E: PhaseError> From<E> for Errors<E> {
+    fn from(error: E) -> Self {
+        Self(vec![error])
+    }
+}
+impl<E: PhaseError> std::fmt::Display for Errors<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for error in &self.0 {
+            writeln!(f, "{}", error)?;
+            if let Some(hint) = error.hint() {
+                writeln!(f, "  hint: {}", hint)?;
+            }
+        }
+        write!(f, "{}", self.summary())
+    }
+}
+impl<E: PhaseError> std::error::Error for Errors<E> {}
+
+/// Warnings that were reported as errors, the program is rejected because of them.
+#[derive(Error, Debug)]
+#[error("found {} denied {}", .0, if *.0 == 1 { "warning" } else { "warnings" })]
+pub struct DeniedWarnings(pub usize);
+
+/// The source lines of every file diagnostics point into, read once when first needed.
+#[derive(Default)]
+pub struct Sources(HashMap<String, Option<Vec<String>>>);
+impl Sources {
+    /// Makes `source` the contents of `name`, for input that is not read from a file.
+    pub fn insert(&mut self, name: &str, source: &str) {
+        self.0.insert(
+            name.to_string(),
+            Some(source.lines().map(str::to_string).collect()),
+        );
+    }
+    fn line(&mut self, position: &Position) -> Option<&str> {
+        self.0
+            .entry(position.filename.to_string())
+            .or_insert_with(|| {
+                std::fs::read_to_string(position.filename.as_str())
+                    .ok()
+                    .map(|source| source.lines().map(str::to_string).collect())
+            })
+            .as_ref()?
+            .get(position.line.checked_sub(1)?)
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn span(line: usize, from: usize, to: usize) -> Span {
+        let filename = Rc::new("<test>".to_string());
+        Span::new(
+            Position::new(line, from, Rc::clone(&filename)),
+            Position::new(line, to, filename),
+        )
+    }
+
+    #[test]
+    fn labels_underline_their_source_lines() {
+        colored::control::set_override(false);
+        let mut sources = Sources::default();
+        sources.insert("<test>", "let a = (1 + 2\n\nlet b = 3\n");
+        let diagnostic =
+            Diagnostic::new(&codes::UNCLOSED_DELIMITER, "\"(\" is never closed by \")\"")
+                .with_primary(&span(3, 1, 4), "expected \")\" before this")
+                .with_secondary(&span(1, 9, 10), "\"(\" opened here")
+                .with_help(Some("add a matching \")\"".to_string()));
+        assert_eq!(
+            diagnostic.render(&mut sources),
+            [
+                "error[E0202]: \"(\" is never closed by \")\"",
+                " --> <test>:3:1",
+                "  |",
+                "1 | let a = (1 + 2",
+                "  |         - \"(\" opened here",
+                "...",
+                "3 | let b = 3",
+                "  | ^^^ expected \")\" before this",
+                "  = help: add a matching \")\"",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn labels_without_source_keep_their_position() {
+        colored::control::set_override(false);
+        let diagnostic = Diagnostic::new(&codes::UNUSED_BINDING, "Unused binding x")
+            .with_primary(&span(2, 5, 6), "");
+        assert_eq!(
+            diagnostic.render(&mut Sources::default()),
+            "warning[W0101]: Unused binding x\n --> <test>:2:5\n  |\n  | <test>:2:5"
+        );
+    }
+
+    #[test]
+    fn levels_allow_or_deny_warnings_but_not_errors() {
+        let warning = || Diagnostic::new(&codes::UNUSED_BINDING, "Unused binding x");
+        let error = || Diagnostic::new(&codes::UNKNOWN_IDENTIFIER, "Identifier x not found");
+        let mut levels = Levels::new(true);
+        assert_eq!(levels.apply(warning()).unwrap().severity, Severity::Error);
+        assert_eq!(levels.apply(error()).unwrap().severity, Severity::Error);
+        levels.set(&codes::UNUSED_BINDING, Level::Warn);
+        assert_eq!(levels.apply(warning()).unwrap().severity, Severity::Warning);
+        levels.set(&codes::UNUSED_BINDING, Level::Allow);
+        assert!(levels.apply(warning()).is_none());
+        assert!(levels.apply(error()).is_some());
+    }
+}
@@ -2,16 +2,21 @@ pub mod token;
 
 use std::{iter::FusedIterator, rc::Rc};
 
-use log::error;
 use thiserror::Error;
 
-use crate::scan;
+use crate::{
+    diagnostic::{codes, Diagnostic, Sources},
+    parse::ast::Span,
+    scan,
+};
 
 use self::token::{Position, Token, TokenKind, TokenValue};
 
 pub struct Lexer<S: Iterator<Item = scan::Line>> {
     scanner: S,
     exit_on_error: bool,
+    /// Keeps the float operators and `**` as they are, see `Token::make_compatible`.
+    strict: bool,
 
     cursor: usize, // cur_colno - 1
     cur_lineno: usize,
@@ -26,6 +31,7 @@ impl<S: Iterator<Item = scan::Line>> Lexer<S> {
         Lexer {
             scanner,
             exit_on_error: true,
+            strict: false,
 
             cursor: 0,
             cur_lineno: 0,
@@ -36,6 +42,12 @@ impl<S: Iterator<Item = scan::Line>> Lexer<S> {
             is_done: false,
         }
     }
+    /// In strict mode the float operators are distinct from the int ones, and `**` is
+    /// exponentiation rather than multiplication.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
     fn get_cur_line(&self, caller_msg: &'static str) -> &Vec<u8> {
         self.cur_line.as_ref().expect(caller_msg)
     }
@@ -62,10 +74,11 @@ impl<S: Iterator<Item = scan::Line>> Lexer<S> {
         self.eat_whitespace();
         for matcher in matchers.iter() {
             if let Some(mut token) = matcher(self)? {
-                token.make_compatible();
-                match &token.kind {
-                    TokenKind::EOF => self.is_done = true,
-                    _ => {}
+                if !self.strict {
+                    token.make_compatible();
+                }
+                if token.kind == TokenKind::EOF {
+                    self.is_done = true;
                 }
                 return Ok(Some(token));
             }
@@ -254,7 +267,7 @@ impl<S: Iterator<Item = scan::Line>> Lexer<S> {
                     original,
                     TokenValue::Char(c),
                     from.clone(),
-                    self.make_position(self.cursor),
+                    self.make_position(self.cursor + cnt + 1),
                 ))),
             )
         };
@@ -454,9 +467,7 @@ impl<S: Iterator<Item = scan::Line>> Lexer<S> {
         let mut retval = String::new();
         while let Some((i, _)) = s[idx..].iter().enumerate().find(|(_, &c)| c == b'\\') {
             let slash_idx = idx + i;
-            retval.push_str(
-                &String::from_utf8(s[idx..slash_idx].to_vec()).expect("should be valid utf8"),
-            );
+            retval.push_str(std::str::from_utf8(&s[idx..slash_idx]).expect("should be valid utf8"));
             let chars_cnt_after_slash = s.len() - slash_idx - 1;
             if chars_cnt_after_slash >= 1 && s[slash_idx + 1] != b'x' {
                 if let Some(escaped) = Self::parse_single_char_escape_seq(s[slash_idx + 1]) {
@@ -478,7 +489,7 @@ impl<S: Iterator<Item = scan::Line>> Lexer<S> {
                 return Err(slash_idx);
             }
         }
-        retval.push_str(&String::from_utf8(s[idx..].to_vec()).expect("should be valid utf8"));
+        retval.push_str(std::str::from_utf8(&s[idx..]).expect("should be valid utf8"));
         Ok(retval)
     }
     fn parse_single_char_escape_seq(c: u8) -> Option<u8> {
@@ -515,7 +526,7 @@ impl<S: Iterator<Item = scan::Line>> Iterator for Lexer<S> {
             Ok(None) => None,
             Err(err) => {
                 if self.exit_on_error {
-                    error!("{}", err);
+                    Diagnostic::from(&err).log(&mut Sources::default());
                     std::process::exit(1);
                 } else {
                     todo!("handle error (possibly by calling match_unmatched)");
@@ -550,6 +561,25 @@ pub enum LexErr {
     #[error("{1} at {0}")]
     FromUtf8Error(Position, String),
 }
+impl From<&LexErr> for Diagnostic {
+    fn from(error: &LexErr) -> Self {
+        use LexErr::*;
+        let (code, at, message, label) = match error {
+            UnterminatedComment(at) => (
+                &codes::UNTERMINATED_COMMENT,
+                at,
+                "unterminated comment",
+                "the comment starts here",
+            ),
+            InvalidCharLiteral(at, msg) => (&codes::INVALID_CHAR_LITERAL, at, *msg, ""),
+            InvalidStringLiteral(at, msg) => (&codes::INVALID_STRING_LITERAL, at, *msg, ""),
+            ParseFloatError(at, msg) | ParseIntError(at, msg) | FromUtf8Error(at, msg) => {
+                (&codes::INVALID_NUMBER_LITERAL, at, msg.as_str(), "")
+            }
+        };
+        Diagnostic::new(code, message).with_primary(&Span::new(at.clone(), at.clone()), label)
+    }
+}
 
 // impl std::fmt::Display for LexErr {
 //     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -105,7 +105,7 @@ pub enum TokenValue {
 
 impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "`{}` from {} to {}\n", self.kind, self.from, self.to)
+        writeln!(f, "`{}` from {} to {}", self.kind, self.from, self.to)
     }
 }
 #[derive(Clone)]
@@ -204,6 +204,8 @@ pub const SINGLE_CHAR_SYMBOLS: [TokenKind; 16]= [
     TokenKind::LParen, TokenKind::RParen, TokenKind::Bar, TokenKind::Exclam,
 ];
 impl Token {
+    /// Turns the float operators into the int ones (types tell them apart), and `**` into `*`,
+    /// as the previous version of the compiler did.
     pub fn make_compatible(&mut self) {
         match self.kind {
             TokenKind::PlusDot => self.kind = TokenKind::Plus,
@@ -1,44 +1,62 @@
 pub mod cli;
+pub mod diagnostic;
 pub mod lex;
 pub mod long_peekable;
 pub mod parse;
 pub mod pass;
+pub mod repl;
 pub mod scan;
 pub mod writer_iter;
 
+use cli::Emit;
 use cli::PrintWriterHelpers;
 use cli::StopAfter;
 use colored::Colorize;
+use diagnostic::{Diagnostic, ErrorFormat, Errors, Levels, PhaseError, Severity, Sources};
 use env_logger::Env;
 use lex::IntoLexer;
 use log::error;
 use parse::IntoParser;
-use pass::sem::sem;
-use std::io::Write;
+use pass::codegen;
+use pass::interp;
+use pass::irgen::irgen;
+use pass::lint::{lint, Allowances};
+use pass::sem::{sem_recovering, sem_table::SemTable};
+use std::io::{BufRead, Write};
 use thiserror::Error;
 use writer_iter::WriterIter;
 
 pub fn run_compiler(args: &cli::Cli) -> CompilerResult<()> {
-    let ast = scan::Scanner::new(&args.filename)?
+    let filename = args
+        .filename
+        .as_deref()
+        .expect("a filename is required without a subcommand");
+    let levels = args.warnings.levels()?;
+    let (ast, parse_errors) = scan::Scanner::new(filename)?
         .preprocess()
         .make_step(
             args,
-            args.print.get_preprocessor_writer()?,
+            args.command.get_preprocessor_writer()?,
             StopAfter::Preprocessing,
             "Stopping... (--stop-after preprocessing)",
         )?
         .into_lexer(true)
+        .strict(args.strict)
         .make_step(
             args,
-            args.print.get_token_writer()?,
+            args.command.get_token_writer()?,
             StopAfter::Lexing,
             "Stopping... (--stop-after lexing)",
         )?
         .into_parser()
-        .program()?;
-    args.print
-        .get_ast_writer()?
-        .map(|w| ast.print(w).expect("Failed to print AST"));
+        .program_recovering();
+    // The AST is printed even if parsing failed, with error nodes where it did.
+    if let Some(w) = args.command.get_ast_writer()? {
+        ast.print(w).expect("Failed to print AST");
+    }
+    if !parse_errors.is_empty() {
+        return Err(parse::ParseErrors::new(parse_errors).into());
+    }
     // println!("{:#?}", ast);
     if args.stop_after == StopAfter::Parsing {
         return Err(CompilerError::EarlyExit(
@@ -46,18 +64,97 @@ pub fn run_compiler(args: &cli::Cli) -> CompilerResult<()> {
         ));
     }
     // *Done(?): Implement sem
-    let mut sem_results = sem(&ast)?;
-    args.print.get_types_writer()?.map(|w| {
+    let (mut sem_results, sem_errors) = sem_recovering(&ast, args.strict);
+    let denied = report_warnings(&ast, &sem_results, &levels, sem_errors.is_ok());
+    sem_errors?;
+    denied?;
+    if let Some(w) = args.command.get_types_writer()? {
         sem_results
             .types
             .print_node_types(w)
-            .expect("Failed to print types")
-    });
+            .expect("Failed to print types");
+    }
     if args.stop_after == StopAfter::Sem {
         return Err(CompilerError::EarlyExit("Stopping... (--stop-after sem)"));
     }
-    // TODO: Implement irgen
-    // TODO: Implement codegen/binary-gen
+    let ir = irgen(&ast, &sem_results);
+    if let Some(w) = args.command.get_ir_writer()? {
+        ir.print(w).expect("Failed to print IR");
+    }
+    if args.stop_after == StopAfter::IrGen {
+        return Err(CompilerError::EarlyExit(
+            "Stopping... (--stop-after ir-gen)",
+        ));
+    }
+    match args.emit {
+        Emit::Exe => {
+            let asm = codegen::x86::emit(&ir);
+            if let Some(mut w) = args.command.get_asm_writer()? {
+                w.write_all(asm.as_bytes())
+                    .and_then(|_| w.flush())
+                    .expect("Failed to print assembly");
+            }
+            codegen::link(&asm, &args.out_file())?;
+        }
+        Emit::C => std::fs::write(args.out_file(), codegen::c::emit(&ir))
+            .map_err(pass::codegen::CodegenError::from)?,
+    }
+    Ok(())
+}
+/// Interprets the program in `filename` and returns its exit code.
+pub fn run_interpreter<'io>(
+    filename: &str,
+    strict: bool,
+    warnings: &cli::WarningArgs,
+    input: Box<dyn BufRead + 'io>,
+    output: Box<dyn Write + 'io>,
+) -> CompilerResult<i32> {
+    let levels = warnings.levels()?;
+    let ast = scan::Scanner::new(filename)?
+        .preprocess()
+        .into_lexer(true)
+        .strict(strict)
+        .into_parser()
+        .program()?;
+    let (sem_results, sem_errors) = sem_recovering(&ast, strict);
+    let denied = report_warnings(&ast, &sem_results, &levels, sem_errors.is_ok());
+    sem_errors?;
+    denied?;
+    Ok(interp::interpret(&ast, &sem_results, input, output)?)
+}
+/// Logs the warnings of sem and the lint pass at their levels, fails if any of them is denied.
+/// The ones an attribute allows are left out (see `Allowances`).
+/// Sem's are reported even if it failed, the lint pass only runs on programs that passed it.
+fn report_warnings<'a>(
+    ast: &'a parse::ast::Program,
+    sem_results: &SemTable<'a>,
+    levels: &Levels,
+    sem_passed: bool,
+) -> CompilerResult<()> {
+    let allowances = Allowances::new(ast, sem_results);
+    let mut sources = Sources::default();
+    let mut denied = 0;
+    let sem_warnings = sem_results
+        .warnings
+        .iter()
+        .filter(|w| !allowances.allows(w.code(), w.span()))
+        .map(Diagnostic::from);
+    let lint_warnings = match sem_passed {
+        true => lint(ast, sem_results),
+        false => Vec::new(),
+    };
+    for diagnostic in sem_warnings
+        .chain(lint_warnings.iter().map(Diagnostic::from))
+        .filter_map(|d| levels.apply(d))
+    {
+        if diagnostic.severity == Severity::Error {
+            denied += 1;
+        }
+        diagnostic.log(&mut sources);
+    }
+    if denied > 0 {
+        return Err(diagnostic::DeniedWarnings(denied).into());
+    }
     Ok(())
 }
 pub trait MaybeStop<Item: std::fmt::Display + 'static>: WriterIter<Item> + 'static {
@@ -93,6 +190,14 @@ impl<Item: std::fmt::Display + 'static, I: Iterator<Item = Item> + 'static> Mayb
 pub fn init_logger() {
     env_logger::Builder::from_env(Env::default().default_filter_or("warn"))
         .format(|f, record| {
+            // Diagnostics start with their own severity, or are already JSON.
+            if record.target() == diagnostic::LOG_TARGET {
+                return writeln!(f, "{}", record.args());
+            }
+            if diagnostic::error_format() == ErrorFormat::Json {
+                let message = record.args().to_string();
+                return writeln!(f, "{}", diagnostic::json::message(record.level(), &message));
+            }
             let level = match record.level() {
                 log::Level::Error => "error".red(),
                 log::Level::Warn => "warning".yellow(),
@@ -106,14 +211,51 @@ pub fn init_logger() {
         .init();
 }
 
+/// Logs every one of the syntax or semantic errors, followed by their count.
+/// *Note: The counts only summarize the diagnostics, they are left out of the JSON output.
+pub fn log_error(err: &CompilerError) {
+    log_error_in(err, &mut Sources::default())
+}
+/// Same as `log_error`, with the source lines of the errors taken from `sources`.
+pub fn log_error_in(err: &CompilerError, sources: &mut Sources) {
+    let summarize = diagnostic::error_format() == ErrorFormat::Human;
+    fn log_errors<E>(errors: &Errors<E>, sources: &mut Sources, summarize: bool)
+    where
+        E: PhaseError,
+        for<'e> Diagnostic: From<&'e E>,
+    {
+        errors.iter().for_each(|e| Diagnostic::from(e).log(sources));
+        if summarize {
+            error!("{}", errors.summary());
+        }
+    }
+    match err {
+        CompilerError::SemanticError(errors) => log_errors(errors, sources, summarize),
+        CompilerError::ParserError(errors) => log_errors(errors, sources, summarize),
+        CompilerError::ScannerError(e) => Diagnostic::from(e).log(sources),
+        CompilerError::RuntimeError(e) => Diagnostic::from(e).log(sources),
+        CompilerError::CliError(e) => Diagnostic::from(e).log(sources),
+        CompilerError::DeniedWarnings(_) if !summarize => (),
+        err => error!("{}", err),
+    }
+}
+
 pub type CompilerResult<T> = Result<T, CompilerError>;
 #[derive(Error, Debug)]
 #[error("{0}")]
 pub enum CompilerError {
     EarlyExit(&'static str),
 
-    SemanticError(#[from] pass::sem::SemanticError),
-    ParserError(#[from] parse::ParseErr),
+    SemanticError(#[from] pass::sem::SemanticErrors),
+    ParserError(#[from] parse::ParseErrors),
     ScannerError(#[from] scan::ScanErr),
     CliError(#[from] cli::CliErr),
+    DeniedWarnings(#[from] diagnostic::DeniedWarnings),
+    CodegenError(#[from] pass::codegen::CodegenError),
+    RuntimeError(#[from] pass::interp::RuntimeError),
+}
+impl From<parse::ParseErr> for CompilerError {
+    fn from(error: parse::ParseErr) -> Self {
+        Self::ParserError(error.into())
+    }
 }
@@ -62,7 +62,7 @@ impl<I: Iterator> LongPeekableIterator<I> {
     }
     fn push_next_to_queue(&mut self) {
         match self.queue.back() {
-            Some(None) => return, // iterator is exhausted
+            Some(None) => (), // iterator is exhausted
             _ => self.queue.push_back(self.iter.next()),
         }
     }
@@ -1,23 +1,81 @@
 use llamac::cli;
+use llamac::cli::Command;
+use llamac::cli::RunArgs;
+use llamac::diagnostic::codes::Code;
 use llamac::init_logger;
+use llamac::log_error;
+use llamac::repl::Repl;
 use llamac::run_compiler;
+use llamac::run_interpreter;
 use llamac::CompilerError;
 use log::error;
 use log::info;
+use std::io::BufWriter;
+use std::io::IsTerminal;
 use std::process::ExitCode;
 
+/// Deeply recursive programs (and their interpretation) need more than the default stack.
+const STACK_SIZE: usize = 1 << 30;
+
 fn main() -> ExitCode {
     init_logger();
     let args = cli::Cli::parse();
-    let res = run_compiler(&args);
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(&args))
+        .expect("Failed to spawn the main thread")
+        .join()
+        .unwrap_or(ExitCode::FAILURE)
+}
+
+fn run(args: &cli::Cli) -> ExitCode {
+    if let Some(code) = &args.explain {
+        return explain(code);
+    }
+    let res = match &args.command {
+        Some(Command::Run(RunArgs {
+            filename,
+            strict,
+            warnings,
+        })) => run_interpreter(
+            filename,
+            *strict,
+            warnings,
+            Box::new(std::io::stdin().lock()),
+            Box::new(BufWriter::new(std::io::stdout().lock())),
+        )
+        .map(|code| ExitCode::from(code as u8)),
+        Some(Command::Repl) => Repl::new(
+            Box::new(std::io::stdin().lock()),
+            Box::new(std::io::stdout().lock()),
+            std::io::stdin().is_terminal(),
+        )
+        .run()
+        .map(|code| ExitCode::from(code as u8)),
+        _ => run_compiler(args).map(|_| ExitCode::SUCCESS),
+    };
     match res {
-        Ok(_) => ExitCode::SUCCESS,
+        Ok(code) => code,
         Err(CompilerError::EarlyExit(msg)) => {
             info!("{}", msg);
             ExitCode::SUCCESS
         }
         Err(err) => {
-            error!("{}", err);
+            log_error(&err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints what `--explain` says about `code`, given by code or name.
+fn explain(code: &str) -> ExitCode {
+    match Code::lookup(code) {
+        Some(code) => {
+            println!("{} ({})\n\n{}", code.code, code.name, code.explanation);
+            ExitCode::SUCCESS
+        }
+        None => {
+            error!("{} is not the code or name of an error or warning", code);
             ExitCode::FAILURE
         }
     }
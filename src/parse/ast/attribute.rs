@@ -0,0 +1,56 @@
+use super::Span;
+
+/// A comment of the form `(*@ name(arg, ...) *)` or `--@ name(arg, ...)`, it applies to the
+/// binding that follows it (see `pass::lint`).
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: String,
+    pub args: Vec<String>,
+    pub span: Span,
+}
+impl Attribute {
+    /// The attribute a comment holds, if it is one.
+    pub fn from_comment(comment: &str, span: Span) -> Option<Self> {
+        let contents = match comment.strip_prefix("(*") {
+            Some(rest) => rest.strip_suffix("*)")?,
+            None => comment.strip_prefix("--")?,
+        };
+        let (name, args) = contents.trim().strip_prefix('@')?.split_once('(')?;
+        let args = args.trim_end().strip_suffix(')')?;
+        Some(Self {
+            name: name.trim().to_string(),
+            args: args
+                .split(',')
+                .map(str::trim)
+                .filter(|arg| !arg.is_empty())
+                .map(str::to_string)
+                .collect(),
+            span,
+        })
+    }
+    /// Whether this allows the lint `name`.
+    pub fn allows(&self, name: &str) -> bool {
+        self.name == "allow" && self.args.iter().any(|arg| arg == name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attributes_are_read_from_either_kind_of_comment() {
+        let attribute =
+            Attribute::from_comment("(*@ allow(unused_binding, unused_type) *)", Span::default())
+                .expect("is an attribute");
+        assert_eq!(attribute.name, "allow");
+        assert_eq!(attribute.args, ["unused_binding", "unused_type"]);
+        assert!(attribute.allows("unused_type"));
+        let attribute = Attribute::from_comment("--@allow(unassigned_mutable)", Span::default())
+            .expect("is an attribute");
+        assert!(attribute.allows("unassigned_mutable"));
+        assert!(!attribute.allows("unused_binding"));
+        assert!(Attribute::from_comment("(* allow(unused_binding) *)", Span::default()).is_none());
+        assert!(Attribute::from_comment("-- @allow unused", Span::default()).is_none());
+    }
+}
@@ -2,6 +2,8 @@ use std::{collections::HashMap, fmt::Debug, hash::Hash};
 
 use enum_dispatch::enum_dispatch;
 
+use crate::pass::sem::builtins::Builtin;
+
 use super::{
     annotation::TypeAnnotation,
     def::{Constr, Def, Definition, Par, TDef},
@@ -26,18 +28,19 @@ pub enum NodeRef<'a> {
     For(&'a For), // Added to help with lookup table in semantic analysis.
     Clause(&'a Clause),
     Pattern(&'a Pattern),
+    Builtin(&'a Builtin), // The standard library, which has no nodes of its own.
 }
 #[enum_dispatch]
 pub trait NodeRefInner {
     // *DONE: Think of a way to implement this function here only once.
-    fn into_ptr(&self) -> *const ();
+    fn as_ptr(&self) -> *const ();
     fn get_span(&self) -> Span;
 }
 macro_rules! impl_node_ref_inner_with_span {
     ($($t:ty),*) => {
         $(
             impl<'a> NodeRefInner for &'a $t {
-                fn into_ptr(&self) -> *const () {
+                fn as_ptr(&self) -> *const () {
                     *self as *const $t as *const ()
                 }
                 fn get_span(&self) -> Span {
@@ -51,7 +54,7 @@ macro_rules! impl_node_ref_inner_without_span {
     ($($t:ty),*) => {
         $(
             impl<'a> NodeRefInner for &'a $t {
-                fn into_ptr(&self) -> *const () {
+                fn as_ptr(&self) -> *const () {
                     *self as *const $t as *const ()
                 }
                 fn get_span(&self) -> Span {
@@ -63,8 +66,16 @@ macro_rules! impl_node_ref_inner_without_span {
     }
 }
 
-impl_node_ref_inner_with_span!(Def, TDef, Constr, Par, Expr, Pattern);
-impl_node_ref_inner_without_span!(Program, Definition, TypeAnnotation, For, Clause);
+impl_node_ref_inner_with_span!(Def, TDef, Constr, Par, Expr, Clause, Pattern);
+impl_node_ref_inner_without_span!(Program, Definition, TypeAnnotation, For);
+impl NodeRefInner for &Builtin {
+    fn as_ptr(&self) -> *const () {
+        *self as *const Builtin as *const ()
+    }
+    fn get_span(&self) -> Span {
+        Span::default()
+    }
+}
 
 impl<'a, T> DataMap<'a, T> {
     pub fn new(_: &'a Program) -> Self {
@@ -87,14 +98,17 @@ impl<'a, T> DataMap<'a, T> {
     pub fn get_node_mut(&mut self, key: &NodeRef<'a>) -> Option<&mut T> {
         self.map.get_mut(key)
     }
-    pub fn iter(&self) -> std::collections::hash_map::Iter<NodeRef<'a>, T> {
+    pub fn remove(&mut self, key: &NodeRef<'a>) -> Option<T> {
+        self.map.remove(key)
+    }
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, NodeRef<'a>, T> {
         self.map.iter()
     }
 }
 impl<'a> PartialEq for NodeRef<'a> {
     fn eq(&self, other: &Self) -> bool {
         if std::mem::discriminant(self) == std::mem::discriminant(other) {
-            std::ptr::eq(self.into_ptr(), other.into_ptr())
+            std::ptr::eq(self.as_ptr(), other.as_ptr())
         } else {
             false
         }
@@ -103,7 +117,7 @@ impl<'a> PartialEq for NodeRef<'a> {
 impl<'a> Eq for NodeRef<'a> {}
 impl<'a> Hash for NodeRef<'a> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.into_ptr().hash(state)
+        self.as_ptr().hash(state)
     }
 }
 
@@ -151,6 +165,7 @@ mod test {
                 }],
                 span: Default::default(),
             })],
+            attributes: Vec::new(),
             // span: Span::default(),
         }
     }
@@ -4,6 +4,8 @@ use super::{annotation::TypeAnnotation, expr::Expr, Span};
 pub enum Definition {
     Let(Letdef),
     Type(Typedef),
+    /// A definition that failed to parse, see `Parser::program_recovering`.
+    Error(Span),
 }
 #[derive(Debug, Clone)]
 pub struct Letdef {
@@ -25,6 +25,8 @@ pub enum ExprKind {
     While(While),
     For(For),
     Match(Match),
+    /// Input that failed to parse, see `Parser::program_recovering`.
+    Error,
 }
 #[derive(Debug, Clone)]
 pub struct Expr {
@@ -92,8 +94,8 @@ pub enum UnopKind {
     Minus,
     Deref,
     Not,
-    // PlusFlt,
-    // MinusFlt,
+    PlusFlt,
+    MinusFlt,
     Delete,
 }
 #[derive(Debug, Clone, Display)]
@@ -116,15 +118,16 @@ pub enum BinopKind {
     Or,
     Semicolon,
     Assign,
-    // AddFlt,
-    // SubFlt,
-    // MulFlt,
-    // DivFlt,
+    AddFlt,
+    SubFlt,
+    MulFlt,
+    DivFlt,
 }
 #[derive(Debug, Clone)]
 pub struct Clause {
     pub pattern: Pattern,
     pub expr: Expr,
+    pub span: Span,
 }
 #[derive(Debug, Clone)]
 pub struct Pattern {
@@ -232,6 +235,8 @@ impl From<&TokenKind> for UnopKind {
         match token_kind {
             TokenKind::Plus => Self::Plus,
             TokenKind::Minus => Self::Minus,
+            TokenKind::PlusDot => Self::PlusFlt,
+            TokenKind::MinusDot => Self::MinusFlt,
             TokenKind::Exclam => Self::Deref,
             TokenKind::Not => Self::Not,
             TokenKind::Delete => Self::Delete,
@@ -246,6 +251,10 @@ impl From<&TokenKind> for BinopKind {
             TokenKind::Minus => Self::Sub,
             TokenKind::Star => Self::Mul,
             TokenKind::Slash => Self::Div,
+            TokenKind::PlusDot => Self::AddFlt,
+            TokenKind::MinusDot => Self::SubFlt,
+            TokenKind::StarDot => Self::MulFlt,
+            TokenKind::SlashDot => Self::DivFlt,
             TokenKind::Mod => Self::Mod,
             TokenKind::DblStar => Self::Pow,
             TokenKind::Eq => Self::StrEq,
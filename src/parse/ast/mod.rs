@@ -1,4 +1,5 @@
 pub mod annotation;
+pub mod attribute;
 pub mod data_map;
 pub mod def;
 pub mod expr;
@@ -6,13 +7,21 @@ pub mod print;
 
 use crate::lex::token::Position;
 
-use self::def::Definition;
+use self::{attribute::Attribute, def::Definition};
 
 #[derive(Debug, Clone)]
 pub struct Program {
     pub definitions: Vec<Definition>,
+    /// The attribute comments found among the definitions, in order.
+    pub attributes: Vec<Attribute>,
 }
+/// One input of the REPL, either definitions to add or an expression to evaluate.
 #[derive(Debug, Clone)]
+pub enum ReplInput {
+    Definitions(Program),
+    Expr(expr::Expr),
+}
+#[derive(Debug, Clone, Default)]
 pub struct Span {
     pub start: Position,
     pub end: Position,
@@ -21,13 +30,12 @@ impl Span {
     pub fn new(start: Position, end: Position) -> Self {
         Self { start, end }
     }
-}
-impl Default for Span {
-    fn default() -> Self {
-        Self {
-            start: Default::default(),
-            end: Default::default(),
-        }
+    /// Whether `other` lies within this span.
+    pub fn contains(&self, other: &Span) -> bool {
+        let at = |position: &Position| (position.line, position.column);
+        self.start.filename == other.start.filename
+            && at(&self.start) <= at(&other.start)
+            && at(&other.end) <= at(&self.end)
     }
 }
 impl std::fmt::Display for Span {
@@ -8,7 +8,7 @@ impl Program {
     pub fn print(&self, w: impl std::io::Write) -> std::io::Result<()> {
         // let config = ptree::PrintConfig::from_env();
         // ptree::write_tree_with(&NodeRef::Program(&self), w, &config)
-        ptree::write_tree(&NodeRef::Program(&self), w)
+        ptree::write_tree(&NodeRef::Program(self), w)
     }
 }
 
@@ -72,6 +72,7 @@ impl<'a> TreeItem for NodeRef<'a> {
                         t.tdefs.len(),
                         if t.tdefs.len() == 1 { "" } else { "s" }
                     ),
+                    Definition::Error(_) => "Syntax error".to_string(),
                 },
                 NodeRef::Def(d) => {
                     let def_type = match &d.kind {
@@ -138,9 +139,9 @@ impl<'a> TreeItem for NodeRef<'a> {
                     ExprKind::Dim(Dim { id, dim }) =>
                         format!("dim call for id {} and dimension {}", id, dim),
                     ExprKind::New(t) => format!("New on type {}", t),
-                    ExprKind::LetIn { .. } => format!("Let In expression"),
-                    ExprKind::If { .. } => format!("If expression"),
-                    ExprKind::While(While { cond: _, body: _ }) => format!("While expression"),
+                    ExprKind::LetIn { .. } => "Let In expression".to_string(),
+                    ExprKind::If { .. } => "If expression".to_string(),
+                    ExprKind::While(While { cond: _, body: _ }) => "While expression".to_string(),
                     ExprKind::For(For {
                         id,
                         from: _,
@@ -164,9 +165,11 @@ impl<'a> TreeItem for NodeRef<'a> {
                         clauses.len(),
                         if clauses.len() == 1 { "" } else { "s" }
                     ),
+                    ExprKind::Error => "Syntax error".to_string(),
                 },
                 NodeRef::For(_) => panic!("For should not be a TreeItem"),
-                NodeRef::Clause(_) => format!("Clause"),
+                NodeRef::Clause(_) => "Clause".to_string(),
+                NodeRef::Builtin(b) => format!("Builtin '{}'", b.id),
                 NodeRef::Pattern(p) => match &p.kind {
                     PatternKind::IntLiteral(i) => format!("Int Literal '{}'", i),
                     PatternKind::FloatLiteral(f) => format!("Float Literal '{}'", f),
@@ -189,8 +192,8 @@ impl<'a> TreeItem for NodeRef<'a> {
             })
         )
     }
-    fn children(&self) -> Cow<[Self::Child]> {
-        let mut children: Vec<NodeRef> = self.children().unwrap_or(Vec::new());
+    fn children(&self) -> Cow<'_, [Self::Child]> {
+        let mut children: Vec<NodeRef> = self.children().unwrap_or_default();
         children.retain(|child| !matches!(child, NodeRef::Type(_)));
         Cow::from(children)
     }
@@ -203,6 +206,7 @@ impl<'a> NodeRef<'a> {
             NodeRef::Definition(d) => match d {
                 Definition::Let(l) => Some(l.defs.iter().map(NodeRef::Def).collect()),
                 Definition::Type(t) => Some(t.tdefs.iter().map(NodeRef::TDef).collect()),
+                Definition::Error(_) => None,
             },
             NodeRef::Def(d) => match &d.kind {
                 DefKind::Const { expr } => {
@@ -227,7 +231,7 @@ impl<'a> NodeRef<'a> {
                     if let Some(t) = &d.type_ {
                         children.push(NodeRef::Type(t));
                     }
-                    children.extend(pars.iter().map(|p| NodeRef::Par(p)));
+                    children.extend(pars.iter().map(NodeRef::Par));
                     children.push(NodeRef::Expr(expr));
                     Some(children)
                 }
@@ -250,7 +254,8 @@ impl<'a> NodeRef<'a> {
                 | ExprKind::FloatLiteral(_)
                 | ExprKind::CharLiteral(_)
                 | ExprKind::StringLiteral(_)
-                | ExprKind::BoolLiteral(_) => None,
+                | ExprKind::BoolLiteral(_)
+                | ExprKind::Error => None,
                 ExprKind::Tuple(ts) => Some(ts.iter().map(NodeRef::Expr).collect()),
                 ExprKind::Unop(Unop { op: _, operand }) => Some(vec![NodeRef::Expr(operand)]),
                 ExprKind::Binop(Binop { lhs, op: _, rhs }) => {
@@ -302,7 +307,7 @@ impl<'a> NodeRef<'a> {
                     Some(children)
                 }
             },
-            NodeRef::For(_) => None,
+            NodeRef::For(_) | NodeRef::Builtin(_) => None,
             NodeRef::Clause(c) => Some(vec![NodeRef::Pattern(&c.pattern), NodeRef::Expr(&c.expr)]),
             NodeRef::Pattern(p) => match &p.kind {
                 PatternKind::IntLiteral(_)
@@ -332,7 +337,7 @@ impl<'a, 'b> std::io::Write for DisplayWriter<'a, 'b> {
     fn write(&mut self, bytes: &[u8]) -> std::result::Result<usize, std::io::Error> {
         self.0
             .write_str(&String::from_utf8_lossy(bytes))
-            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+            .map_err(std::io::Error::other)?;
 
         Ok(bytes.len())
     }
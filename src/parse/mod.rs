@@ -5,12 +5,39 @@ use std::ops::Neg;
 use thiserror::Error;
 
 use crate::{
+    diagnostic::{
+        codes::{self, Code},
+        Diagnostic, Errors, PhaseError,
+    },
     lex::token::{Position, Token, TokenKind, TokenValue},
     long_peekable::{LongPeek, LongPeekableIterator},
 };
 
 use self::ast::Span;
 
+// ! Implementation notes:
+// !   Recovery is panic-mode: an error is recorded, the input is skipped up to a token that
+// !   a construct can go on from, and an error node takes the place of what failed to parse.
+// !   Definitions recover on `let` and `type`, bodies of definitions, `begin`, loops and
+// !   match clauses also on `in`, `end`, `done` and `|`. Constructs opened while skipping
+// !   are skipped whole, so that their `end` or `done` is not taken for a boundary.
+// !   A `let` inside an expression may start a `let ... in` as well as the next definition,
+// !   skipping stops at both and the expression goes on with the former (see `at_let_in`).
+// !   An error found before any token is accepted after the previous one is taken to follow
+// !   from it, and is not reported.
+
+/// Where definitions go on from after a syntax error.
+const DEFINITION_SYNC: [TokenKind; 2] = [TokenKind::Let, TokenKind::Type];
+/// Where expressions go on from after a syntax error.
+const EXPR_SYNC: [TokenKind; 6] = [
+    TokenKind::Let,
+    TokenKind::Type,
+    TokenKind::In,
+    TokenKind::End,
+    TokenKind::Done,
+    TokenKind::Bar,
+];
+
 macro_rules! expect_any_of {
     ($self:ident, $($kind:path $(| $kinds:path)* => $expr:expr),+) => {
         match $self.peek_token().map(|t| &t.kind) {
@@ -22,7 +49,7 @@ macro_rules! expect_any_of {
                 }
             )*
             _ => Err(ParseErr::UnexpectedToken(
-                $self.peek_token().cloned(),
+                $self.peek_token().cloned().map(Box::new),
                 vec![$($kind $(,$kinds)*),*]
             ))
 
@@ -33,6 +60,14 @@ macro_rules! expect_any_of {
 pub struct Parser<L: Iterator<Item = Token>> {
     lexer: LongPeekableIterator<L>,
     consumed_token_span: Span,
+    /// The attribute comments skipped so far, see `skip_comments`.
+    attributes: Vec<ast::attribute::Attribute>,
+    /// Whether syntax errors are recovered from, see `program_recovering`.
+    recovering: bool,
+    errors: Vec<ParseErr>,
+    /// How many tokens have been consumed, and how many had been after the last recovery.
+    consumed: usize,
+    recovered_at: Option<usize>,
 }
 
 impl<L: Iterator<Item = Token>> Parser<L> {
@@ -40,19 +75,88 @@ impl<L: Iterator<Item = Token>> Parser<L> {
         Self {
             lexer: lexer.long_peekable(),
             consumed_token_span: Default::default(),
+            attributes: Vec::new(),
+            recovering: false,
+            errors: Vec::new(),
+            consumed: 0,
+            recovered_at: None,
         }
     }
 
-    pub fn program(&mut self) -> ParseResult<ast::Program> {
+    /// Fails with every syntax error in the input, see `program_recovering`.
+    pub fn program(&mut self) -> Result<ast::Program, ParseErrors> {
+        let (program, errors) = self.program_recovering();
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(ParseErrors::new(errors))
+        }
+    }
+    /// Parses the whole input, even past syntax errors. Returns the program, with error nodes
+    /// in place of the definitions and expressions that failed to parse, and every error found.
+    pub fn program_recovering(&mut self) -> (ast::Program, Vec<ParseErr>) {
+        self.recovering = true;
         let mut definitions: Vec<ast::def::Definition> = Vec::new();
-        while self.accept(&TokenKind::EOF).is_none() {
+        while self.accept(&TokenKind::EOF).is_none() && self.peek_token().is_some() {
+            let from = self.next_token_start();
             let definition = expect_any_of!(self,
                 TokenKind::Let  => |_| self.letdef().map(ast::def::Definition::Let),
                 TokenKind::Type => |_| self.typedef().map(ast::def::Definition::Type)
-            )?;
-            definitions.push(definition);
+            );
+            match definition {
+                Ok(definition) => definitions.push(definition),
+                Err(error) => {
+                    self.recover(error, &DEFINITION_SYNC);
+                    definitions.push(ast::def::Definition::Error(self.skipped_span(from)));
+                }
+            }
         }
-        Ok(ast::Program { definitions })
+        let program = ast::Program {
+            definitions,
+            attributes: std::mem::take(&mut self.attributes),
+        };
+        (program, std::mem::take(&mut self.errors))
+    }
+    /// Definitions, or an expression that may itself start with `let ... in`.
+    pub fn repl_input(&mut self) -> ParseResult<ast::ReplInput> {
+        let mut definitions: Vec<ast::def::Definition> = Vec::new();
+        while self.accept(&TokenKind::EOF).is_none() {
+            match self.peek_token().map(|t| &t.kind) {
+                Some(TokenKind::Type) => {
+                    self.consume_token();
+                    definitions.push(ast::def::Definition::Type(self.typedef()?));
+                }
+                Some(TokenKind::Let) => {
+                    self.consume_token();
+                    let letdef = self.letdef()?;
+                    if !definitions.is_empty() || self.accept(&TokenKind::In).is_none() {
+                        definitions.push(ast::def::Definition::Let(letdef));
+                        continue;
+                    }
+                    let expr = Box::new(self.expr()?);
+                    self.expect(TokenKind::EOF)?;
+                    return Ok(ast::ReplInput::Expr(ast::expr::Expr {
+                        span: Span::new(letdef.span.start.clone(), expr.span.end.clone()),
+                        kind: ast::expr::ExprKind::LetIn(ast::expr::LetIn { letdef, expr }),
+                    }));
+                }
+                _ if definitions.is_empty() => {
+                    let expr = self.expr()?;
+                    self.expect(TokenKind::EOF)?;
+                    return Ok(ast::ReplInput::Expr(expr));
+                }
+                _ => {
+                    return Err(ParseErr::UnexpectedToken(
+                        self.peek_token().cloned().map(Box::new),
+                        vec![TokenKind::Let, TokenKind::Type, TokenKind::EOF],
+                    ))
+                }
+            }
+        }
+        Ok(ast::ReplInput::Definitions(ast::Program {
+            definitions,
+            attributes: std::mem::take(&mut self.attributes),
+        }))
     }
     fn letdef(&mut self) -> ParseResult<ast::def::Letdef> {
         let from = self.consumed_token_span.start.clone();
@@ -77,7 +181,7 @@ impl<L: Iterator<Item = Token>> Parser<L> {
                     None
                 };
                 self.expect(TokenKind::Eq)?;
-                let expr = self.expr()?;
+                let expr = self.expr_or_error()?;
                 let span = Span::new(from, self.consumed_token_span.end.clone());
                 if pars.is_empty() {
                     Ok(ast::def::Def { id, type_, kind: ast::def::DefKind::Const{ expr }, span })
@@ -91,20 +195,23 @@ impl<L: Iterator<Item = Token>> Parser<L> {
             TokenKind::Mutable => |_| {
                 let from = self.consumed_token_span.start.clone();
                 let id = self.expect(TokenKind::IdLower)?.extract_value();
-                let dims = if self.accept(&TokenKind::LBracket).is_some() {
+                let dims = if let Some(bracket) = self.accept(&TokenKind::LBracket) {
                     let dims = self.match_at_least_one(Self::expr, &TokenKind::Comma)?;
-                    self.expect(TokenKind::RBracket)?;
+                    self.expect_closing(TokenKind::RBracket, &bracket)?;
                     dims
                 } else {
                     Vec::new()
                 };
+                // The annotation of an array is its elements' type, that of a variable is
+                // the type of what its ref points to.
                 let type_ = if self.accept(&TokenKind::Colon).is_some() {
-                    Some(ast::annotation::TypeAnnotation::Ref(Box::new(self.r#type()?)))
+                    Some(self.r#type()?)
                 } else {
                     None
                 };
                 let span = Span::new(from, self.consumed_token_span.end.clone());
                 if dims.is_empty() {
+                    let type_ = type_.map(|t| ast::annotation::TypeAnnotation::Ref(Box::new(t)));
                     Ok(ast::def::Def { id, type_, kind: ast::def::DefKind::Variable, span })
                 } else {
                     Ok(ast::def::Def{ id, type_, kind: ast::def::DefKind::Array{dims}, span })
@@ -163,11 +270,11 @@ impl<L: Iterator<Item = Token>> Parser<L> {
                 })
             },
             TokenKind::LParen => |token: Token| {
-                let from = token.from;
                 let id = self.expect(TokenKind::IdLower)?.extract_value();
                 self.expect(TokenKind::Colon)?;
                 let type_ = self.r#type()?;
-                self.expect(TokenKind::RParen)?;
+                self.expect_closing(TokenKind::RParen, &token)?;
+                let from = token.from;
                 Ok(ast::def::Par {
                     id,
                     type_: Some(type_),
@@ -191,22 +298,22 @@ impl<L: Iterator<Item = Token>> Parser<L> {
         let mut t = expect_any_of!(self,
             TokenKind::Unit | TokenKind::Int | TokenKind::Char
             | TokenKind::Bool | TokenKind::Float  => |token: Token| Ok((&token.kind).into()),
-            TokenKind::LParen => |_| {
+            TokenKind::LParen => |paren: Token| {
                 let t = self
                     .match_at_least_one(Self::r#type, &TokenKind::Comma)
                     .map(ast::annotation::TypeAnnotation::maybe_tuple)?;
-                self.expect(TokenKind::RParen)?;
+                self.expect_closing(TokenKind::RParen, &paren)?;
                 Ok(t)
             },
             TokenKind::Array => |_| {
-                let dim_cnt = if self.accept(&TokenKind::LBracket).is_some() {
+                let dim_cnt = if let Some(bracket) = self.accept(&TokenKind::LBracket) {
                     self.expect(TokenKind::Star)?;
                     let mut dim_cnt = 1;
                     while self.accept(&TokenKind::Comma).is_some() {
                         self.expect(TokenKind::Star)?;
                         dim_cnt += 1;
                     }
-                    self.expect(TokenKind::RBracket)?;
+                    self.expect_closing(TokenKind::RBracket, &bracket)?;
                     dim_cnt
                 } else {
                     1
@@ -231,14 +338,32 @@ impl<L: Iterator<Item = Token>> Parser<L> {
     }
     fn expr0(&mut self) -> ParseResult<ast::expr::Expr> {
         let mut letdefs = Vec::new();
-        while self.accept(&TokenKind::Let).is_some() {
+        let mut missing_body = None;
+        while let Some(let_token) = self.accept(&TokenKind::Let) {
             letdefs.push(self.letdef()?);
-            self.expect(TokenKind::In)?;
+            if self.accept(&TokenKind::In).is_none() {
+                let error = ParseErr::LetWithoutIn {
+                    let_at: let_token.into_span(),
+                    found: self.peek_token().cloned().map(Box::new),
+                };
+                let from = self.next_token_start();
+                self.recover_or_fail(error, &EXPR_SYNC)?;
+                if self.at_let_in() {
+                    continue;
+                }
+                if self.accept(&TokenKind::In).is_none() {
+                    missing_body = Some(self.error_node(from));
+                    break;
+                }
+            }
         }
         if letdefs.is_empty() {
             self.expr1()
         } else {
-            let expr = self.expr1()?;
+            let expr = match missing_body {
+                Some(error_node) => error_node,
+                None => self.expr1()?,
+            };
             let end = expr.span.end.clone();
             Ok(letdefs.into_iter().rfold(expr, |expr, letdef| {
                 let expr = Box::new(expr);
@@ -263,9 +388,9 @@ impl<L: Iterator<Item = Token>> Parser<L> {
     }
     fn expr2(&mut self) -> ParseResult<ast::expr::Expr> {
         if let Some(token) = self.accept(&TokenKind::If) {
-            let from = token.from;
             let cond = Box::new(self.expr()?);
-            self.expect(TokenKind::Then)?;
+            self.expect_keyword(TokenKind::Then, &token)?;
+            let from = token.from;
             let then_body = Box::new(self.expr()?);
             let else_body = if self.accept(&TokenKind::Else).is_some() {
                 Some(Box::new(self.expr()?))
@@ -346,11 +471,11 @@ impl<L: Iterator<Item = Token>> Parser<L> {
         }
     }
     fn expr7(&mut self) -> ParseResult<ast::expr::Expr> {
-        const OPS: [Option<&TokenKind>; 2] = [
+        const OPS: [Option<&TokenKind>; 4] = [
             Some(&TokenKind::Plus),
             Some(&TokenKind::Minus),
-            // Some(&TokenKind::PlusDot),
-            // Some(&TokenKind::MinusDot),
+            Some(&TokenKind::PlusDot),
+            Some(&TokenKind::MinusDot),
         ];
         let mut lhs = self.expr8()?;
         while let Some(Some(op)) = OPS
@@ -371,12 +496,12 @@ impl<L: Iterator<Item = Token>> Parser<L> {
         Ok(lhs)
     }
     fn expr8(&mut self) -> ParseResult<ast::expr::Expr> {
-        const OPS: [Option<&TokenKind>; 3] = [
+        const OPS: [Option<&TokenKind>; 5] = [
             Some(&TokenKind::Star),
             Some(&TokenKind::Slash),
             Some(&TokenKind::Mod),
-            // Some(&TokenKind::StarDot),
-            // Some(&TokenKind::SlashDot),
+            Some(&TokenKind::StarDot),
+            Some(&TokenKind::SlashDot),
         ];
         let mut lhs = self.expr9()?;
         while let Some(Some(op)) = OPS
@@ -413,9 +538,11 @@ impl<L: Iterator<Item = Token>> Parser<L> {
         }
     }
     fn expr10(&mut self) -> ParseResult<ast::expr::Expr> {
-        const OPS: [Option<&TokenKind>; 4] = [
+        const OPS: [Option<&TokenKind>; 6] = [
             Some(&TokenKind::Plus),
             Some(&TokenKind::Minus),
+            Some(&TokenKind::PlusDot),
+            Some(&TokenKind::MinusDot),
             Some(&TokenKind::Not),
             Some(&TokenKind::Delete),
         ];
@@ -470,7 +597,7 @@ impl<L: Iterator<Item = Token>> Parser<L> {
     }
     fn expr12(&mut self) -> ParseResult<ast::expr::Expr> {
         let deref_tokens = self.accept_many(&TokenKind::Exclam);
-        if deref_tokens.len() > 0 {
+        if !deref_tokens.is_empty() {
             let inner_expr = self.expr13()?;
             Ok(deref_tokens
                 .into_iter()
@@ -488,10 +615,10 @@ impl<L: Iterator<Item = Token>> Parser<L> {
     fn expr13(&mut self) -> ParseResult<ast::expr::Expr> {
         if let Some(token) = self.accept(&TokenKind::IdLower) {
             let (id_span, id) = token.into_span_and_value::<String>();
-            if self.accept(&TokenKind::LBracket).is_some() {
+            if let Some(bracket) = self.accept(&TokenKind::LBracket) {
                 let from = id_span.start;
                 let indexes = self.match_at_least_one(Self::expr, &TokenKind::Comma)?;
-                let to = self.expect(TokenKind::RBracket)?.to;
+                let to = self.expect_closing(TokenKind::RBracket, &bracket)?.to;
                 Ok(ast::expr::Expr {
                     span: Span::new(from, to),
                     kind: ast::expr::ExprKind::ArrayAccess(ast::expr::ArrayAccess { id, indexes }),
@@ -525,18 +652,17 @@ impl<L: Iterator<Item = Token>> Parser<L> {
                 Ok(ast::expr::Expr::from_literal(token))
             },
             TokenKind::LParen => |token: Token| {
-                let from = token.from;
                 if let Some(rparen_token) = self.accept(&TokenKind::RParen) {
                     Ok(ast::expr::Expr {
-                        span: Span::new(from, rparen_token.to),
+                        span: Span::new(token.from, rparen_token.to),
                         kind: ast::expr::ExprKind::UnitLiteral,
                     })
                 } else {
                     let mut retval = ast::expr::Expr::maybe_tuple(
                         self.match_at_least_one(Self::expr, &TokenKind::Comma)?
                     );
-                    let to = self.expect(TokenKind::RParen)?.to;
-                    retval.span = Span::new(from, to);
+                    let to = self.expect_closing(TokenKind::RParen, &token)?.to;
+                    retval.span = Span::new(token.from, to);
                     Ok(retval)
                 }
             },
@@ -557,65 +683,81 @@ impl<L: Iterator<Item = Token>> Parser<L> {
                 })
             },
             TokenKind::Begin => |token: Token| {
-                let from = token.from;
-                let mut expr = self.expr()?;
-                let to = self.expect(TokenKind::End)?.to;
-                expr.span = Span::new(from, to);
+                let mut expr = self.expr_or_error()?;
+                let to = self.expect_closing(TokenKind::End, &token)?.to;
+                expr.span = Span::new(token.from, to);
                 Ok(expr)
             },
             TokenKind::While => |token: Token| {
-                let from = token.from;
                 let cond = Box::new(self.expr()?);
-                self.expect(TokenKind::Do)?;
-                let body = Box::new(self.expr()?);
-                let to = self.expect(TokenKind::Done)?.to;
+                self.expect_keyword(TokenKind::Do, &token)?;
+                let body = Box::new(self.expr_or_error()?);
+                let to = self.expect_closing(TokenKind::Done, &token)?.to;
                 Ok(ast::expr::Expr{
                     kind: ast::expr::ExprKind::While(ast::expr::While {cond, body}),
-                    span: Span::new(from, to)
+                    span: Span::new(token.from, to)
                 })
             },
             TokenKind::For => |token: Token| {
-                let span_from = token.from;
                 let id = self.expect(TokenKind::IdLower)?.extract_value();
-                self.expect(TokenKind::Eq)?;
+                self.expect_keyword(TokenKind::Eq, &token)?;
                 let from = Box::new(self.expr()?);
                 let ascending = expect_any_of!(self,
                     TokenKind::To => |_| Ok(true),
                     TokenKind::Downto => |_| Ok(false)
                 )?;
                 let to = Box::new(self.expr()?);
-                self.expect(TokenKind::Do)?;
-                let body = Box::new(self.expr()?);
-                let span_to = self.expect(TokenKind::Done)?.to;
+                self.expect_keyword(TokenKind::Do, &token)?;
+                let body = Box::new(self.expr_or_error()?);
+                let span_to = self.expect_closing(TokenKind::Done, &token)?.to;
                 Ok(ast::expr::Expr{
                     kind: ast::expr::ExprKind::For(ast::expr::For {id, from, ascending, to, body}),
-                    span: Span::new(span_from, span_to),
+                    span: Span::new(token.from, span_to),
                 })
             },
             TokenKind::Match => |token: Token| {
-                let from = token.from;
                 let to_match = Box::new(self.expr()?);
-                self.expect(TokenKind::With)?;
-                let clauses = self.match_at_least_one(Self::clause, &TokenKind::Bar)?;
-                let to = self.expect(TokenKind::End)?.to;
+                self.expect_keyword(TokenKind::With, &token)?;
+                let clauses = self.clauses()?;
+                let to = self.expect_closing(TokenKind::End, &token)?.to;
                 Ok(ast::expr::Expr{
                     kind: ast::expr::ExprKind::Match(ast::expr::Match {to_match, clauses}),
-                    span: Span::new(from, to)
+                    span: Span::new(token.from, to)
                 })
             }
         )
     }
+    /// Clauses that fail to parse before their body are left out when recovering.
+    fn clauses(&mut self) -> ParseResult<Vec<ast::expr::Clause>> {
+        let mut clauses = Vec::new();
+        loop {
+            match self.clause() {
+                Ok(clause) => clauses.push(clause),
+                Err(error) => self.recover_or_fail(error, &EXPR_SYNC)?,
+            }
+            if self.accept(&TokenKind::Bar).is_none() {
+                break;
+            }
+        }
+        Ok(clauses)
+    }
     fn clause(&mut self) -> ParseResult<ast::expr::Clause> {
         let pattern = self.pattern()?;
         self.expect(TokenKind::Arrow)?;
-        let expr = self.expr()?;
-        Ok(ast::expr::Clause { pattern, expr })
+        let expr = self.expr_or_error()?;
+        Ok(ast::expr::Clause {
+            span: Span::new(pattern.span.start.clone(), expr.span.end.clone()),
+            pattern,
+            expr,
+        })
     }
     fn pattern(&mut self) -> ParseResult<ast::expr::Pattern> {
         use TokenKind::*;
-        const FIRST_SET_TOKENS: [TokenKind; 11] = [
+        const FIRST_SET_TOKENS: [TokenKind; 13] = [
             Plus,
             Minus,
+            PlusDot,
+            MinusDot,
             IntLiteral,
             FloatLiteral,
             CharLiteral,
@@ -646,18 +788,18 @@ impl<L: Iterator<Item = Token>> Parser<L> {
         }
     }
     fn pattern_no_args(&mut self) -> ParseResult<ast::expr::Pattern> {
-        fn make_num_literal<N: TryFrom<TokenValue> + std::ops::Neg>(
+        fn make_num_literal<N>(
             op: TokenKind,
             from: Position,
             literal: Token,
             pattern_maker: impl Fn(N) -> ast::expr::PatternKind,
         ) -> ParseResult<ast::expr::Pattern>
         where
+            N: TryFrom<TokenValue> + Neg<Output = N>,
             <N as TryFrom<TokenValue>>::Error: std::fmt::Debug,
-            N: Neg<Output = N>,
         {
             let (span, mut value) = literal.into_span_and_value::<N>();
-            if op == TokenKind::Minus {
+            if matches!(op, TokenKind::Minus | TokenKind::MinusDot) {
                 value = -value;
             }
             Ok(ast::expr::Pattern {
@@ -689,6 +831,13 @@ impl<L: Iterator<Item = Token>> Parser<L> {
                     }
                 )
             },
+            TokenKind::PlusDot | TokenKind::MinusDot => |op: Token| {
+                expect_any_of!(self,
+                    TokenKind::FloatLiteral => |literal: Token| {
+                        make_num_literal(op.kind, op.from, literal, ast::expr::PatternKind::FloatLiteral)
+                    }
+                )
+            },
             TokenKind::CharLiteral => |literal: Token| {
                 make_pattern(literal, ast::expr::PatternKind::CharLiteral)
             },
@@ -725,15 +874,129 @@ impl<L: Iterator<Item = Token>> Parser<L> {
                 let mut patterns = self
                     .match_at_least_one(Self::pattern, &TokenKind::Comma)
                     .map(ast::expr::Pattern::maybe_tuple)?;
-                let to = self.expect(TokenKind::RParen)?.to;
+                let to = self.expect_closing(TokenKind::RParen, &paren)?.to;
                 patterns.span = Span::new(paren.from, to);
                 Ok(patterns)
             }
         )
     }
+    /// An expression, or an error node in place of one that fails to parse when recovering.
+    fn expr_or_error(&mut self) -> ParseResult<ast::expr::Expr> {
+        let from = self.next_token_start();
+        match self.expr() {
+            Ok(expr) => Ok(expr),
+            Err(error) => {
+                self.recover_or_fail(error, &EXPR_SYNC)?;
+                let skipped = self.error_node(from);
+                if !self.at_let_in() {
+                    return Ok(skipped);
+                }
+                // The rest of the expression follows what was skipped, as after a `;`.
+                let rest = self.expr_or_error()?;
+                Ok(ast::expr::Expr {
+                    span: Span::new(skipped.span.start.clone(), rest.span.end.clone()),
+                    kind: ast::expr::ExprKind::Binop(ast::expr::Binop {
+                        lhs: Box::new(skipped),
+                        op: (&TokenKind::Semicolon).into(),
+                        rhs: Box::new(rest),
+                    }),
+                })
+            }
+        }
+    }
+    /// Whether the next token is a `let` of a `let ... in`, rather than of a definition.
+    /// *Note: It is one if an `in` comes before the next `let` or `type`, which misses the
+    /// ones whose definitions have a `let ... in` of their own.
+    fn at_let_in(&mut self) -> bool {
+        if self.peek_token().map(|t| &t.kind) != Some(&TokenKind::Let) {
+            return false;
+        }
+        (1..)
+            .find_map(|n| match self.peek_token_nth(n).map(|t| &t.kind) {
+                Some(TokenKind::In) => Some(true),
+                Some(TokenKind::Let | TokenKind::Type | TokenKind::EOF) | None => Some(false),
+                Some(_) => None,
+            })
+            .unwrap_or(false)
+    }
+    /// Spans the input skipped since `from`.
+    fn error_node(&self, from: Position) -> ast::expr::Expr {
+        ast::expr::Expr {
+            kind: ast::expr::ExprKind::Error,
+            span: self.skipped_span(from),
+        }
+    }
+    fn skipped_span(&self, from: Position) -> Span {
+        let end = self.consumed_token_span.end.clone();
+        let to = if (end.line, end.column) < (from.line, from.column) {
+            from.clone()
+        } else {
+            end
+        };
+        Span::new(from, to)
+    }
+    fn next_token_start(&mut self) -> Position {
+        self.peek_token()
+            .map(|t| t.from.clone())
+            .unwrap_or_default()
+    }
+    fn recover_or_fail(&mut self, error: ParseErr, sync: &[TokenKind]) -> ParseResult<()> {
+        if !self.recovering {
+            return Err(error);
+        }
+        self.recover(error, sync);
+        Ok(())
+    }
+    /// Records the error, unless it follows from the last one, and skips to one of `sync`.
+    fn recover(&mut self, error: ParseErr, sync: &[TokenKind]) {
+        if self.recovered_at != Some(self.consumed) {
+            self.errors.push(error);
+        }
+        self.skip_until(sync);
+        self.recovered_at = Some(self.consumed);
+    }
+    /// Skips up to one of `sync` or the end of input, along with any construct opened on the way.
+    fn skip_until(&mut self, sync: &[TokenKind]) {
+        let mut closers = Vec::new();
+        while let Some(kind) = self.peek_token().map(|t| t.kind.clone()) {
+            match kind {
+                TokenKind::EOF => break,
+                // Only ever found at the top level.
+                TokenKind::Type if sync.contains(&kind) => break,
+                TokenKind::Begin | TokenKind::Match => closers.push(TokenKind::End),
+                TokenKind::While | TokenKind::For => closers.push(TokenKind::Done),
+                _ if closers.last() == Some(&kind) => {
+                    closers.pop();
+                }
+                _ if closers.is_empty() && sync.contains(&kind) => break,
+                _ => (),
+            }
+            self.consume_token();
+        }
+    }
+    /// Expects `closer`, which closes the construct `opener` started.
+    fn expect_closing(&mut self, closer: TokenKind, opener: &Token) -> ParseResult<Token> {
+        self.accept(&closer).ok_or_else(|| ParseErr::Unclosed {
+            opener: opener.kind.clone(),
+            opened_at: Span::new(opener.from.clone(), opener.to.clone()),
+            closer,
+            found: self.peek_token().cloned().map(Box::new),
+        })
+    }
+    /// Expects `keyword`, which goes on with the construct `start` started.
+    fn expect_keyword(&mut self, keyword: TokenKind, start: &Token) -> ParseResult<Token> {
+        self.accept(&keyword)
+            .ok_or_else(|| ParseErr::MissingKeyword {
+                keyword,
+                construct: start.kind.clone(),
+                construct_at: Span::new(start.from.clone(), start.to.clone()),
+                found: self.peek_token().cloned().map(Box::new),
+            })
+    }
     fn expect(&mut self, token_kind: TokenKind) -> ParseResult<Token> {
-        self.accept(&token_kind)
-            .ok_or_else(|| ParseErr::UnexpectedToken(self.peek_token().cloned(), vec![token_kind]))
+        self.accept(&token_kind).ok_or_else(|| {
+            ParseErr::UnexpectedToken(self.peek_token().cloned().map(Box::new), vec![token_kind])
+        })
     }
     // fn accept_many_and_count(&mut self, token_kind: &TokenKind) -> usize {
     //     let mut cnt = 0;
@@ -757,19 +1020,28 @@ impl<L: Iterator<Item = Token>> Parser<L> {
         }
     }
     fn consume_token(&mut self) -> Option<Token> {
-        while self.lexer.peek().map(|t| &t.kind) == Some(&TokenKind::COMMENT) {
-            self.lexer.next();
-        }
+        self.skip_comments();
         let tok = self.lexer.next();
-        tok.as_ref()
-            .map(|t| self.consumed_token_span = Span::new(t.from.clone(), t.to.clone()));
+        self.consumed += 1;
+        if let Some(t) = &tok {
+            self.consumed_token_span = Span::new(t.from.clone(), t.to.clone());
+        }
         tok
     }
     fn peek_token(&mut self) -> Option<&Token> {
+        self.skip_comments();
+        self.lexer.peek()
+    }
+    /// Comments are skipped, the attributes among them are kept for the program.
+    fn skip_comments(&mut self) {
         while self.lexer.peek().map(|t| &t.kind) == Some(&TokenKind::COMMENT) {
-            self.lexer.next();
+            let comment = self.lexer.next().expect("a comment was peeked");
+            let span = Span::new(comment.from, comment.to);
+            let contents = String::from_utf8_lossy(&comment.original);
+            if let Some(attribute) = ast::attribute::Attribute::from_comment(&contents, span) {
+                self.attributes.push(attribute);
+            }
         }
-        self.lexer.peek()
     }
     fn peek_token_nth(&mut self, mut n: usize) -> Option<&Token> {
         for i in 0.. {
@@ -859,5 +1131,166 @@ pub enum ParseErr {
             .unwrap_or("Unexpected end of input".to_string()),
         .1.iter().map(|t| format!("\"{}\"", t)).collect::<Vec<_>>().join(", ")
     )]
-    UnexpectedToken(Option<Token>, Vec<TokenKind>),
+    UnexpectedToken(Option<Box<Token>>, Vec<TokenKind>),
+    #[error(
+        "{}, \"{}\" at {} is never closed by \"{}\"",
+        describe_found(.found), .opener, .opened_at.start, .closer
+    )]
+    Unclosed {
+        opener: TokenKind,
+        opened_at: Span,
+        closer: TokenKind,
+        found: Option<Box<Token>>,
+    },
+    #[error(
+        "{}, \"let\" at {} inside an expression is never followed by \"in\"",
+        describe_found(.found), .let_at.start
+    )]
+    LetWithoutIn {
+        let_at: Span,
+        found: Option<Box<Token>>,
+    },
+    #[error(
+        "{}, \"{}\" at {} is missing its \"{}\"",
+        describe_found(.found), .construct, .construct_at.start, .keyword
+    )]
+    MissingKeyword {
+        keyword: TokenKind,
+        construct: TokenKind,
+        construct_at: Span,
+        found: Option<Box<Token>>,
+    },
+}
+impl ParseErr {
+    pub fn code(&self) -> &'static Code {
+        match self {
+            ParseErr::UnexpectedToken(..) => &codes::UNEXPECTED_TOKEN,
+            ParseErr::Unclosed { .. } => &codes::UNCLOSED_DELIMITER,
+            ParseErr::LetWithoutIn { .. } => &codes::LET_WITHOUT_IN,
+            ParseErr::MissingKeyword { .. } => &codes::MISSING_KEYWORD,
+        }
+    }
+    /// The token the parser gave up on, `None` at the end of input.
+    pub fn found(&self) -> Option<&Token> {
+        match self {
+            ParseErr::UnexpectedToken(found, _)
+            | ParseErr::Unclosed { found, .. }
+            | ParseErr::LetWithoutIn { found, .. }
+            | ParseErr::MissingKeyword { found, .. } => found.as_deref(),
+        }
+    }
+    /// A suggestion on how to fix the error, for the kinds that know enough to make one.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            ParseErr::UnexpectedToken(..) => None,
+            ParseErr::Unclosed { closer, .. } => Some(format!(
+                "add a matching \"{}\", or check the tokens before it for a mistake",
+                closer
+            )),
+            ParseErr::LetWithoutIn { .. } => Some(
+                "a local definition needs \"in\" and the expression it is visible in, \
+                 top-level definitions cannot appear inside an expression"
+                    .to_string(),
+            ),
+            ParseErr::MissingKeyword {
+                keyword, construct, ..
+            } => Some(match (construct, keyword) {
+                (TokenKind::If, _) => {
+                    "write it as \"if <cond> then <expr> else <expr>\"".to_string()
+                }
+                (TokenKind::While, _) => "write it as \"while <cond> do <expr> done\"".to_string(),
+                (TokenKind::For, _) => {
+                    "write it as \"for <id> = <expr> to <expr> do <expr> done\"".to_string()
+                }
+                (TokenKind::Match, _) => {
+                    "write it as \"match <expr> with <pattern> -> <expr> | ... end\"".to_string()
+                }
+                _ => format!("insert \"{}\" before this token", keyword),
+            }),
+        }
+    }
+}
+impl From<&ParseErr> for Diagnostic {
+    fn from(error: &ParseErr) -> Self {
+        let message = match error {
+            ParseErr::UnexpectedToken(found, _) => found
+                .as_ref()
+                .map(|t| format!("Unexpected token \"{}\"", t.kind))
+                .unwrap_or("Unexpected end of input".to_string()),
+            ParseErr::Unclosed { opener, closer, .. } => {
+                format!("\"{}\" is never closed by \"{}\"", opener, closer)
+            }
+            ParseErr::LetWithoutIn { .. } => {
+                "\"let\" inside an expression is never followed by \"in\"".to_string()
+            }
+            ParseErr::MissingKeyword {
+                keyword, construct, ..
+            } => format!("\"{}\" is missing its \"{}\"", construct, keyword),
+        };
+        let label = match error {
+            ParseErr::UnexpectedToken(_, expected) => format!(
+                "expected any of {{{}}}",
+                expected
+                    .iter()
+                    .map(|t| format!("\"{}\"", t))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ParseErr::Unclosed { closer, .. } => format!("expected \"{}\" before this", closer),
+            ParseErr::LetWithoutIn { .. } => "expected \"in\" before this".to_string(),
+            ParseErr::MissingKeyword { keyword, .. } => {
+                format!("expected \"{}\" before this", keyword)
+            }
+        };
+        let mut diagnostic = match error.found() {
+            Some(found) => Diagnostic::new(error.code(), message)
+                .with_primary(&Span::new(found.from.clone(), found.to.clone()), label),
+            // At the end of input there is nothing to point at.
+            None => Diagnostic::new(error.code(), message).with_note(label),
+        };
+        diagnostic = match error {
+            ParseErr::UnexpectedToken(..) => diagnostic,
+            ParseErr::Unclosed {
+                opener, opened_at, ..
+            } => diagnostic.with_secondary(opened_at, format!("\"{}\" opened here", opener)),
+            ParseErr::LetWithoutIn { let_at, .. } => {
+                diagnostic.with_secondary(let_at, "the \"let\" that needs it")
+            }
+            ParseErr::MissingKeyword {
+                construct,
+                construct_at,
+                ..
+            } => diagnostic.with_secondary(construct_at, format!("this \"{}\"", construct)),
+        };
+        // The missing token goes right before the one found instead.
+        let missing = match error {
+            ParseErr::UnexpectedToken(..) => None,
+            ParseErr::Unclosed { closer, .. } => Some(closer),
+            ParseErr::LetWithoutIn { .. } => Some(&TokenKind::In),
+            ParseErr::MissingKeyword { keyword, .. } => Some(keyword),
+        };
+        if let (Some(missing), Some(found)) = (missing, error.found()) {
+            diagnostic = diagnostic.with_suggestion(
+                &Span::new(found.from.clone(), found.from.clone()),
+                format!("{} ", missing),
+                format!("insert \"{}\"", missing),
+            );
+        }
+        diagnostic.with_help(error.hint())
+    }
+}
+fn describe_found(found: &Option<Box<Token>>) -> String {
+    found
+        .as_ref()
+        .map(|t| format!("{}: Unexpected token \"{}\"", t.from, t.kind))
+        .unwrap_or("Unexpected end of input".to_string())
+}
+
+/// Every syntax error found in the input, in the order they were found.
+pub type ParseErrors = Errors<ParseErr>;
+impl PhaseError for ParseErr {
+    const KIND: &'static str = "syntax";
+    fn hint(&self) -> Option<String> {
+        ParseErr::hint(self)
+    }
 }
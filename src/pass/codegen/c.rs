@@ -0,0 +1,473 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::pass::irgen::ir::{
+    BinOp, CmpOp, Const, FuncId, Function, Inst, Module, Temp, Terminator, Trap, Ty, UnOp,
+};
+
+use super::RUNTIME;
+
+// ! Implementation notes:
+// !   Produces a single C11 translation unit, the runtime library included.
+// !   Temps become typed locals and blocks become labels.
+// !   Scalars are `llama_word`s (floats are `double`s), every other value is a pointer:
+// !     tuples to structs, custom types to tagged unions, refs to their cell,
+// !     arrays to structs mirroring the runtime layout (`ndims, dims[n], elems[]`)
+// !     and functions to closure records (a code pointer followed by the captured values).
+// !   A closure's code takes the record it was entered through and one argument.
+
+pub fn emit(module: &Module) -> String {
+    let mut emitter = Emitter {
+        module,
+        types: HashMap::new(),
+        pending: Vec::new(),
+    };
+    let mut functions = String::new();
+    let mut prototypes = String::new();
+    for (i, func) in module.functions.iter().enumerate() {
+        emitter.function(&mut functions, FuncId(i as u32), func);
+        let _ = writeln!(prototypes, "{};", emitter.prototype(FuncId(i as u32), func));
+    }
+    let mut globals = String::new();
+    for (i, global) in module.globals.iter().enumerate() {
+        let decl = emitter.declaration(&global.ty, &format!("llama_global_{}", i));
+        let _ = writeln!(globals, "static {}; /* {} */", decl, global.name);
+    }
+    let mut out = String::new();
+    out.push_str(RUNTIME);
+    out.push_str("\n/* Generated by llamac */\n\n");
+    out.push_str(
+        "typedef void (*llama_code)(void);\nstruct llama_closure {\n    llama_code code;\n};\n",
+    );
+    let mut definitions = String::new();
+    for (i, env) in module.envs.iter().enumerate() {
+        let fields: Vec<String> = env
+            .captures
+            .iter()
+            .enumerate()
+            .map(|(idx, ty)| emitter.declaration(ty, &format!("c{}", idx)))
+            .collect();
+        let _ = writeln!(out, "struct llama_env_{};", i);
+        let _ = writeln!(
+            definitions,
+            "struct llama_env_{} {{\n    llama_code code;\n{}}};",
+            i,
+            fields_block(&fields)
+        );
+    }
+    for custom in &module.customs {
+        let _ = writeln!(out, "struct llama_type_{};", custom.name);
+        let mut union = String::new();
+        for (tag, constr) in custom.constrs.iter().enumerate() {
+            if constr.fields.is_empty() {
+                continue;
+            }
+            let fields: Vec<String> = constr
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(idx, ty)| emitter.declaration(ty, &format!("f{}", idx)))
+                .collect();
+            let _ = writeln!(
+                union,
+                "        struct {{ /* {} */\n{}        }} c{};",
+                constr.name,
+                fields
+                    .iter()
+                    .map(|field| format!("        {};\n", field))
+                    .collect::<String>(),
+                tag
+            );
+        }
+        let union = if union.is_empty() {
+            String::new()
+        } else {
+            format!("    union {{\n{}    }} u;\n", union)
+        };
+        let _ = writeln!(
+            definitions,
+            "struct llama_type_{} {{\n    llama_word tag;\n{}}};",
+            custom.name, union
+        );
+    }
+    // Defining a tuple or an array may name types that were not seen before.
+    while let Some(ty) = emitter.pending.pop() {
+        let name = emitter.types[&ty].clone();
+        let _ = writeln!(out, "struct {};", name);
+        let body = match &ty {
+            Ty::Tuple(elems) => {
+                let fields: Vec<String> = elems
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, ty)| emitter.declaration(ty, &format!("f{}", idx)))
+                    .collect();
+                fields_block(&fields)
+            }
+            Ty::Array(inner, dims) => format!(
+                "    llama_word ndims;\n    llama_word dims[{}];\n    {};\n",
+                dims,
+                emitter.declaration(inner, "elems[]")
+            ),
+            _ => unreachable!("only tuples and arrays are interned"),
+        };
+        let _ = writeln!(definitions, "struct {} {{\n{}}};", name, body);
+    }
+    out.push('\n');
+    out.push_str(&definitions);
+    out.push('\n');
+    out.push_str(&globals);
+    for (i, string) in module.strings.iter().enumerate() {
+        let bytes: Vec<String> = string.iter().map(|b| b.to_string()).collect();
+        let _ = writeln!(
+            out,
+            "static const char llama_str_{}[] = {{{}}};",
+            i,
+            if bytes.is_empty() {
+                "0".to_string()
+            } else {
+                bytes.join(", ")
+            }
+        );
+    }
+    out.push('\n');
+    out.push_str(&prototypes);
+    out.push('\n');
+    out.push_str(&functions);
+    let _ = writeln!(
+        out,
+        "void llama_main(void) {{\n    {}();\n}}",
+        fn_name(module.entry)
+    );
+    out
+}
+
+struct Emitter<'m> {
+    module: &'m Module,
+    /// Names of the structs of the tuple and array types seen so far.
+    types: HashMap<Ty, String>,
+    /// Interned types whose struct is not defined yet.
+    pending: Vec<Ty>,
+}
+
+impl<'m> Emitter<'m> {
+    fn c_type(&mut self, ty: &Ty) -> String {
+        match ty {
+            Ty::Unit | Ty::Bool | Ty::Char | Ty::Int => "llama_word".to_string(),
+            Ty::Float => "double".to_string(),
+            Ty::Func(..) => "struct llama_closure *".to_string(),
+            Ty::Ref(inner) => format!("{}*", self.c_type_spaced(inner)),
+            Ty::Custom(name) => format!("struct llama_type_{} *", name),
+            Ty::Env(env) => format!("struct llama_env_{} *", env.0),
+            Ty::Tuple(_) | Ty::Array(..) => {
+                if !self.types.contains_key(ty) {
+                    let name = match ty {
+                        Ty::Tuple(_) => format!("llama_tuple_{}", self.types.len()),
+                        _ => format!("llama_array_{}", self.types.len()),
+                    };
+                    self.types.insert(ty.clone(), name);
+                    self.pending.push(ty.clone());
+                }
+                format!("struct {} *", self.types[ty])
+            }
+        }
+    }
+    /// The type, followed by a space unless it ends with a `*`.
+    fn c_type_spaced(&mut self, ty: &Ty) -> String {
+        let c_type = self.c_type(ty);
+        if c_type.ends_with('*') {
+            c_type
+        } else {
+            c_type + " "
+        }
+    }
+    fn declaration(&mut self, ty: &Ty, name: &str) -> String {
+        format!("{}{}", self.c_type_spaced(ty), name)
+    }
+    fn prototype(&mut self, id: FuncId, func: &Function) -> String {
+        let mut params: Vec<String> = func.env.iter().map(|_| "void *env_".to_string()).collect();
+        for param in &func.params {
+            params.push(self.declaration(func.temp_ty(*param), &temp(*param)));
+        }
+        if params.is_empty() {
+            params.push("void".to_string());
+        }
+        format!(
+            "static {}({})",
+            self.declaration(&func.ret, &fn_name(id)),
+            params.join(", ")
+        )
+    }
+
+    fn function(&mut self, out: &mut String, id: FuncId, func: &Function) {
+        let _ = writeln!(out, "/* {} */", func.name);
+        let _ = writeln!(out, "{} {{", self.prototype(id, func));
+        if let Some(env) = func.env {
+            let _ = writeln!(out, "    struct llama_env_{0} *env = env_;", env.0);
+        }
+        for (i, ty) in func.temps.iter().enumerate() {
+            let temp = Temp(i as u32);
+            if !func.params.contains(&temp) {
+                let _ = writeln!(out, "    {};", self.declaration(ty, &self::temp(temp)));
+            }
+        }
+        for (i, block) in func.blocks.iter().enumerate() {
+            let _ = writeln!(out, "L{}:", i);
+            for inst in &block.insts {
+                for stmt in self.inst(func, inst).lines() {
+                    let _ = writeln!(out, "    {}", stmt);
+                }
+            }
+            let term = match &block.term {
+                Terminator::Jump(block) => format!("goto L{};", block.0),
+                Terminator::Branch { cond, then, else_ } => format!(
+                    "if ({}) goto L{}; else goto L{};",
+                    temp(*cond),
+                    then.0,
+                    else_.0
+                ),
+                Terminator::Return(value) => format!("return {};", temp(*value)),
+                Terminator::Trap(trap) => format!("llama_trap({});", trap.code()),
+                Terminator::Unreachable => "llama_trap(0);".to_string(),
+            };
+            let _ = writeln!(out, "    {}", term);
+        }
+        let _ = writeln!(out, "}}\n");
+    }
+
+    /// `src` converted to the C type of `ty`, for the places where the IR only relates
+    /// the two types structurally (closures entered as environments and vice versa).
+    fn convert(&mut self, func: &Function, src: Temp, ty: &Ty) -> String {
+        if self.c_type(func.temp_ty(src)) == self.c_type(ty) {
+            temp(src)
+        } else {
+            format!("(void *){}", temp(src))
+        }
+    }
+
+    /// One statement per line.
+    fn inst(&mut self, func: &Function, inst: &Inst) -> String {
+        let t = |temp: &Temp| self::temp(*temp);
+        let dst_ty = |dst: &Temp| func.temp_ty(*dst).clone();
+        match inst {
+            Inst::Const { dst, value } => {
+                let value = match value {
+                    Const::Unit => "0".to_string(),
+                    Const::Bool(b) => (*b as i64).to_string(),
+                    Const::Char(c) => c.to_string(),
+                    Const::Int(i64::MIN) => "INT64_MIN".to_string(),
+                    Const::Int(i) => format!("INT64_C({})", i),
+                    Const::Float(x) => format!("{:?}", x),
+                };
+                format!("{} = {};", t(dst), value)
+            }
+            Inst::Copy { dst, src } => {
+                format!("{} = {};", t(dst), self.convert(func, *src, &dst_ty(dst)))
+            }
+            Inst::Unop { dst, op, src } => match op {
+                UnOp::INeg => format!("{} = (llama_word)(0 - (uint64_t){});", t(dst), t(src)),
+                UnOp::FNeg => format!("{} = -{};", t(dst), t(src)),
+                UnOp::Not => format!("{} = !{};", t(dst), t(src)),
+            },
+            Inst::Binop { dst, op, lhs, rhs } => {
+                let (dst, lhs, rhs) = (t(dst), t(lhs), t(rhs));
+                let wrapping = |op: &str| {
+                    format!(
+                        "{} = (llama_word)((uint64_t){} {} (uint64_t){});",
+                        dst, lhs, op, rhs
+                    )
+                };
+                let checked = |op: &str| {
+                    format!(
+                        "if ({} == 0) llama_trap({});\n{} = {} {} {};",
+                        rhs,
+                        Trap::DivisionByZero.code(),
+                        dst,
+                        lhs,
+                        op,
+                        rhs
+                    )
+                };
+                let simple = |op: &str| format!("{} = {} {} {};", dst, lhs, op, rhs);
+                match op {
+                    BinOp::IAdd => wrapping("+"),
+                    BinOp::ISub => wrapping("-"),
+                    BinOp::IMul => wrapping("*"),
+                    BinOp::IDiv => checked("/"),
+                    BinOp::IMod => checked("%"),
+                    BinOp::IPow => format!("{} = llama_ipow({}, {});", dst, lhs, rhs),
+                    BinOp::FAdd => simple("+"),
+                    BinOp::FSub => simple("-"),
+                    BinOp::FMul => simple("*"),
+                    BinOp::FDiv => simple("/"),
+                    BinOp::FPow => format!("{} = pow({}, {});", dst, lhs, rhs),
+                    BinOp::ICmp(cmp) | BinOp::FCmp(cmp) => simple(match cmp {
+                        CmpOp::Eq => "==",
+                        CmpOp::Ne => "!=",
+                        CmpOp::Lt => "<",
+                        CmpOp::Gt => ">",
+                        CmpOp::Le => "<=",
+                        CmpOp::Ge => ">=",
+                    }),
+                }
+            }
+            Inst::String { dst, string } => format!(
+                "{} = (void *)llama_make_string(llama_str_{}, {});",
+                t(dst),
+                string.0,
+                self.module.string(*string).len()
+            ),
+            Inst::NewRef { dst } | Inst::NewTuple { dst, .. } | Inst::NewConstr { dst, .. } => {
+                let mut stmt = format!("{0} = llama_alloc(sizeof *{0});", t(dst));
+                match inst {
+                    Inst::NewTuple { elems, .. } => {
+                        for (idx, elem) in elems.iter().enumerate() {
+                            let _ = write!(stmt, "\n{}->f{} = {};", t(dst), idx, t(elem));
+                        }
+                    }
+                    Inst::NewConstr { tag, args, .. } => {
+                        let _ = write!(stmt, "\n{}->tag = {};", t(dst), tag);
+                        for (idx, arg) in args.iter().enumerate() {
+                            let _ = write!(stmt, "\n{}->u.c{}.f{} = {};", t(dst), tag, idx, t(arg));
+                        }
+                    }
+                    _ => (),
+                }
+                stmt
+            }
+            Inst::Load { dst, ptr } => format!("{} = *{};", t(dst), t(ptr)),
+            Inst::Store { ptr, value } => format!("*{} = {};", t(ptr), t(value)),
+            Inst::Delete { ptr } => format!("llama_delete({});", t(ptr)),
+            Inst::NewArray { dst, dims } => format!(
+                "{{ llama_word dims[] = {{{}}}; {} = ({})llama_new_array({}, dims); }}",
+                dims.iter().map(t).collect::<Vec<_>>().join(", "),
+                t(dst),
+                self.c_type(&dst_ty(dst)),
+                dims.len()
+            ),
+            Inst::ArrayElem {
+                dst,
+                array,
+                indexes,
+            } => {
+                let mut stmt = String::new();
+                let mut flat = String::new();
+                for (k, index) in indexes.iter().enumerate() {
+                    let _ = writeln!(
+                        stmt,
+                        "if ((uint64_t){} >= (uint64_t){}->dims[{}]) llama_trap({});",
+                        t(index),
+                        t(array),
+                        k,
+                        Trap::IndexOutOfBounds.code()
+                    );
+                    flat = match k {
+                        0 => t(index),
+                        _ => format!("({}) * {}->dims[{}] + {}", flat, t(array), k, t(index)),
+                    };
+                }
+                let _ = write!(stmt, "{} = &{}->elems[{}];", t(dst), t(array), flat);
+                stmt
+            }
+            Inst::ArrayDim { dst, array, dim } => {
+                format!("{} = {}->dims[{}];", t(dst), t(array), dim - 1)
+            }
+            Inst::TupleGet { dst, src, idx } => format!("{} = {}->f{};", t(dst), t(src), idx),
+            Inst::Tag { dst, src } => format!("{} = {}->tag;", t(dst), t(src)),
+            Inst::ConstrGet { dst, src, tag, idx } => {
+                format!("{} = {}->u.c{}.f{};", t(dst), t(src), tag, idx)
+            }
+            Inst::Closure { dst, code } => {
+                let env = self
+                    .module
+                    .function(*code)
+                    .env
+                    .expect("closures are entered through their environment");
+                format!(
+                    "{0} = llama_alloc(sizeof(struct llama_env_{1}));\n{0}->code = (llama_code){2};",
+                    t(dst),
+                    env.0,
+                    fn_name(*code)
+                )
+            }
+            Inst::SetCapture {
+                closure,
+                env,
+                idx,
+                value,
+            } => format!(
+                "((struct llama_env_{}*){})->c{} = {};",
+                env.0,
+                t(closure),
+                idx,
+                t(value)
+            ),
+            Inst::Capture { dst, idx } => format!("{} = env->c{};", t(dst), idx),
+            Inst::SelfClosure { dst } => format!("{} = (void *)env;", t(dst)),
+            Inst::GetGlobal { dst, global } => format!("{} = llama_global_{};", t(dst), global.0),
+            Inst::SetGlobal { global, value } => {
+                format!("llama_global_{} = {};", global.0, t(value))
+            }
+            Inst::Call {
+                dst,
+                func: id,
+                args,
+            } => {
+                let callee = self.module.function(*id);
+                let mut args = args.iter();
+                let mut c_args = Vec::new();
+                if callee.env.is_some() {
+                    let env = args.next().expect("the closure record is passed first");
+                    c_args.push(format!("(void *){}", t(env)));
+                }
+                for (arg, param) in args.zip(&callee.params) {
+                    c_args.push(self.convert(func, *arg, callee.temp_ty(*param)));
+                }
+                format!("{} = {}({});", t(dst), fn_name(*id), c_args.join(", "))
+            }
+            Inst::CallClosure { dst, closure, arg } => {
+                let Ty::Func(lhs, rhs) = func.temp_ty(*closure) else {
+                    panic!("called a non-function");
+                };
+                let code = format!(
+                    "({} (*)(void *, {})){}->code",
+                    self.c_type(rhs),
+                    self.c_type(lhs),
+                    t(closure)
+                );
+                format!("{} = ({})({}, {});", t(dst), code, t(closure), t(arg))
+            }
+            Inst::CallRuntime {
+                dst,
+                func: runtime,
+                args,
+            } => {
+                // Only scalars are passed to the runtime as they are.
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| match func.temp_ty(*arg).is_scalar() {
+                        true => t(arg),
+                        false => format!("(void *){}", t(arg)),
+                    })
+                    .collect();
+                let call = format!("{}({})", runtime.symbol(), args.join(", "));
+                // Results of polymorphic builtins (that never return) may have any type.
+                match func.temp_ty(*dst).is_scalar() {
+                    true => format!("{} = {};", t(dst), call),
+                    false => format!("{} = (void *)(intptr_t){};", t(dst), call),
+                }
+            }
+        }
+    }
+}
+
+fn fields_block(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| format!("    {};\n", field))
+        .collect()
+}
+fn temp(temp: Temp) -> String {
+    format!("t{}", temp.0)
+}
+fn fn_name(func: FuncId) -> String {
+    format!("llama_fn_{}", func.0)
+}
@@ -0,0 +1,78 @@
+pub mod c;
+pub mod x86;
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use thiserror::Error;
+
+/// The runtime library every compiled program is linked with.
+pub const RUNTIME: &str = include_str!("runtime/llama.c");
+
+/// Assembles `asm` and links it with the runtime into the executable `out`.
+pub fn link(asm: &str, out: &str) -> CodegenResult<()> {
+    let dir = BuildDir::new()?;
+    let inputs = [
+        dir.write("program.s", asm)?,
+        dir.write("runtime.c", RUNTIME)?,
+    ];
+    cc(&inputs, out)
+}
+
+/// Runs the system C compiler (`$CC`, or `cc`) to produce the executable `out`.
+fn cc(inputs: &[PathBuf], out: &str) -> CodegenResult<()> {
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let output = Command::new(&cc)
+        .args(inputs)
+        .args(["-o", out, "-lm"])
+        .output()?;
+    if !output.status.success() {
+        return Err(CodegenError::ToolchainError {
+            cmd: cc,
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// A temporary directory for the intermediate files, removed on drop.
+struct BuildDir(PathBuf);
+impl BuildDir {
+    fn new() -> std::io::Result<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "llamac-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+    fn write(&self, name: impl AsRef<Path>, contents: &str) -> std::io::Result<PathBuf> {
+        let path = self.0.join(name);
+        std::fs::write(&path, contents)?;
+        Ok(path)
+    }
+}
+impl Drop for BuildDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub type CodegenResult<T> = Result<T, CodegenError>;
+#[derive(Error, Debug)]
+pub enum CodegenError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("'{}' failed ({}):\n{}", cmd, status, stderr)]
+    ToolchainError {
+        cmd: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+}
@@ -0,0 +1,227 @@
+/*
+ * Runtime library linked with every compiled Llama program.
+ *
+ * Every value is one 64-bit word. Heap records (refs, tuples, constructors,
+ * closures) are arrays of words, arrays are laid out as
+ * [ndims, dim_1, ..., dim_n, elements...].
+ */
+#include <inttypes.h>
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef int64_t llama_word;
+
+void llama_main(void);
+
+/* Keep in sync with `ir::Trap::code`. */
+static const char *llama_trap_messages[] = {
+    "unknown runtime error",
+    "no match case matched the value",
+    "division by zero",
+    "array index out of bounds",
+    "array dimensions must be positive",
+};
+
+_Noreturn void llama_trap(llama_word code) {
+    if (code < 0 || code >= (llama_word)(sizeof(llama_trap_messages) / sizeof(*llama_trap_messages)))
+        code = 0;
+    fflush(stdout);
+    fprintf(stderr, "Runtime error: %s\n", llama_trap_messages[code]);
+    exit(1);
+}
+
+void *llama_alloc(llama_word size) {
+    void *ptr = calloc(1, (size_t)size);
+    if (ptr == NULL) {
+        fflush(stdout);
+        fprintf(stderr, "Runtime error: out of memory\n");
+        exit(1);
+    }
+    return ptr;
+}
+
+void llama_delete(void *ptr) { free(ptr); }
+
+llama_word *llama_new_array(llama_word ndims, const llama_word *dims) {
+    llama_word size = 1;
+    for (llama_word i = 0; i < ndims; i++) {
+        if (dims[i] <= 0)
+            llama_trap(4);
+        size *= dims[i];
+    }
+    llama_word *array = llama_alloc((1 + ndims + size) * (llama_word)sizeof(llama_word));
+    array[0] = ndims;
+    memcpy(array + 1, dims, (size_t)ndims * sizeof(llama_word));
+    return array;
+}
+
+/* Builds a fresh `char[]` holding `len` bytes and a terminating '\0'. */
+llama_word *llama_make_string(const char *bytes, llama_word len) {
+    llama_word dims[] = {len + 1};
+    llama_word *array = llama_new_array(1, dims);
+    for (llama_word i = 0; i < len; i++)
+        array[2 + i] = (unsigned char)bytes[i];
+    return array;
+}
+
+/* Compares two `char[]`s up to their terminating '\0'. */
+llama_word llama_str_eq(const llama_word *lhs, const llama_word *rhs) {
+    llama_word i = 0;
+    for (; i < lhs[1] && i < rhs[1] && lhs[2 + i] != 0; i++)
+        if (lhs[2 + i] != rhs[2 + i])
+            return 0;
+    llama_word lhs_end = i < lhs[1] ? lhs[2 + i] : 0;
+    llama_word rhs_end = i < rhs[1] ? rhs[2 + i] : 0;
+    return lhs_end == rhs_end;
+}
+
+/* Wraps around on overflow, like the other integer operations. */
+llama_word llama_ipow(llama_word base, llama_word exp) {
+    uint64_t result = 1, factor = (uint64_t)base;
+    for (; exp > 0; exp >>= 1) {
+        if (exp & 1)
+            result *= factor;
+        factor *= factor;
+    }
+    return (llama_word)result;
+}
+
+/*
+ * The standard library, see `sem::builtins`. Every builtin is called as
+ * `llama_<name>`, unit arguments and results are passed as 0.
+ */
+
+/* Length of a `char[]` up to its terminating '\0' (or its end). */
+static llama_word llama_string_len(const llama_word *s) {
+    llama_word len = 0;
+    while (len < s[1] && s[2 + len] != 0)
+        len++;
+    return len;
+}
+
+llama_word llama_print_int(llama_word n) {
+    printf("%" PRId64, n);
+    return 0;
+}
+llama_word llama_print_bool(llama_word b) {
+    fputs(b ? "true" : "false", stdout);
+    return 0;
+}
+llama_word llama_print_char(llama_word c) {
+    putchar((int)c);
+    return 0;
+}
+llama_word llama_print_float(double x) {
+    printf("%g", x);
+    return 0;
+}
+llama_word llama_print_string(const llama_word *s) {
+    llama_word len = llama_string_len(s);
+    for (llama_word i = 0; i < len; i++)
+        putchar((int)s[2 + i]);
+    return 0;
+}
+
+llama_word llama_read_int(llama_word unit) {
+    (void)unit;
+    int64_t n = 0;
+    if (scanf("%" SCNd64, &n) != 1)
+        n = 0;
+    return n;
+}
+llama_word llama_read_bool(llama_word unit) {
+    (void)unit;
+    char word[6] = {0};
+    if (scanf("%5s", word) != 1)
+        return 0;
+    return strcmp(word, "true") == 0;
+}
+llama_word llama_read_char(llama_word unit) {
+    (void)unit;
+    int c = getchar();
+    return c == EOF ? 0 : c;
+}
+double llama_read_float(llama_word unit) {
+    (void)unit;
+    double x = 0;
+    if (scanf("%lf", &x) != 1)
+        x = 0;
+    return x;
+}
+/* Reads a line of at most `n - 1` characters, without the newline. */
+llama_word llama_read_string(llama_word n, llama_word *s) {
+    if (n > s[1])
+        n = s[1];
+    llama_word len = 0;
+    int c;
+    while (len < n - 1 && (c = getchar()) != EOF && c != '\n')
+        s[2 + len++] = c;
+    if (n > 0)
+        s[2 + len] = 0;
+    return 0;
+}
+
+llama_word llama_abs(llama_word n) { return n < 0 ? (llama_word)(0 - (uint64_t)n) : n; }
+double llama_fabs(double x) { return fabs(x); }
+double llama_sqrt(double x) { return sqrt(x); }
+double llama_sin(double x) { return sin(x); }
+double llama_cos(double x) { return cos(x); }
+double llama_tan(double x) { return tan(x); }
+double llama_atan(double x) { return atan(x); }
+double llama_exp(double x) { return exp(x); }
+double llama_ln(double x) { return log(x); }
+double llama_pi(llama_word unit) {
+    (void)unit;
+    return 3.14159265358979323846;
+}
+
+llama_word llama_incr(llama_word *r) {
+    *r = (llama_word)((uint64_t)*r + 1);
+    return 0;
+}
+llama_word llama_decr(llama_word *r) {
+    *r = (llama_word)((uint64_t)*r - 1);
+    return 0;
+}
+
+double llama_float_of_int(llama_word n) { return (double)n; }
+llama_word llama_int_of_float(double x) { return (llama_word)x; }
+llama_word llama_round(double x) { return (llama_word)llround(x); }
+llama_word llama_int_of_char(llama_word c) { return c; }
+llama_word llama_char_of_int(llama_word n) { return n & 0xff; }
+
+llama_word llama_strlen(const llama_word *s) { return llama_string_len(s); }
+llama_word llama_strcmp(const llama_word *lhs, const llama_word *rhs) {
+    llama_word lhs_len = llama_string_len(lhs), rhs_len = llama_string_len(rhs);
+    for (llama_word i = 0; i < lhs_len && i < rhs_len; i++)
+        if (lhs[2 + i] != rhs[2 + i])
+            return lhs[2 + i] < rhs[2 + i] ? -1 : 1;
+    return lhs_len < rhs_len ? -1 : lhs_len > rhs_len;
+}
+/* Copies `src` (and its terminating '\0') to `dst` starting at `offset`. */
+static void llama_string_copy(llama_word *dst, llama_word offset, const llama_word *src) {
+    llama_word len = llama_string_len(src);
+    if (offset + len >= dst[1])
+        llama_trap(3);
+    for (llama_word i = 0; i < len; i++)
+        dst[2 + offset + i] = src[2 + i];
+    dst[2 + offset + len] = 0;
+}
+llama_word llama_strcpy(llama_word *dst, const llama_word *src) {
+    llama_string_copy(dst, 0, src);
+    return 0;
+}
+llama_word llama_strcat(llama_word *dst, const llama_word *src) {
+    llama_string_copy(dst, llama_string_len(dst), src);
+    return 0;
+}
+
+_Noreturn llama_word llama_exit(llama_word code) { exit((int)code); }
+
+int main(void) {
+    llama_main();
+    return 0;
+}
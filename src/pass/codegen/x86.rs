@@ -0,0 +1,477 @@
+use std::fmt::Write;
+
+use crate::pass::irgen::ir::{
+    BinOp, BlockId, CmpOp, Const, FuncId, Function, Inst, Module, Temp, Terminator, Trap, UnOp,
+};
+
+// ! Implementation notes:
+// !   Emits GNU `as` (AT&T syntax) for x86-64 System V.
+// !   Every temp lives in its own stack slot, instructions go through %rax/%rcx/%rdx.
+// !   Calls between compiled functions pass every word as an INTEGER argument (floats by their bits),
+// !   the environment (if any) being the first one. Only calls into libm use %xmm registers.
+// !   The runtime's `main` calls `llama_main`, which is the entry function of the module.
+
+const ARG_REGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+const TRAPS: [Trap; 4] = [
+    Trap::MatchFailure,
+    Trap::DivisionByZero,
+    Trap::IndexOutOfBounds,
+    Trap::InvalidArraySize,
+];
+
+pub fn emit(module: &Module) -> String {
+    let mut emitter = Emitter {
+        module,
+        out: String::new(),
+    };
+    emitter.module();
+    emitter.out
+}
+
+struct Emitter<'m> {
+    module: &'m Module,
+    out: String,
+}
+
+macro_rules! ins {
+    ($self:ident, $($arg:tt)*) => {
+        writeln!($self.out, "    {}", format_args!($($arg)*)).expect("writing to a String cannot fail")
+    };
+}
+macro_rules! label {
+    ($self:ident, $($arg:tt)*) => {
+        writeln!($self.out, "{}:", format_args!($($arg)*)).expect("writing to a String cannot fail")
+    };
+}
+
+impl<'m> Emitter<'m> {
+    fn module(&mut self) {
+        ins!(self, ".text");
+        for (i, func) in self.module.functions.iter().enumerate() {
+            self.function(FuncId(i as u32), func);
+        }
+        for trap in TRAPS {
+            label!(self, "{}", trap_label(trap));
+            ins!(self, "movq ${}, %rdi", trap.code());
+            ins!(self, "call llama_trap@PLT");
+        }
+        if !self.module.globals.is_empty() {
+            ins!(self, ".bss");
+            ins!(self, ".p2align 3");
+            for (i, global) in self.module.globals.iter().enumerate() {
+                ins!(self, "# {}: {}", global.name, global.ty);
+                label!(self, "llama_global_{}", i);
+                ins!(self, ".zero 8");
+            }
+        }
+        if !self.module.strings.is_empty() {
+            ins!(self, ".section .rodata");
+            for (i, string) in self.module.strings.iter().enumerate() {
+                label!(self, "llama_str_{}", i);
+                let bytes: Vec<_> = string.iter().map(|b| b.to_string()).collect();
+                if !bytes.is_empty() {
+                    ins!(self, ".byte {}", bytes.join(", "));
+                }
+            }
+        }
+        ins!(self, ".section .note.GNU-stack,\"\",@progbits");
+    }
+
+    fn function(&mut self, id: FuncId, func: &'m Function) {
+        let frame = Frame::new(func);
+        ins!(self, "");
+        ins!(self, "# {}", func.name);
+        if id == self.module.entry {
+            ins!(self, ".globl llama_main");
+            label!(self, "llama_main");
+        }
+        label!(self, "{}", fn_label(id));
+        ins!(self, "pushq %rbp");
+        ins!(self, "movq %rsp, %rbp");
+        ins!(self, "subq ${}, %rsp", frame.size);
+        let params = func
+            .env
+            .iter()
+            .map(|_| frame.env())
+            .chain(func.params.iter().map(|param| frame.slot(*param)));
+        for (k, slot) in params.enumerate() {
+            match ARG_REGS.get(k) {
+                Some(reg) => ins!(self, "movq {}, {}", reg, slot),
+                None => {
+                    ins!(self, "movq {}(%rbp), %rax", 16 + 8 * (k - ARG_REGS.len()));
+                    ins!(self, "movq %rax, {}", slot);
+                }
+            }
+        }
+        for (i, block) in func.blocks.iter().enumerate() {
+            label!(self, "{}", block_label(id, BlockId(i as u32)));
+            for inst in &block.insts {
+                self.inst(func, &frame, inst);
+            }
+            self.terminator(id, &frame, &block.term);
+        }
+    }
+
+    fn inst(&mut self, func: &Function, frame: &Frame, inst: &Inst) {
+        let slot = |temp: &Temp| frame.slot(*temp);
+        match inst {
+            Inst::Const { dst, value } => {
+                let bits = match value {
+                    Const::Unit => 0,
+                    Const::Bool(b) => *b as i64,
+                    Const::Char(c) => *c as i64,
+                    Const::Int(i) => *i,
+                    Const::Float(x) => x.to_bits() as i64,
+                };
+                ins!(self, "movabsq ${}, %rax", bits);
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::Copy { dst, src } => {
+                ins!(self, "movq {}, %rax", slot(src));
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::Unop { dst, op, src } => {
+                ins!(self, "movq {}, %rax", slot(src));
+                match op {
+                    UnOp::INeg => ins!(self, "negq %rax"),
+                    UnOp::FNeg => ins!(self, "btcq $63, %rax"),
+                    UnOp::Not => ins!(self, "xorq $1, %rax"),
+                }
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::Binop { dst, op, lhs, rhs } => {
+                self.binop(*op, &slot(lhs), &slot(rhs));
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::String { dst, string } => {
+                ins!(self, "leaq llama_str_{}(%rip), %rdi", string.0);
+                ins!(self, "movq ${}, %rsi", self.module.string(*string).len());
+                ins!(self, "call llama_make_string@PLT");
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::NewRef { dst } => {
+                self.alloc(1);
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::Load { dst, ptr } => {
+                ins!(self, "movq {}, %rax", slot(ptr));
+                ins!(self, "movq (%rax), %rax");
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::Store { ptr, value } => {
+                ins!(self, "movq {}, %rax", slot(ptr));
+                ins!(self, "movq {}, %rcx", slot(value));
+                ins!(self, "movq %rcx, (%rax)");
+            }
+            Inst::Delete { ptr } => {
+                ins!(self, "movq {}, %rdi", slot(ptr));
+                ins!(self, "call llama_delete@PLT");
+            }
+            Inst::NewArray { dst, dims } => {
+                let size = align16(8 * dims.len());
+                ins!(self, "subq ${}, %rsp", size);
+                for (i, dim) in dims.iter().enumerate() {
+                    ins!(self, "movq {}, %rax", slot(dim));
+                    ins!(self, "movq %rax, {}(%rsp)", 8 * i);
+                }
+                ins!(self, "movq ${}, %rdi", dims.len());
+                ins!(self, "movq %rsp, %rsi");
+                ins!(self, "call llama_new_array@PLT");
+                ins!(self, "addq ${}, %rsp", size);
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::ArrayElem {
+                dst,
+                array,
+                indexes,
+            } => {
+                ins!(self, "movq {}, %rdx", slot(array));
+                ins!(self, "xorq %rax, %rax");
+                for (k, index) in indexes.iter().enumerate() {
+                    let dim = format!("{}(%rdx)", 8 * (k + 1));
+                    ins!(self, "movq {}, %rcx", slot(index));
+                    ins!(self, "cmpq {}, %rcx", dim);
+                    ins!(self, "jae {}", trap_label(Trap::IndexOutOfBounds));
+                    if k > 0 {
+                        ins!(self, "imulq {}, %rax", dim);
+                    }
+                    ins!(self, "addq %rcx, %rax");
+                }
+                ins!(self, "leaq {}(%rdx,%rax,8), %rax", 8 * (indexes.len() + 1));
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::ArrayDim { dst, array, dim } => {
+                ins!(self, "movq {}, %rax", slot(array));
+                ins!(self, "movq {}(%rax), %rax", 8 * dim);
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::NewTuple { dst, elems } => {
+                self.alloc(elems.len());
+                for (i, elem) in elems.iter().enumerate() {
+                    ins!(self, "movq {}, %rcx", slot(elem));
+                    ins!(self, "movq %rcx, {}(%rax)", 8 * i);
+                }
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::TupleGet { dst, src, idx } => {
+                ins!(self, "movq {}, %rax", slot(src));
+                ins!(self, "movq {}(%rax), %rax", 8 * idx);
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::NewConstr { dst, tag, args } => {
+                self.alloc(1 + args.len());
+                ins!(self, "movq ${}, (%rax)", tag);
+                for (i, arg) in args.iter().enumerate() {
+                    ins!(self, "movq {}, %rcx", slot(arg));
+                    ins!(self, "movq %rcx, {}(%rax)", 8 * (i + 1));
+                }
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::Tag { dst, src } => {
+                ins!(self, "movq {}, %rax", slot(src));
+                ins!(self, "movq (%rax), %rax");
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::ConstrGet { dst, src, idx, .. } => {
+                ins!(self, "movq {}, %rax", slot(src));
+                ins!(self, "movq {}(%rax), %rax", 8 * (idx + 1));
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::Closure { dst, code } => {
+                let env = self
+                    .module
+                    .function(*code)
+                    .env
+                    .expect("closures are entered through their environment");
+                self.alloc(1 + self.module.env(env).captures.len());
+                ins!(self, "leaq {}(%rip), %rcx", fn_label(*code));
+                ins!(self, "movq %rcx, (%rax)");
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::SetCapture {
+                closure,
+                idx,
+                value,
+                ..
+            } => {
+                ins!(self, "movq {}, %rax", slot(closure));
+                ins!(self, "movq {}, %rcx", slot(value));
+                ins!(self, "movq %rcx, {}(%rax)", 8 * (idx + 1));
+            }
+            Inst::Capture { dst, idx } => {
+                ins!(self, "movq {}, %rax", frame.env());
+                ins!(self, "movq {}(%rax), %rax", 8 * (idx + 1));
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::SelfClosure { dst } => {
+                ins!(self, "movq {}, %rax", frame.env());
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::GetGlobal { dst, global } => {
+                ins!(self, "movq llama_global_{}(%rip), %rax", global.0);
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::SetGlobal { global, value } => {
+                ins!(self, "movq {}, %rax", slot(value));
+                ins!(self, "movq %rax, llama_global_{}(%rip)", global.0);
+            }
+            Inst::Call { dst, func, args } => {
+                let args: Vec<_> = args.iter().map(slot).collect();
+                let popped = self.push_args(&args);
+                ins!(self, "call {}", fn_label(*func));
+                if popped > 0 {
+                    ins!(self, "addq ${}, %rsp", popped);
+                }
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            Inst::CallClosure { dst, closure, arg } => {
+                ins!(self, "movq {}, %rdi", slot(closure));
+                ins!(self, "movq {}, %rsi", slot(arg));
+                ins!(self, "call *(%rdi)");
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+            // The runtime is written in C, so floats are passed in %xmm registers.
+            Inst::CallRuntime {
+                dst,
+                func: runtime,
+                args,
+            } => {
+                let (floats, words): (Vec<_>, Vec<_>) =
+                    args.iter().partition(|arg| func.temp_ty(**arg).is_float());
+                assert!(
+                    words.len() <= ARG_REGS.len() && floats.len() <= 8,
+                    "runtime functions take their arguments in registers"
+                );
+                for (reg, arg) in ARG_REGS.iter().zip(words) {
+                    ins!(self, "movq {}, {}", slot(arg), reg);
+                }
+                for (k, arg) in floats.into_iter().enumerate() {
+                    ins!(self, "movsd {}, %xmm{}", slot(arg), k);
+                }
+                ins!(self, "call {}@PLT", runtime.symbol());
+                if func.temp_ty(*dst).is_float() {
+                    ins!(self, "movq %xmm0, %rax");
+                }
+                ins!(self, "movq %rax, {}", slot(dst));
+            }
+        }
+    }
+    /// Leaves the result in %rax.
+    fn binop(&mut self, op: BinOp, lhs: &str, rhs: &str) {
+        match op {
+            BinOp::IAdd | BinOp::ISub | BinOp::IMul => {
+                let mnemonic = match op {
+                    BinOp::IAdd => "addq",
+                    BinOp::ISub => "subq",
+                    _ => "imulq",
+                };
+                ins!(self, "movq {}, %rax", lhs);
+                ins!(self, "{} {}, %rax", mnemonic, rhs);
+            }
+            BinOp::IDiv | BinOp::IMod => {
+                ins!(self, "movq {}, %rax", lhs);
+                ins!(self, "movq {}, %rcx", rhs);
+                ins!(self, "testq %rcx, %rcx");
+                ins!(self, "jz {}", trap_label(Trap::DivisionByZero));
+                ins!(self, "cqto");
+                ins!(self, "idivq %rcx");
+                if op == BinOp::IMod {
+                    ins!(self, "movq %rdx, %rax");
+                }
+            }
+            BinOp::IPow => {
+                ins!(self, "movq {}, %rdi", lhs);
+                ins!(self, "movq {}, %rsi", rhs);
+                ins!(self, "call llama_ipow@PLT");
+            }
+            BinOp::FAdd | BinOp::FSub | BinOp::FMul | BinOp::FDiv => {
+                let mnemonic = match op {
+                    BinOp::FAdd => "addsd",
+                    BinOp::FSub => "subsd",
+                    BinOp::FMul => "mulsd",
+                    _ => "divsd",
+                };
+                ins!(self, "movsd {}, %xmm0", lhs);
+                ins!(self, "{} {}, %xmm0", mnemonic, rhs);
+                ins!(self, "movq %xmm0, %rax");
+            }
+            BinOp::FPow => {
+                ins!(self, "movsd {}, %xmm0", lhs);
+                ins!(self, "movsd {}, %xmm1", rhs);
+                ins!(self, "call pow@PLT");
+                ins!(self, "movq %xmm0, %rax");
+            }
+            BinOp::ICmp(cmp) => {
+                let cond = match cmp {
+                    CmpOp::Eq => "e",
+                    CmpOp::Ne => "ne",
+                    CmpOp::Lt => "l",
+                    CmpOp::Gt => "g",
+                    CmpOp::Le => "le",
+                    CmpOp::Ge => "ge",
+                };
+                ins!(self, "movq {}, %rax", lhs);
+                ins!(self, "cmpq {}, %rax", rhs);
+                ins!(self, "set{} %al", cond);
+                ins!(self, "movzbq %al, %rax");
+            }
+            BinOp::FCmp(cmp) => {
+                ins!(self, "movsd {}, %xmm0", lhs);
+                ins!(self, "movsd {}, %xmm1", rhs);
+                // `ucomisd` sets the flags like an unsigned comparison, and PF if unordered.
+                match cmp {
+                    CmpOp::Eq | CmpOp::Ne => {
+                        let (cond, parity, combine) = match cmp {
+                            CmpOp::Eq => ("e", "np", "andb"),
+                            _ => ("ne", "p", "orb"),
+                        };
+                        ins!(self, "ucomisd %xmm1, %xmm0");
+                        ins!(self, "set{} %al", cond);
+                        ins!(self, "set{} %cl", parity);
+                        ins!(self, "{} %cl, %al", combine);
+                    }
+                    CmpOp::Gt | CmpOp::Ge => {
+                        ins!(self, "ucomisd %xmm1, %xmm0");
+                        ins!(self, "set{} %al", if cmp == CmpOp::Gt { "a" } else { "ae" });
+                    }
+                    CmpOp::Lt | CmpOp::Le => {
+                        ins!(self, "ucomisd %xmm0, %xmm1");
+                        ins!(self, "set{} %al", if cmp == CmpOp::Lt { "a" } else { "ae" });
+                    }
+                }
+                ins!(self, "movzbq %al, %rax");
+            }
+        }
+    }
+    /// Allocates a zeroed record of `words` words, leaves it in %rax.
+    fn alloc(&mut self, words: usize) {
+        ins!(self, "movq ${}, %rdi", 8 * words.max(1));
+        ins!(self, "call llama_alloc@PLT");
+    }
+    /// Passes `args` (stack slots), returns the number of bytes to pop after the call.
+    fn push_args(&mut self, args: &[String]) -> usize {
+        let stack_args = args.len().saturating_sub(ARG_REGS.len());
+        let padding = 8 * (stack_args % 2);
+        if padding > 0 {
+            ins!(self, "subq ${}, %rsp", padding);
+        }
+        for arg in args.iter().skip(ARG_REGS.len()).rev() {
+            ins!(self, "pushq {}", arg);
+        }
+        for (reg, arg) in ARG_REGS.iter().zip(args) {
+            ins!(self, "movq {}, {}", arg, reg);
+        }
+        8 * stack_args + padding
+    }
+    fn terminator(&mut self, func: FuncId, frame: &Frame, term: &Terminator) {
+        match term {
+            Terminator::Jump(block) => ins!(self, "jmp {}", block_label(func, *block)),
+            Terminator::Branch { cond, then, else_ } => {
+                ins!(self, "cmpq $0, {}", frame.slot(*cond));
+                ins!(self, "jne {}", block_label(func, *then));
+                ins!(self, "jmp {}", block_label(func, *else_));
+            }
+            Terminator::Return(value) => {
+                ins!(self, "movq {}, %rax", frame.slot(*value));
+                ins!(self, "leave");
+                ins!(self, "ret");
+            }
+            Terminator::Trap(trap) => ins!(self, "jmp {}", trap_label(*trap)),
+            Terminator::Unreachable => ins!(self, "ud2"),
+        }
+    }
+}
+
+/// One slot per temp, below them the environment the function was entered through.
+struct Frame {
+    temps: usize,
+    size: usize,
+}
+impl Frame {
+    fn new(func: &Function) -> Self {
+        let temps = func.temps.len();
+        Self {
+            temps,
+            size: align16(8 * (temps + 1)),
+        }
+    }
+    fn slot(&self, temp: Temp) -> String {
+        format!("-{}(%rbp)", 8 * (temp.0 as usize + 1))
+    }
+    fn env(&self) -> String {
+        format!("-{}(%rbp)", 8 * (self.temps + 1))
+    }
+}
+
+fn align16(bytes: usize) -> usize {
+    (bytes + 15) & !15
+}
+fn fn_label(func: FuncId) -> String {
+    format!("llama_fn_{}", func.0)
+}
+fn block_label(func: FuncId, block: BlockId) -> String {
+    format!(".Lfn{}_{}", func.0, block.0)
+}
+fn trap_label(trap: Trap) -> String {
+    format!(".Ltrap_{}", trap.code())
+}
@@ -0,0 +1,159 @@
+use std::io::{BufRead, Write};
+
+use crate::{parse::ast::Span, pass::sem::builtins::Builtin};
+
+use super::{
+    eval::Eval,
+    value::{format_float, Array, Slot, Value},
+    InterpResult, RuntimeError,
+};
+
+// *Note: These follow `runtime/llama.c`, so that interpreted and compiled programs agree.
+
+impl<'i, 'a> Eval<'i, 'a> {
+    pub(super) fn builtin(
+        &mut self,
+        builtin: &'static Builtin,
+        args: Vec<Value<'a>>,
+        span: &Span,
+    ) -> InterpResult<Value<'a>> {
+        use Value::*;
+        let out = &mut self.io.output;
+        let input: &mut dyn BufRead = &mut self.io.input;
+        Ok(match (builtin.id, args.as_slice()) {
+            ("print_int", [Int(n)]) => write!(out, "{}", n).map(|_| Unit)?,
+            ("print_bool", [Bool(b)]) => write!(out, "{}", b).map(|_| Unit)?,
+            ("print_char", [Char(c)]) => out.write_all(&[*c]).map(|_| Unit)?,
+            ("print_float", [Float(x)]) => write!(out, "{}", format_float(*x)).map(|_| Unit)?,
+            ("print_string", [Array(s)]) => out.write_all(&s.string_bytes()).map(|_| Unit)?,
+
+            ("read_int", [Unit]) => {
+                out.flush()?;
+                Int(
+                    read_token(input, |b| b.is_ascii_digit() || b == b'-' || b == b'+')?
+                        .parse()
+                        .unwrap_or(0),
+                )
+            }
+            ("read_bool", [Unit]) => {
+                out.flush()?;
+                Bool(read_token(input, |b| !b.is_ascii_whitespace())? == "true")
+            }
+            ("read_char", [Unit]) => {
+                out.flush()?;
+                Char(read_byte(input)?.unwrap_or(0))
+            }
+            ("read_float", [Unit]) => {
+                out.flush()?;
+                let token = read_token(input, |b| {
+                    b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E')
+                })?;
+                Float(token.parse().unwrap_or(0.0))
+            }
+            ("read_string", [Int(n), Array(s)]) => {
+                out.flush()?;
+                let n = (*n).min(s.dims[0] as i64);
+                let mut elems = Vec::new();
+                while (elems.len() as i64) < n - 1 {
+                    match read_byte(input)? {
+                        None | Some(b'\n') => break,
+                        Some(b) => elems.push(b),
+                    }
+                }
+                if n > 0 {
+                    elems.push(0);
+                    let mut slots = s.elems.borrow_mut();
+                    for (slot, b) in slots.iter_mut().zip(elems) {
+                        *slot = Slot::Full(Char(b));
+                    }
+                }
+                Unit
+            }
+
+            ("abs", [Int(n)]) => Int(n.wrapping_abs()),
+            ("fabs", [Float(x)]) => Float(x.abs()),
+            ("sqrt", [Float(x)]) => Float(x.sqrt()),
+            ("sin", [Float(x)]) => Float(x.sin()),
+            ("cos", [Float(x)]) => Float(x.cos()),
+            ("tan", [Float(x)]) => Float(x.tan()),
+            ("atan", [Float(x)]) => Float(x.atan()),
+            ("exp", [Float(x)]) => Float(x.exp()),
+            ("ln", [Float(x)]) => Float(x.ln()),
+            ("pi", [Unit]) => Float(std::f64::consts::PI),
+
+            ("incr" | "decr", [Ref(loc)]) => {
+                let Int(n) = self.deref(loc, span)? else {
+                    unreachable!("incr and decr take an int ref")
+                };
+                let delta = if builtin.id == "incr" { 1 } else { -1 };
+                loc.set(Slot::Full(Int(n.wrapping_add(delta))));
+                Unit
+            }
+
+            ("float_of_int", [Int(n)]) => Float(*n as f64),
+            ("int_of_float", [Float(x)]) => Int(*x as i64),
+            ("round", [Float(x)]) => Int(x.round() as i64),
+            ("int_of_char", [Char(c)]) => Int(*c as i64),
+            ("char_of_int", [Int(n)]) => Char((n & 0xff) as u8),
+
+            ("strlen", [Array(s)]) => Int(s.string_bytes().len() as i64),
+            ("strcmp", [Array(lhs), Array(rhs)]) => {
+                Int(match lhs.string_bytes().cmp(&rhs.string_bytes()) {
+                    std::cmp::Ordering::Less => -1,
+                    std::cmp::Ordering::Equal => 0,
+                    std::cmp::Ordering::Greater => 1,
+                })
+            }
+            ("strcpy", [Array(dst), Array(src)]) => string_copy(dst, 0, src, span).map(|_| Unit)?,
+            ("strcat", [Array(dst), Array(src)]) => {
+                let offset = dst.string_bytes().len();
+                string_copy(dst, offset, src, span).map(|_| Unit)?
+            }
+
+            ("exit", [Int(code)]) => return Err(RuntimeError::Exit { code: *code as i32 }),
+            (id, _) => unreachable!("ill-typed call to builtin {}", id),
+        })
+    }
+}
+
+/// Copies `src` (and its terminating '\0') to `dst` starting at `offset`.
+fn string_copy(dst: &Array, offset: usize, src: &Array, span: &Span) -> InterpResult<()> {
+    let bytes = src.string_bytes();
+    let size = dst.dims[0];
+    if offset + bytes.len() >= size {
+        return Err(RuntimeError::IndexOutOfBounds {
+            index: (offset + bytes.len()) as i64,
+            dim: 1,
+            size,
+            span: span.clone(),
+        });
+    }
+    let mut elems = dst.elems.borrow_mut();
+    for (i, b) in bytes.into_iter().chain(std::iter::once(0)).enumerate() {
+        elems[offset + i] = Slot::Full(Value::Char(b));
+    }
+    Ok(())
+}
+
+fn read_byte(input: &mut dyn BufRead) -> std::io::Result<Option<u8>> {
+    let byte = input.fill_buf()?.first().copied();
+    if byte.is_some() {
+        input.consume(1);
+    }
+    Ok(byte)
+}
+fn peek_byte(input: &mut dyn BufRead) -> std::io::Result<Option<u8>> {
+    Ok(input.fill_buf()?.first().copied())
+}
+/// Skips whitespace, then reads the longest run of bytes that satisfy `accept`, like `scanf`.
+fn read_token(input: &mut dyn BufRead, accept: impl Fn(u8) -> bool) -> std::io::Result<String> {
+    while peek_byte(input)?.is_some_and(|b| b.is_ascii_whitespace()) {
+        input.consume(1);
+    }
+    let mut token = Vec::new();
+    while let Some(b) = peek_byte(input)?.filter(|b| accept(*b)) {
+        token.push(b);
+        input.consume(1);
+    }
+    Ok(String::from_utf8_lossy(&token).into_owned())
+}
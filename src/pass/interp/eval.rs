@@ -0,0 +1,455 @@
+use std::rc::Rc;
+
+use crate::{
+    parse::ast::{
+        annotation::TypeAnnotation,
+        def::{Def, DefKind, Letdef},
+        expr::{
+            ArrayAccess, Binop, BinopKind, Call, Clause, Expr, ExprKind, For, Pattern, PatternKind,
+            Unop, UnopKind,
+        },
+        Span,
+    },
+    pass::sem::{
+        builtins::BUILTINS,
+        sem_table::SemTable,
+        types::{inference::InfererHelpers, Type},
+    },
+};
+
+use super::{
+    value::{Array, Callee, Env, Func, Loc, Slot, Value},
+    InterpResult, Io, RuntimeError,
+};
+
+pub(super) struct Eval<'i, 'a> {
+    pub(super) sem: &'i SemTable<'a>,
+    pub(super) io: &'i mut Io<'a>,
+}
+
+impl<'i, 'a> Eval<'i, 'a> {
+    /// Returns `env` extended with the bindings of `letdef`.
+    pub(super) fn letdef(&mut self, letdef: &'a Letdef, env: &Env<'a>) -> InterpResult<Env<'a>> {
+        if letdef.rec {
+            let env = letdef
+                .defs
+                .iter()
+                .fold(env.clone(), |env, def| env.bind(&def.id, None));
+            // Functions first, so that the other definitions can already call them.
+            let (funcs, others): (Vec<_>, Vec<_>) = letdef
+                .defs
+                .iter()
+                .partition(|def| matches!(def.kind, DefKind::Function { .. }));
+            for def in funcs.into_iter().chain(others) {
+                let value = self.def(def, &env)?;
+                env.set(&def.id, value);
+            }
+            Ok(env)
+        } else {
+            let values = letdef
+                .defs
+                .iter()
+                .map(|def| self.def(def, env))
+                .collect::<InterpResult<Vec<_>>>()?;
+            Ok(letdef
+                .defs
+                .iter()
+                .zip(values)
+                .fold(env.clone(), |env, (def, value)| {
+                    env.bind(&def.id, Some(value))
+                }))
+        }
+    }
+    fn def(&mut self, def: &'a Def, env: &Env<'a>) -> InterpResult<Value<'a>> {
+        Ok(match &def.kind {
+            DefKind::Const { expr } => self.expr(expr, env)?,
+            DefKind::Variable => Value::new_cell(self.zero_of_def(def)),
+            DefKind::Array { dims } => {
+                let dims = dims
+                    .iter()
+                    .map(|dim| match self.expr(dim, env)? {
+                        Value::Int(size) if size > 0 => Ok(size as usize),
+                        Value::Int(size) => Err(RuntimeError::InvalidArraySize {
+                            size,
+                            span: dim.span.clone(),
+                        }),
+                        _ => unreachable!("array sizes are ints"),
+                    })
+                    .collect::<InterpResult<_>>()?;
+                Value::new_array(dims, self.zero_of_def(def))
+            }
+            DefKind::Function { pars, expr } => Value::new_func(Callee::Closure {
+                pars,
+                body: expr,
+                env: env.clone(),
+            }),
+        })
+    }
+    pub(super) fn expr(&mut self, expr: &'a Expr, env: &Env<'a>) -> InterpResult<Value<'a>> {
+        let span = &expr.span;
+        Ok(match &expr.kind {
+            ExprKind::UnitLiteral => Value::Unit,
+            ExprKind::IntLiteral(n) => Value::Int(*n as i64),
+            ExprKind::FloatLiteral(x) => Value::Float(*x),
+            ExprKind::CharLiteral(c) => Value::Char(*c),
+            ExprKind::StringLiteral(s) => Value::new_string(s.as_bytes()),
+            ExprKind::BoolLiteral(b) => Value::Bool(*b),
+            ExprKind::Tuple(exprs) => Value::Tuple(self.exprs(exprs, env)?.into()),
+            ExprKind::Unop(unop) => self.unop(unop, span, env)?,
+            ExprKind::Binop(binop) => self.binop(binop, span, env)?,
+            ExprKind::Call(Call { id, args }) => {
+                let func = self.lookup(id, env, span)?;
+                if args.is_empty() {
+                    func
+                } else {
+                    let args = self.exprs(args, env)?;
+                    self.apply(func, args, span)?
+                }
+            }
+            ExprKind::ConstrCall(Call { id, args }) => Value::Constr {
+                id,
+                args: self.exprs(args, env)?.into(),
+            },
+            ExprKind::ArrayAccess(access) => self.array_access(access, span, env)?,
+            ExprKind::Dim(dim) => {
+                let array = self.array(&dim.id, env, span)?;
+                let size = usize::try_from(dim.dim - 1)
+                    .ok()
+                    .and_then(|i| array.dims.get(i))
+                    .ok_or_else(|| RuntimeError::InvalidDim {
+                        id: dim.id.clone(),
+                        dim: dim.dim,
+                        span: span.clone(),
+                    })?;
+                Value::Int(*size as i64)
+            }
+            ExprKind::New(annotation) => Value::new_cell(zero_of_annotation(annotation)),
+            ExprKind::LetIn(letin) => {
+                let env = self.letdef(&letin.letdef, env)?;
+                self.expr(&letin.expr, &env)?
+            }
+            ExprKind::If(if_) => {
+                if self.bool(&if_.cond, env)? {
+                    self.expr(&if_.then_body, env)?
+                } else if let Some(else_body) = &if_.else_body {
+                    self.expr(else_body, env)?
+                } else {
+                    Value::Unit
+                }
+            }
+            ExprKind::While(while_) => {
+                while self.bool(&while_.cond, env)? {
+                    self.expr(&while_.body, env)?;
+                }
+                Value::Unit
+            }
+            ExprKind::For(for_) => self.for_loop(for_, env)?,
+            ExprKind::Match(match_) => {
+                let value = self.expr(&match_.to_match, env)?;
+                let (clause, env) = match_clause(&match_.clauses, &value, env)
+                    .ok_or_else(|| RuntimeError::MatchFailure { span: span.clone() })?;
+                self.expr(&clause.expr, &env)?
+            }
+            ExprKind::Error => unreachable!("programs with syntax errors are not run"),
+        })
+    }
+    fn unop(&mut self, unop: &'a Unop, span: &Span, env: &Env<'a>) -> InterpResult<Value<'a>> {
+        Ok(match (&unop.op, self.expr(&unop.operand, env)?) {
+            (UnopKind::Plus | UnopKind::PlusFlt, value) => value,
+            (UnopKind::Minus, Value::Int(n)) => Value::Int(n.wrapping_neg()),
+            (UnopKind::Minus | UnopKind::MinusFlt, Value::Float(x)) => Value::Float(-x),
+            (UnopKind::Not, Value::Bool(b)) => Value::Bool(!b),
+            (UnopKind::Deref, Value::Ref(loc)) => self.deref(&loc, span)?,
+            (UnopKind::Delete, Value::Ref(loc @ Loc::Cell(_))) => {
+                self.deref(&loc, span).or_else(|err| match err {
+                    RuntimeError::Uninitialized { .. } => Ok(Value::Unit),
+                    err => Err(err),
+                })?;
+                loc.set(Slot::Deleted);
+                Value::Unit
+            }
+            (UnopKind::Delete, Value::Ref(Loc::Elem { .. })) => {
+                return Err(RuntimeError::InvalidDelete { span: span.clone() })
+            }
+            (op, _) => unreachable!("ill-typed operand of {}", op),
+        })
+    }
+    /// Bounds checks every index, the result is a reference to the element.
+    fn array_access(
+        &mut self,
+        access: &'a ArrayAccess,
+        span: &Span,
+        env: &Env<'a>,
+    ) -> InterpResult<Value<'a>> {
+        let array = self.array(&access.id, env, span)?;
+        let mut index = 0;
+        for (dim, (expr, size)) in access.indexes.iter().zip(&array.dims).enumerate() {
+            match self.int(expr, env)? {
+                i if 0 <= i && (i as usize) < *size => index = index * size + i as usize,
+                i => {
+                    return Err(RuntimeError::IndexOutOfBounds {
+                        index: i,
+                        dim: dim + 1,
+                        size: *size,
+                        span: span.clone(),
+                    })
+                }
+            }
+        }
+        Ok(Value::Ref(Loc::Elem { array, index }))
+    }
+    fn for_loop(&mut self, for_: &'a For, env: &Env<'a>) -> InterpResult<Value<'a>> {
+        let from = self.int(&for_.from, env)?;
+        let to = self.int(&for_.to, env)?;
+        let mut run = |i| {
+            self.expr(&for_.body, &env.bind(&for_.id, Some(Value::Int(i))))
+                .map(drop)
+        };
+        if for_.ascending {
+            (from..=to).try_for_each(&mut run)?
+        } else {
+            (to..=from).rev().try_for_each(&mut run)?
+        }
+        Ok(Value::Unit)
+    }
+    fn exprs(&mut self, exprs: &'a [Expr], env: &Env<'a>) -> InterpResult<Vec<Value<'a>>> {
+        exprs.iter().map(|expr| self.expr(expr, env)).collect()
+    }
+    fn int(&mut self, expr: &'a Expr, env: &Env<'a>) -> InterpResult<i64> {
+        match self.expr(expr, env)? {
+            Value::Int(n) => Ok(n),
+            _ => unreachable!("expected an int"),
+        }
+    }
+    fn bool(&mut self, expr: &'a Expr, env: &Env<'a>) -> InterpResult<bool> {
+        match self.expr(expr, env)? {
+            Value::Bool(b) => Ok(b),
+            _ => unreachable!("expected a bool"),
+        }
+    }
+    fn binop(&mut self, binop: &'a Binop, span: &Span, env: &Env<'a>) -> InterpResult<Value<'a>> {
+        use BinopKind::*;
+        match binop.op {
+            And => {
+                return Ok(Value::Bool(
+                    self.bool(&binop.lhs, env)? && self.bool(&binop.rhs, env)?,
+                ))
+            }
+            Or => {
+                return Ok(Value::Bool(
+                    self.bool(&binop.lhs, env)? || self.bool(&binop.rhs, env)?,
+                ))
+            }
+            Semicolon => {
+                self.expr(&binop.lhs, env)?;
+                return self.expr(&binop.rhs, env);
+            }
+            _ => {}
+        }
+        let lhs = self.expr(&binop.lhs, env)?;
+        let rhs = self.expr(&binop.rhs, env)?;
+        Ok(match (&binop.op, lhs, rhs) {
+            (Assign, Value::Ref(loc), value) => {
+                if let Slot::Deleted = loc.get() {
+                    return Err(RuntimeError::UseAfterDelete { span: span.clone() });
+                }
+                loc.set(Slot::Full(value));
+                Value::Unit
+            }
+            (StrEq, lhs, rhs) => Value::Bool(lhs.struct_eq(&rhs)),
+            (StrNotEq, lhs, rhs) => Value::Bool(!lhs.struct_eq(&rhs)),
+            (NatEq, lhs, rhs) => Value::Bool(lhs.phys_eq(&rhs)),
+            (NatNotEq, lhs, rhs) => Value::Bool(!lhs.phys_eq(&rhs)),
+            (op @ (Lt | Gt | LEq | GEq), lhs, rhs) => {
+                let ordering = match (lhs, rhs) {
+                    (Value::Int(l), Value::Int(r)) => l.partial_cmp(&r),
+                    (Value::Float(l), Value::Float(r)) => l.partial_cmp(&r),
+                    (Value::Char(l), Value::Char(r)) => l.partial_cmp(&r),
+                    (Value::Bool(l), Value::Bool(r)) => l.partial_cmp(&r),
+                    _ => unreachable!("ill-typed operands of {}", op),
+                };
+                Value::Bool(ordering.is_some_and(|ordering| match op {
+                    Lt => ordering.is_lt(),
+                    Gt => ordering.is_gt(),
+                    LEq => ordering.is_le(),
+                    _ => ordering.is_ge(),
+                }))
+            }
+            (Div | Mod, Value::Int(_), Value::Int(0)) => {
+                return Err(RuntimeError::DivisionByZero { span: span.clone() })
+            }
+            (op, Value::Int(l), Value::Int(r)) => Value::Int(match op {
+                Add => l.wrapping_add(r),
+                Sub => l.wrapping_sub(r),
+                Mul => l.wrapping_mul(r),
+                Div => l.wrapping_div(r),
+                Mod => l.wrapping_rem(r),
+                Pow => ipow(l, r),
+                _ => unreachable!("ill-typed operands of {}", op),
+            }),
+            (op, Value::Float(l), Value::Float(r)) => Value::Float(match op {
+                Add | AddFlt => l + r,
+                Sub | SubFlt => l - r,
+                Mul | MulFlt => l * r,
+                Div | DivFlt => l / r,
+                Pow => l.powf(r),
+                _ => unreachable!("ill-typed operands of {}", op),
+            }),
+            (op, _, _) => unreachable!("ill-typed operands of {}", op),
+        })
+    }
+    /// Calls `func` with `args`, which may be fewer or more than it takes.
+    pub(super) fn apply(
+        &mut self,
+        mut func: Value<'a>,
+        mut args: Vec<Value<'a>>,
+        span: &Span,
+    ) -> InterpResult<Value<'a>> {
+        loop {
+            let Value::Func(f) = func else {
+                unreachable!("only functions can be applied")
+            };
+            let arity = f.callee.arity();
+            let mut applied = f.applied.clone();
+            applied.append(&mut args);
+            if applied.len() < arity {
+                return Ok(Value::Func(Rc::new(Func {
+                    callee: f.callee.clone(),
+                    applied,
+                })));
+            }
+            args = applied.split_off(arity);
+            let result = match &f.callee {
+                Callee::Closure { pars, body, env } => {
+                    let env = pars
+                        .iter()
+                        .zip(applied)
+                        .fold(env.clone(), |env, (par, arg)| env.bind(&par.id, Some(arg)));
+                    self.expr(body, &env)?
+                }
+                Callee::Builtin(builtin) => self.builtin(builtin, applied, span)?,
+            };
+            if args.is_empty() {
+                return Ok(result);
+            }
+            func = result;
+        }
+    }
+    fn lookup(&self, id: &str, env: &Env<'a>, span: &Span) -> InterpResult<Value<'a>> {
+        match env.lookup(id) {
+            Some(Some(value)) => Ok(value),
+            Some(None) => Err(RuntimeError::Uninitialized { span: span.clone() }),
+            None => Ok(Value::new_func(Callee::Builtin(
+                BUILTINS
+                    .iter()
+                    .find(|builtin| builtin.id == id)
+                    .expect("names are resolved by sem"),
+            ))),
+        }
+    }
+    fn array(&self, id: &str, env: &Env<'a>, span: &Span) -> InterpResult<Rc<Array<'a>>> {
+        match self.lookup(id, env, span)? {
+            Value::Array(array) => Ok(array),
+            _ => unreachable!("expected an array"),
+        }
+    }
+    pub(super) fn deref(&self, loc: &Loc<'a>, span: &Span) -> InterpResult<Value<'a>> {
+        match loc.get() {
+            Slot::Full(value) => Ok(value),
+            Slot::Uninit => Err(RuntimeError::Uninitialized { span: span.clone() }),
+            Slot::Deleted => Err(RuntimeError::UseAfterDelete { span: span.clone() }),
+        }
+    }
+    /// Zero value for the cells of a mutable variable or array.
+    fn zero_of_def(&self, def: &'a Def) -> Slot<'a> {
+        let types = &self.sem.types;
+        let Some(ty) = types.get_type(def) else {
+            return Slot::Uninit;
+        };
+        match types.get(types.resolve_type(ty)) {
+            Type::Ref(inner) | Type::Array { inner, .. } => {
+                zero_of_type(types.get(types.resolve_type(*inner)))
+            }
+            _ => Slot::Uninit,
+        }
+    }
+}
+
+/// The first clause whose pattern matches `value`, and the scope of its body.
+fn match_clause<'a>(
+    clauses: &'a [Clause],
+    value: &Value<'a>,
+    env: &Env<'a>,
+) -> Option<(&'a Clause, Env<'a>)> {
+    clauses.iter().find_map(|clause| {
+        let mut env = env.clone();
+        match_pattern(&clause.pattern, value, &mut env).then_some((clause, env))
+    })
+}
+/// Binds the variables of `pattern` in `env` if it matches.
+fn match_pattern<'a>(pattern: &'a Pattern, value: &Value<'a>, env: &mut Env<'a>) -> bool {
+    match (&pattern.kind, value) {
+        (PatternKind::IntLiteral(p), Value::Int(n)) => *p as i64 == *n,
+        (PatternKind::FloatLiteral(p), Value::Float(x)) => p == x,
+        (PatternKind::CharLiteral(p), Value::Char(c)) => p == c,
+        (PatternKind::BoolLiteral(p), Value::Bool(b)) => p == b,
+        (PatternKind::StringLiteral(p), Value::Array(array)) => {
+            array.string_bytes() == p.as_bytes()
+        }
+        (PatternKind::IdLower(id), value) => {
+            *env = env.bind(id, Some(value.clone()));
+            true
+        }
+        (PatternKind::Tuple(patterns), Value::Tuple(values)) => {
+            patterns.len() == values.len()
+                && patterns
+                    .iter()
+                    .zip(values.iter())
+                    .all(|(pattern, value)| match_pattern(pattern, value, env))
+        }
+        (PatternKind::IdUpper { id, args: patterns }, Value::Constr { id: constr, args }) => {
+            id == constr
+                && patterns.len() == args.len()
+                && patterns
+                    .iter()
+                    .zip(args.iter())
+                    .all(|(pattern, value)| match_pattern(pattern, value, env))
+        }
+        _ => false,
+    }
+}
+
+/// Scalars start zeroed, like the memory compiled code gets from the runtime.
+fn zero_of_type<'a>(ty: &Type) -> Slot<'a> {
+    Slot::Full(match ty {
+        Type::Unit => Value::Unit,
+        Type::Int => Value::Int(0),
+        Type::Char => Value::Char(0),
+        Type::Bool => Value::Bool(false),
+        Type::Float => Value::Float(0.0),
+        _ => return Slot::Uninit,
+    })
+}
+fn zero_of_annotation<'a>(annotation: &TypeAnnotation) -> Slot<'a> {
+    Slot::Full(match annotation {
+        TypeAnnotation::Unit => Value::Unit,
+        TypeAnnotation::Int => Value::Int(0),
+        TypeAnnotation::Char => Value::Char(0),
+        TypeAnnotation::Bool => Value::Bool(false),
+        TypeAnnotation::Float => Value::Float(0.0),
+        _ => return Slot::Uninit,
+    })
+}
+
+/// Wraps around on overflow, like `llama_ipow` of the runtime.
+fn ipow(base: i64, mut exp: i64) -> i64 {
+    let (mut result, mut factor) = (1i64, base);
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.wrapping_mul(factor);
+        }
+        factor = factor.wrapping_mul(factor);
+        exp >>= 1;
+    }
+    result
+}
@@ -0,0 +1,212 @@
+mod builtins;
+mod eval;
+pub mod value;
+
+use std::io::{BufRead, Write};
+
+use thiserror::Error;
+
+use crate::{
+    diagnostic::{codes, Diagnostic},
+    parse::ast::{def::Definition, expr::Expr, Program, Span},
+};
+
+use super::sem::sem_table::SemTable;
+
+use self::{
+    eval::Eval,
+    value::{Env, Value},
+};
+
+// ! Implementation notes:
+// !   Runs the checked AST directly, the `SemTable` is only consulted for the types
+// !   of mutable variables and `new`, so that their cells start zeroed like in compiled code.
+// !   Definitions are evaluated in order, extending a persistent scope of globals,
+// !   which is what lets the same interpreter be fed one definition at a time.
+// !   Evaluation recurses on the Rust stack, callers with deep programs should give
+// !   it a big one.
+
+/// Interprets `ast` and returns its exit code.
+pub fn interpret<'a>(
+    ast: &'a Program,
+    sem: &SemTable<'a>,
+    input: Box<dyn BufRead + 'a>,
+    output: Box<dyn Write + 'a>,
+) -> InterpResult<i32> {
+    Interpreter::new(input, output).run_program(ast, sem)
+}
+
+pub struct Interpreter<'a> {
+    globals: Env<'a>,
+    io: Io<'a>,
+}
+struct Io<'a> {
+    input: Box<dyn BufRead + 'a>,
+    output: Box<dyn Write + 'a>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(input: Box<dyn BufRead + 'a>, output: Box<dyn Write + 'a>) -> Self {
+        Self {
+            globals: Env::default(),
+            io: Io { input, output },
+        }
+    }
+    /// Evaluates all definitions of `ast`, returns the code the program exited with.
+    pub fn run_program(&mut self, ast: &'a Program, sem: &SemTable<'a>) -> InterpResult<i32> {
+        let res = ast
+            .definitions
+            .iter()
+            .try_for_each(|def| self.eval_definition(def, sem));
+        match res {
+            Ok(()) => Ok(0),
+            Err(RuntimeError::Exit { code }) => Ok(code),
+            Err(err) => Err(err),
+        }
+    }
+    /// Evaluates a top level definition, its bindings are visible to the ones that follow.
+    pub fn eval_definition(&mut self, def: &'a Definition, sem: &SemTable<'a>) -> InterpResult<()> {
+        let res = match def {
+            Definition::Let(letdef) => {
+                let globals = self.globals.clone();
+                self.eval(sem)
+                    .letdef(letdef, &globals)
+                    .map(|globals| self.globals = globals)
+            }
+            Definition::Type(_) => Ok(()),
+            Definition::Error(_) => unreachable!("programs with syntax errors are not run"),
+        };
+        self.flush_after(res)
+    }
+    /// Evaluates an expression in the scope of the globals defined so far.
+    pub fn eval_expr(&mut self, expr: &'a Expr, sem: &SemTable<'a>) -> InterpResult<Value<'a>> {
+        let globals = self.globals.clone();
+        let res = self.eval(sem).expr(expr, &globals);
+        self.flush_after(res)
+    }
+    /// The value of a global, `None` if it is not defined.
+    pub fn lookup(&self, name: &str) -> Option<Value<'a>> {
+        self.globals.lookup(name).flatten()
+    }
+    /// What programs read from, shared with whoever drives the interpreter.
+    pub fn input(&mut self) -> &mut (dyn BufRead + 'a) {
+        &mut self.io.input
+    }
+    /// What programs print to, shared with whoever drives the interpreter.
+    pub fn output(&mut self) -> &mut (dyn Write + 'a) {
+        &mut self.io.output
+    }
+    fn eval<'i>(&'i mut self, sem: &'i SemTable<'a>) -> Eval<'i, 'a> {
+        Eval {
+            sem,
+            io: &mut self.io,
+        }
+    }
+    /// Output is flushed before anything is reported, so that it comes first.
+    fn flush_after<T>(&mut self, res: InterpResult<T>) -> InterpResult<T> {
+        self.io.output.flush()?;
+        res
+    }
+}
+
+pub type InterpResult<T> = Result<T, RuntimeError>;
+#[derive(Error, Debug)]
+pub enum RuntimeError {
+    #[error("Division by zero (at {})", span)]
+    DivisionByZero { span: Span },
+    #[error(
+        "Index {} out of bounds for dimension {} of size {} (at {})",
+        index,
+        dim,
+        size,
+        span
+    )]
+    IndexOutOfBounds {
+        index: i64,
+        dim: usize,
+        size: usize,
+        span: Span,
+    },
+    #[error("Array {} has no dimension {} (at {})", id, dim, span)]
+    InvalidDim { id: String, dim: i32, span: Span },
+    #[error("Array dimensions must be positive, got {} (at {})", size, span)]
+    InvalidArraySize { size: i64, span: Span },
+    #[error("Use of a deleted reference (at {})", span)]
+    UseAfterDelete { span: Span },
+    #[error("Array elements cannot be deleted (at {})", span)]
+    InvalidDelete { span: Span },
+    #[error("Use of an uninitialized value (at {})", span)]
+    Uninitialized { span: Span },
+    #[error("No match case matched the value (at {})", span)]
+    MatchFailure { span: Span },
+    #[error("Program exited with code {}", code)]
+    Exit { code: i32 },
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+}
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        use RuntimeError::*;
+        let (code, message, span, label) = match error {
+            DivisionByZero { span } => (
+                &codes::DIVISION_BY_ZERO,
+                "Division by zero".to_string(),
+                span,
+                "divided by zero here",
+            ),
+            IndexOutOfBounds {
+                index,
+                dim,
+                size,
+                span,
+            } => (
+                &codes::INDEX_OUT_OF_BOUNDS,
+                format!(
+                    "Index {} out of bounds for dimension {} of size {}",
+                    index, dim, size
+                ),
+                span,
+                "out of bounds",
+            ),
+            InvalidDim { id, dim, span } => (
+                &codes::INVALID_DIMENSION,
+                format!("Array {} has no dimension {}", id, dim),
+                span,
+                "no such dimension",
+            ),
+            InvalidArraySize { size, span } => (
+                &codes::INVALID_ARRAY_SIZE,
+                format!("Array dimensions must be positive, got {}", size),
+                span,
+                "evaluated to a size that is not positive",
+            ),
+            UseAfterDelete { span } => (
+                &codes::USE_AFTER_DELETE,
+                "Use of a deleted reference".to_string(),
+                span,
+                "deleted before this use",
+            ),
+            InvalidDelete { span } => (
+                &codes::INVALID_DELETE,
+                "Array elements cannot be deleted".to_string(),
+                span,
+                "not made by `new`",
+            ),
+            Uninitialized { span } => (
+                &codes::UNINITIALIZED_VALUE,
+                "Use of an uninitialized value".to_string(),
+                span,
+                "used before it has a value",
+            ),
+            MatchFailure { span } => (
+                &codes::MATCH_FAILURE,
+                "No match case matched the value".to_string(),
+                span,
+                "no clause matches",
+            ),
+            IO(_) => return Diagnostic::new(&codes::IO_ERROR, error.to_string()),
+            Exit { .. } => unreachable!("exiting is not an error, the exit code is returned"),
+        };
+        Diagnostic::new(code, message).with_primary(span, label)
+    }
+}
@@ -0,0 +1,311 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{
+    parse::ast::{def::Par, expr::Expr},
+    pass::sem::builtins::Builtin,
+};
+
+/// A runtime value. Everything behind an `Rc` is shared, like a pointer in compiled code.
+#[derive(Clone)]
+pub enum Value<'a> {
+    Unit,
+    Int(i64),
+    Float(f64),
+    Char(u8),
+    Bool(bool),
+    Tuple(Rc<[Value<'a>]>),
+    Constr { id: &'a str, args: Rc<[Value<'a>]> },
+    Ref(Loc<'a>),
+    Array(Rc<Array<'a>>),
+    Func(Rc<Func<'a>>),
+}
+
+/// The contents of a memory cell.
+#[derive(Clone)]
+pub enum Slot<'a> {
+    /// Never assigned, and of a type without a zero value.
+    Uninit,
+    Full(Value<'a>),
+    Deleted,
+}
+
+/// Where a `Value::Ref` points to.
+#[derive(Clone)]
+pub enum Loc<'a> {
+    /// A cell made by `new` or `let mutable`.
+    Cell(Rc<RefCell<Slot<'a>>>),
+    /// An element of an array, by its row-major index.
+    Elem { array: Rc<Array<'a>>, index: usize },
+}
+
+pub struct Array<'a> {
+    pub dims: Vec<usize>,
+    pub elems: RefCell<Vec<Slot<'a>>>,
+}
+
+/// A function value, along with the arguments it has been partially applied to.
+pub struct Func<'a> {
+    pub callee: Callee<'a>,
+    pub applied: Vec<Value<'a>>,
+}
+#[derive(Clone)]
+pub enum Callee<'a> {
+    Closure {
+        pars: &'a [Par],
+        body: &'a Expr,
+        env: Env<'a>,
+    },
+    Builtin(&'static Builtin),
+}
+
+impl<'a> Value<'a> {
+    pub fn new_cell(slot: Slot<'a>) -> Self {
+        Value::Ref(Loc::Cell(Rc::new(RefCell::new(slot))))
+    }
+    pub fn new_array(dims: Vec<usize>, init: Slot<'a>) -> Self {
+        let size = dims.iter().product();
+        Value::Array(Rc::new(Array {
+            dims,
+            elems: RefCell::new(vec![init; size]),
+        }))
+    }
+    /// A fresh `char[]` holding `bytes` and a terminating '\0'.
+    pub fn new_string(bytes: &[u8]) -> Self {
+        let elems = bytes
+            .iter()
+            .chain(std::iter::once(&0))
+            .map(|b| Slot::Full(Value::Char(*b)))
+            .collect();
+        Value::Array(Rc::new(Array {
+            dims: vec![bytes.len() + 1],
+            elems: RefCell::new(elems),
+        }))
+    }
+    pub fn new_func(callee: Callee<'a>) -> Self {
+        Value::Func(Rc::new(Func {
+            callee,
+            applied: Vec::new(),
+        }))
+    }
+    /// Structural equality (`=`), refs are compared by their contents.
+    pub fn struct_eq(&self, other: &Self) -> bool {
+        use Value::*;
+        match (self, other) {
+            (Tuple(lhs), Tuple(rhs)) => all_struct_eq(lhs, rhs),
+            (
+                Constr {
+                    id: lid,
+                    args: largs,
+                },
+                Constr {
+                    id: rid,
+                    args: rargs,
+                },
+            ) => lid == rid && all_struct_eq(largs, rargs),
+            (Ref(lhs), Ref(rhs)) => match (lhs.get(), rhs.get()) {
+                (Slot::Full(lhs), Slot::Full(rhs)) => lhs.struct_eq(&rhs),
+                _ => lhs.same_as(rhs),
+            },
+            _ => self.scalar_eq(other),
+        }
+    }
+    /// Physical equality (`==`), compound values are compared by identity.
+    pub fn phys_eq(&self, other: &Self) -> bool {
+        use Value::*;
+        match (self, other) {
+            (Tuple(lhs), Tuple(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (
+                Constr {
+                    id: lid,
+                    args: largs,
+                },
+                Constr {
+                    id: rid,
+                    args: rargs,
+                },
+            ) => lid == rid && (largs.is_empty() || Rc::ptr_eq(largs, rargs)),
+            (Ref(lhs), Ref(rhs)) => lhs.same_as(rhs),
+            (Array(lhs), Array(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Func(lhs), Func(rhs)) => Rc::ptr_eq(lhs, rhs),
+            _ => self.scalar_eq(other),
+        }
+    }
+    fn scalar_eq(&self, other: &Self) -> bool {
+        use Value::*;
+        match (self, other) {
+            (Unit, Unit) => true,
+            (Int(lhs), Int(rhs)) => lhs == rhs,
+            (Float(lhs), Float(rhs)) => lhs == rhs,
+            (Char(lhs), Char(rhs)) => lhs == rhs,
+            (Bool(lhs), Bool(rhs)) => lhs == rhs,
+            _ => false,
+        }
+    }
+}
+fn all_struct_eq<'a>(lhs: &[Value<'a>], rhs: &[Value<'a>]) -> bool {
+    lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(l, r)| l.struct_eq(r))
+}
+
+impl<'a> Loc<'a> {
+    pub fn get(&self) -> Slot<'a> {
+        match self {
+            Loc::Cell(cell) => cell.borrow().clone(),
+            Loc::Elem { array, index } => array.elems.borrow()[*index].clone(),
+        }
+    }
+    pub fn set(&self, slot: Slot<'a>) {
+        match self {
+            Loc::Cell(cell) => *cell.borrow_mut() = slot,
+            Loc::Elem { array, index } => array.elems.borrow_mut()[*index] = slot,
+        }
+    }
+    pub fn same_as(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Loc::Cell(lhs), Loc::Cell(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (
+                Loc::Elem {
+                    array: la,
+                    index: li,
+                },
+                Loc::Elem {
+                    array: ra,
+                    index: ri,
+                },
+            ) => Rc::ptr_eq(la, ra) && li == ri,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Array<'a> {
+    /// The bytes of a `char[]` up to its terminating '\0' (or its end).
+    pub fn string_bytes(&self) -> Vec<u8> {
+        self.elems
+            .borrow()
+            .iter()
+            .map_while(|slot| match slot {
+                Slot::Full(Value::Char(c)) if *c != 0 => Some(*c),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl<'a> Callee<'a> {
+    pub fn arity(&self) -> usize {
+        match self {
+            Callee::Closure { pars, .. } => pars.len(),
+            Callee::Builtin(builtin) => builtin.arity(),
+        }
+    }
+}
+
+/// A persistent scope, closures keep the one they were created in.
+#[derive(Clone, Default)]
+pub struct Env<'a>(Option<Rc<Frame<'a>>>);
+struct Frame<'a> {
+    name: &'a str,
+    /// Empty until a recursive definition has been evaluated.
+    value: RefCell<Option<Value<'a>>>,
+    parent: Env<'a>,
+}
+impl<'a> Env<'a> {
+    pub fn bind(&self, name: &'a str, value: Option<Value<'a>>) -> Self {
+        Env(Some(Rc::new(Frame {
+            name,
+            value: RefCell::new(value),
+            parent: self.clone(),
+        })))
+    }
+    /// Fills in the innermost binding of `name`.
+    pub fn set(&self, name: &str, value: Value<'a>) {
+        let frame = self.frame(name).expect("binding should exist");
+        *frame.value.borrow_mut() = Some(value);
+    }
+    /// `None` if there is no such binding, `Some(None)` if it is not yet evaluated.
+    pub fn lookup(&self, name: &str) -> Option<Option<Value<'a>>> {
+        self.frame(name).map(|frame| frame.value.borrow().clone())
+    }
+    fn frame(&self, name: &str) -> Option<&Frame<'a>> {
+        let mut env = self;
+        while let Some(frame) = &env.0 {
+            if frame.name == name {
+                return Some(frame);
+            }
+            env = &frame.parent;
+        }
+        None
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{}", format_float(*x)),
+            Value::Char(c) => write!(f, "'{}'", std::ascii::escape_default(*c)),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, ")")
+            }
+            Value::Constr { id, args } => {
+                write!(f, "{}", id)?;
+                for arg in args.iter() {
+                    match arg {
+                        Value::Constr { args, .. } if !args.is_empty() => write!(f, " ({})", arg)?,
+                        _ => write!(f, " {}", arg)?,
+                    }
+                }
+                Ok(())
+            }
+            Value::Ref(_) => write!(f, "<ref>"),
+            Value::Array(_) => write!(f, "<array>"),
+            Value::Func(_) => write!(f, "<fun>"),
+        }
+    }
+}
+
+/// Formats like C's `printf("%g")`, to match the compiled `print_float`.
+pub fn format_float(x: f64) -> String {
+    const PRECISION: i32 = 6;
+    if x.is_nan() {
+        return if x.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if x.is_infinite() {
+        return if x < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    if x == 0.0 {
+        return if x.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+    let scientific = format!("{:.*e}", (PRECISION - 1) as usize, x);
+    let (mantissa, exp) = scientific
+        .split_once('e')
+        .expect("exponent in scientific notation");
+    let exp: i32 = exp.parse().expect("exponent is a number");
+    if (-4..PRECISION).contains(&exp) {
+        let fixed = format!("{:.*}", (PRECISION - 1 - exp) as usize, x);
+        strip_fraction_zeros(&fixed).to_string()
+    } else {
+        format!(
+            "{}e{}{:02}",
+            strip_fraction_zeros(mantissa),
+            if exp < 0 { '-' } else { '+' },
+            exp.abs()
+        )
+    }
+}
+fn strip_fraction_zeros(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use super::{
+    ir::{Block, BlockId, EnvId, FuncId, Function, Inst, Temp, Terminator, Ty},
+    mono::Subst,
+    BindingId,
+};
+
+/// Accumulates the body of a function while it is being lowered.
+pub struct FnBuilder {
+    pub id: FuncId,
+    pub env: Option<EnvId>,
+    /// The instantiation of the (generic) definition being lowered.
+    pub subst: Subst,
+    /// Temps holding the values of the bindings available in this function.
+    pub locals: HashMap<BindingId, Temp>,
+    /// Bindings of enclosing functions read by this function, in environment order.
+    pub captures: Vec<BindingId>,
+    /// The binding of the function itself, whose value is the closure it was entered through.
+    pub self_binding: Option<BindingId>,
+    params: Vec<Temp>,
+    temps: Vec<Ty>,
+    blocks: Vec<Block>,
+    current: BlockId,
+    /// Number of capture reads at the start of the entry block.
+    prelude_len: usize,
+}
+
+impl FnBuilder {
+    pub fn new(id: FuncId, env: Option<EnvId>, subst: Subst) -> Self {
+        Self {
+            id,
+            env,
+            subst,
+            locals: HashMap::new(),
+            captures: Vec::new(),
+            self_binding: None,
+            params: Vec::new(),
+            temps: Vec::new(),
+            blocks: vec![Self::empty_block()],
+            current: BlockId(0),
+            prelude_len: 0,
+        }
+    }
+    fn empty_block() -> Block {
+        Block {
+            insts: Vec::new(),
+            term: Terminator::Unreachable,
+        }
+    }
+    pub fn new_temp(&mut self, ty: Ty) -> Temp {
+        self.temps.push(ty);
+        Temp(self.temps.len() as u32 - 1)
+    }
+    pub fn new_param(&mut self, ty: Ty) -> Temp {
+        let temp = self.new_temp(ty);
+        self.params.push(temp);
+        temp
+    }
+    pub fn temp_ty(&self, temp: Temp) -> &Ty {
+        &self.temps[temp.0 as usize]
+    }
+    pub fn emit(&mut self, inst: Inst) {
+        self.blocks[self.current.0 as usize].insts.push(inst);
+    }
+    /// Inserts `inst` at the start of the entry block, so that it dominates every use.
+    pub fn emit_prelude(&mut self, inst: Inst) {
+        self.blocks[0].insts.insert(self.prelude_len, inst);
+        self.prelude_len += 1;
+    }
+    pub fn new_block(&mut self) -> BlockId {
+        self.blocks.push(Self::empty_block());
+        BlockId(self.blocks.len() as u32 - 1)
+    }
+    pub fn switch_to(&mut self, block: BlockId) {
+        self.current = block;
+    }
+    /// Ends the current block. Anything emitted afterwards (before a `switch_to`) is dead code.
+    pub fn terminate(&mut self, term: Terminator) {
+        self.blocks[self.current.0 as usize].term = term;
+        self.current = self.new_block();
+    }
+    /// Drops the blocks that are not reachable from the entry block.
+    pub fn finish(self, name: String, ret: Ty) -> Function {
+        let mut new_ids: Vec<Option<BlockId>> = vec![None; self.blocks.len()];
+        let mut order = Vec::new();
+        let mut stack = vec![BlockId(0)];
+        while let Some(block) = stack.pop() {
+            if new_ids[block.0 as usize].is_some() {
+                continue;
+            }
+            new_ids[block.0 as usize] = Some(BlockId(order.len() as u32));
+            order.push(block);
+            match &self.blocks[block.0 as usize].term {
+                Terminator::Jump(target) => stack.push(*target),
+                Terminator::Branch { then, else_, .. } => {
+                    stack.push(*else_);
+                    stack.push(*then);
+                }
+                _ => (),
+            }
+        }
+        let remap = |block: &BlockId| new_ids[block.0 as usize].expect("target is reachable");
+        let mut blocks: Vec<Option<Block>> = self.blocks.into_iter().map(Some).collect();
+        let blocks = order
+            .into_iter()
+            .map(|old| {
+                let mut block = blocks[old.0 as usize].take().expect("visited once");
+                block.term = match block.term {
+                    Terminator::Jump(target) => Terminator::Jump(remap(&target)),
+                    Terminator::Branch { cond, then, else_ } => Terminator::Branch {
+                        cond,
+                        then: remap(&then),
+                        else_: remap(&else_),
+                    },
+                    term => term,
+                };
+                block
+            })
+            .collect();
+        Function {
+            name,
+            env: self.env,
+            params: self.params,
+            ret,
+            temps: self.temps,
+            blocks,
+        }
+    }
+}
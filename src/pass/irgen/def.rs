@@ -0,0 +1,406 @@
+use std::rc::Rc;
+
+use crate::parse::ast::{
+    def::{Def, DefKind, Letdef},
+    expr::{Expr, ExprKind},
+};
+
+use super::{
+    ir::{FuncId, Inst, Runtime, Ty},
+    mono::{mono_type, Subst},
+    BindingId, BindingKind, GenericGroup, IrGen, Signature,
+};
+use crate::pass::sem::builtins::Builtin;
+
+impl<'a, 's> IrGen<'a, 's> {
+    pub(super) fn lower_letdef(&mut self, letdef: &'a Letdef, top_level: bool) {
+        // Generic functions are lowered on use, once per instance.
+        let generic = letdef.defs.iter().any(|def| {
+            matches!(def.kind, DefKind::Function { .. }) && self.sem.types.is_generic(def)
+        });
+        let group = generic.then(|| {
+            Rc::new(GenericGroup {
+                letdef,
+                scope: self.scope.clone(),
+                subst: self.cur().subst.clone(),
+                top_level,
+            })
+        });
+        let mut bindings = Vec::with_capacity(letdef.defs.len());
+        let mut funcs = Vec::new();
+        let mut values = Vec::new();
+        for (idx, def) in letdef.defs.iter().enumerate() {
+            let binding = match (&def.kind, &group) {
+                (DefKind::Function { .. }, Some(group)) => {
+                    let ty = self.ty_of(def);
+                    let kind = BindingKind::Generic {
+                        group: group.clone(),
+                        idx,
+                    };
+                    self.new_binding(ty, kind)
+                }
+                (DefKind::Function { .. }, None) => {
+                    let subst = self.cur().subst.clone();
+                    let binding = self.new_fn_binding(def, &subst, top_level);
+                    funcs.push((def, binding));
+                    binding
+                }
+                _ => match self.alias_of(def) {
+                    Some(binding) => binding,
+                    None => {
+                        let binding = self.new_value_binding(def, top_level, letdef.rec);
+                        values.push((def, binding));
+                        binding
+                    }
+                },
+            };
+            bindings.push((&def.id, binding));
+        }
+        if letdef.rec {
+            bindings.iter().for_each(|(id, b)| self.bind(id, *b));
+        }
+        let subst = self.cur().subst.clone();
+        for (def, binding) in &funcs {
+            self.lower_fn_body(def, *binding, subst.clone());
+        }
+        if !top_level {
+            self.make_closures(funcs.iter().map(|(_, binding)| *binding));
+        }
+        for (def, binding) in values {
+            self.init_value_def(def, binding);
+        }
+        if !letdef.rec {
+            bindings.iter().for_each(|(id, b)| self.bind(id, *b));
+        }
+    }
+    /// A generic constant that just names another binding can be used in its place,
+    /// which keeps it generic.
+    fn alias_of(&self, def: &'a Def) -> Option<BindingId> {
+        match &def.kind {
+            DefKind::Const { expr } if self.sem.types.is_generic(def) => match &expr.kind {
+                ExprKind::Call(call) if call.args.is_empty() => Some(self.lookup(&call.id)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    fn new_value_binding(&mut self, def: &'a Def, top_level: bool, rec: bool) -> BindingId {
+        let ty = self.ty_of(def);
+        if top_level {
+            let global = self.new_global(&def.id, ty.clone());
+            return self.new_binding(ty, BindingKind::Global(global));
+        }
+        let binding = self.new_binding(ty.clone(), BindingKind::Local);
+        // Functions of the group may capture it before it is initialized.
+        if rec {
+            let temp = self.new_temp(ty);
+            self.cur_mut().locals.insert(binding, temp);
+        }
+        binding
+    }
+    fn init_value_def(&mut self, def: &'a Def, binding: BindingId) {
+        let ty = self.binding(binding).ty.clone();
+        let value = match &def.kind {
+            DefKind::Const { expr } => self.lower_expr(expr),
+            DefKind::Variable => self.emit_value(ty, |dst| Inst::NewRef { dst }),
+            DefKind::Array { dims } => {
+                let dims = dims.iter().map(|dim| self.lower_expr(dim)).collect();
+                self.emit_value(ty, |dst| Inst::NewArray { dst, dims })
+            }
+            DefKind::Function { .. } => unreachable!("functions are not values"),
+        };
+        match self.binding(binding).kind {
+            BindingKind::Global(global) => self.emit(Inst::SetGlobal { global, value }),
+            _ => match self.cur().locals.get(&binding).copied() {
+                Some(temp) => self.emit_copy(temp, value),
+                None => {
+                    self.cur_mut().locals.insert(binding, value);
+                }
+            },
+        }
+    }
+
+    fn new_fn_binding(&mut self, def: &'a Def, subst: &Subst, top_level: bool) -> BindingId {
+        let DefKind::Function { pars, expr } = &def.kind else {
+            unreachable!("should be a function definition")
+        };
+        let ty = self.ty_in(def, subst);
+        let name = if self.sem.types.is_generic(def) {
+            format!("{}<{}>", def.id, ty)
+        } else {
+            def.id.clone()
+        };
+        let signature = Signature {
+            name: self.unique_name(&name),
+            env: (!top_level).then(|| self.new_env(Vec::new())),
+            params: pars.iter().map(|par| self.ty_in(par, subst)).collect(),
+            ret: self.ty_in(expr, subst),
+        };
+        let direct = self.reserve_function(signature);
+        let kind = BindingKind::Func {
+            direct,
+            arity: pars.len(),
+            top_level,
+        };
+        self.new_binding(ty, kind)
+    }
+    fn lower_fn_body(&mut self, def: &'a Def, binding: BindingId, subst: Subst) {
+        let DefKind::Function { pars, expr } = &def.kind else {
+            unreachable!("should be a function definition")
+        };
+        let BindingKind::Func {
+            direct, top_level, ..
+        } = self.binding(binding).kind
+        else {
+            unreachable!("should be a function binding")
+        };
+        let params = self.begin_function(direct, subst);
+        if !top_level {
+            self.cur_mut().self_binding = Some(binding);
+        }
+        let outer_scope = self.scope.clone();
+        for (par, param) in pars.iter().zip(params) {
+            self.bind_local(&par.id, param);
+        }
+        let result = self.lower_expr(expr);
+        self.scope = outer_scope;
+        self.end_function(result);
+    }
+    /// Allocates the closures of a group of local functions, in the current function.
+    /// They are allocated before any capture is set, so that they can capture each other.
+    pub(super) fn make_closures(&mut self, bindings: impl Iterator<Item = BindingId>) {
+        let closures: Vec<_> = bindings
+            .map(|binding| {
+                let BindingKind::Func { direct, .. } = self.binding(binding).kind else {
+                    unreachable!("should be a function binding")
+                };
+                let code = self.code0(direct);
+                let ty = self.binding(binding).ty.clone();
+                let closure = self.emit_value(ty, |dst| Inst::Closure { dst, code });
+                self.cur_mut().locals.insert(binding, closure);
+                (direct, closure)
+            })
+            .collect();
+        for (direct, closure) in closures {
+            let env = self
+                .signature(direct)
+                .env
+                .expect("local functions have an environment");
+            for (idx, captured) in self.captures[&direct].clone().into_iter().enumerate() {
+                let value = self.local_value(captured);
+                self.emit(Inst::SetCapture {
+                    closure,
+                    env,
+                    idx: idx as u32,
+                    value,
+                });
+            }
+        }
+    }
+
+    /// Returns the function binding standing for the generic binding `id` at its use `site`.
+    pub(super) fn instantiate(&mut self, id: BindingId, site: &'a Expr) -> BindingId {
+        let BindingKind::Generic { group, idx } = self.binding(id).kind.clone() else {
+            unreachable!("should be a generic binding")
+        };
+        let def = &group.letdef.defs[idx];
+        // *Note: The instance is the one sem made for this use, as seen from the instance
+        // of the function the use is in.
+        let mut subst = group.subst.clone();
+        let types = &self.sem.types;
+        if let (Some(scheme), Some(instantiation)) =
+            (types.get_scheme(&def.into()), types.get_instantiation(site))
+        {
+            for (id, ty) in scheme.quantified.iter().zip(instantiation) {
+                subst.insert(*id, mono_type(types, *ty, &self.cur().subst));
+            }
+        }
+        let mut key: Vec<_> = subst.iter().map(|(id, ty)| (*id, ty.clone())).collect();
+        key.sort_by_key(|(id, _)| *id);
+        let key = (group.letdef as *const Letdef, key);
+        let members = match self.instances.get(&key) {
+            Some(members) => members.clone(),
+            None => {
+                let members = self.lower_instance(&group, subst);
+                self.instances.insert(key, members.clone());
+                members
+            }
+        };
+        if !group.top_level {
+            self.make_closures(members.iter().flatten().copied());
+        }
+        members[idx].expect("generic bindings stand for functions")
+    }
+    /// Only the functions of a generic group are instantiated, constants of a
+    /// recursive group are not visible in the instances.
+    fn lower_instance(&mut self, group: &GenericGroup<'a>, subst: Subst) -> Vec<Option<BindingId>> {
+        let use_scope = std::mem::replace(&mut self.scope, group.scope.clone());
+        let members: Vec<_> = group
+            .letdef
+            .defs
+            .iter()
+            .map(|def| match def.kind {
+                DefKind::Function { .. } => Some(self.new_fn_binding(def, &subst, group.top_level)),
+                _ => None,
+            })
+            .collect();
+        if group.letdef.rec {
+            for (def, member) in group.letdef.defs.iter().zip(&members) {
+                if let Some(member) = member {
+                    self.bind(&def.id, *member);
+                }
+            }
+        }
+        for (def, member) in group.letdef.defs.iter().zip(&members) {
+            if let Some(member) = member {
+                self.lower_fn_body(def, *member, subst.clone());
+            }
+        }
+        self.scope = use_scope;
+        members
+    }
+
+    /// The function a closure of `direct` starts with, it takes the first argument.
+    /// Every wrapper but the last collects one more argument into a new closure,
+    /// the last one calls `direct` with all of them.
+    pub(super) fn code0(&mut self, direct: FuncId) -> FuncId {
+        if let Some(code) = self.curried.get(&direct) {
+            return *code;
+        }
+        let Signature {
+            name,
+            env,
+            params,
+            ret,
+        } = self.signature(direct).clone();
+        if env.is_some() && params.len() == 1 {
+            self.curried.insert(direct, direct);
+            return direct;
+        }
+        let env_ty = env.map(Ty::Env);
+        let codes: Vec<(FuncId, _)> = (0..params.len())
+            .map(|k| {
+                let code_env = match k {
+                    0 => env.unwrap_or(self.empty_env),
+                    _ => self.new_env(env_ty.iter().chain(&params[..k]).cloned().collect()),
+                };
+                let signature = Signature {
+                    name: format!("{}$curry{}", name, k),
+                    env: Some(code_env),
+                    params: vec![params[k].clone()],
+                    ret: Ty::new_multi_arg_func(params[k + 1..].to_vec(), ret.clone()),
+                };
+                (self.reserve_function(signature), code_env)
+            })
+            .collect();
+        self.curried.insert(direct, codes[0].0);
+        for (k, (code, code_env)) in codes.iter().enumerate() {
+            let arg = self.begin_function(*code, Subst::new())[0];
+            let mut collected = match (k, &env_ty) {
+                (0, None) => Vec::new(),
+                (0, Some(env_ty)) => {
+                    vec![self.emit_value(env_ty.clone(), |dst| Inst::SelfClosure { dst })]
+                }
+                _ => self.envs[code_env.0 as usize]
+                    .captures
+                    .clone()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, ty)| {
+                        self.emit_value(ty, |dst| Inst::Capture {
+                            dst,
+                            idx: idx as u32,
+                        })
+                    })
+                    .collect(),
+            };
+            collected.push(arg);
+            let ret = self.signature(*code).ret.clone();
+            let result = match codes.get(k + 1) {
+                None => self.emit_value(ret, |dst| Inst::Call {
+                    dst,
+                    func: direct,
+                    args: collected,
+                }),
+                Some((next, next_env)) => {
+                    let closure = self.emit_value(ret, |dst| Inst::Closure { dst, code: *next });
+                    for (idx, value) in collected.into_iter().enumerate() {
+                        self.emit(Inst::SetCapture {
+                            closure,
+                            env: *next_env,
+                            idx: idx as u32,
+                            value,
+                        });
+                    }
+                    closure
+                }
+            };
+            self.end_function(result);
+        }
+        codes[0].0
+    }
+    /// A function building a value of the constructor `id`, for its uses as a value.
+    pub(super) fn constr_fn(&mut self, id: BindingId) -> FuncId {
+        if let Some(func) = self.constr_fns.get(&id) {
+            return *func;
+        }
+        let binding = self.binding(id).clone();
+        let BindingKind::Constr { tag, arity } = binding.kind else {
+            unreachable!("should be a constructor binding")
+        };
+        let mut params = Vec::with_capacity(arity);
+        let mut ty = &binding.ty;
+        while let Ty::Func(lhs, rhs) = ty {
+            params.push((**lhs).clone());
+            ty = rhs;
+        }
+        let Ty::Custom(custom) = ty else {
+            unreachable!("constructors build custom types")
+        };
+        let constr_name = self.custom(custom).constrs[tag as usize].name.clone();
+        let signature = Signature {
+            name: self.unique_name(&constr_name),
+            env: None,
+            params,
+            ret: ty.clone(),
+        };
+        let func = self.reserve_function(signature);
+        self.constr_fns.insert(id, func);
+        let args = self.begin_function(func, Subst::new());
+        let value = self.emit_value(ty.clone(), |dst| Inst::NewConstr { dst, tag, args });
+        self.end_function(value);
+        func
+    }
+    /// A function calling the builtin, for when it is used as a value of type `ty`.
+    pub(super) fn builtin_fn(&mut self, builtin: &'static Builtin, ty: &Ty) -> FuncId {
+        if let Some(func) = self.builtin_fns.get(&(builtin.id, ty.clone())) {
+            return *func;
+        }
+        let arity = builtin.arity();
+        let mut params = Vec::with_capacity(arity);
+        let mut ret = ty;
+        for _ in 0..arity {
+            let Ty::Func(lhs, rhs) = ret else {
+                unreachable!("builtins are used at their function types")
+            };
+            params.push((**lhs).clone());
+            ret = rhs;
+        }
+        let signature = Signature {
+            name: self.unique_name(builtin.id),
+            env: None,
+            params,
+            ret: ret.clone(),
+        };
+        let func = self.reserve_function(signature);
+        self.builtin_fns.insert((builtin.id, ty.clone()), func);
+        let args = self.begin_function(func, Subst::new());
+        let value = self.emit_value(ret.clone(), |dst| Inst::CallRuntime {
+            dst,
+            func: Runtime::Builtin(builtin),
+            args,
+        });
+        self.end_function(value);
+        func
+    }
+}
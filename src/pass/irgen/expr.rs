@@ -0,0 +1,503 @@
+use crate::parse::ast::expr::{
+    ArrayAccess, Binop, BinopKind, Call, Expr, ExprKind, For, If, Match, Unop, UnopKind, While,
+};
+
+use super::{
+    ir::{BinOp, CmpOp, Const, FuncId, Inst, Runtime, StrId, Temp, Terminator, Trap, Ty, UnOp},
+    mono::Subst,
+    BindingId, BindingKind, IrGen, Signature,
+};
+
+impl<'a, 's> IrGen<'a, 's> {
+    pub(super) fn lower_expr(&mut self, expr: &'a Expr) -> Temp {
+        use ExprKind::*;
+        match &expr.kind {
+            UnitLiteral => self.emit_const(Const::Unit),
+            IntLiteral(i) => self.emit_const(Const::Int(*i as i64)),
+            FloatLiteral(x) => self.emit_const(Const::Float(*x)),
+            CharLiteral(c) => self.emit_const(Const::Char(*c)),
+            StringLiteral(s) => self.lower_string(s),
+            BoolLiteral(b) => self.emit_const(Const::Bool(*b)),
+            Tuple(exprs) => {
+                let elems = exprs.iter().map(|e| self.lower_expr(e)).collect();
+                let ty = self.ty_of(expr);
+                self.emit_value(ty, |dst| Inst::NewTuple { dst, elems })
+            }
+            Unop(unop) => self.lower_unop(unop, expr),
+            Binop(binop) => self.lower_binop(binop),
+            Call(call) | ConstrCall(call) => self.lower_call(call, expr),
+            ArrayAccess(access) => self.lower_array_access(access, expr),
+            Dim(dim) => {
+                let array = self.lookup(&dim.id);
+                let array = self.value_of_binding(array);
+                let dim = dim.dim as u32;
+                self.emit_value(Ty::Int, |dst| Inst::ArrayDim { dst, array, dim })
+            }
+            New(_) => {
+                let ty = self.ty_of(expr);
+                self.emit_value(ty, |dst| Inst::NewRef { dst })
+            }
+            LetIn(let_in) => {
+                let outer_scope = self.scope.clone();
+                self.lower_letdef(&let_in.letdef, false);
+                let value = self.lower_expr(&let_in.expr);
+                self.scope = outer_scope;
+                value
+            }
+            If(if_expr) => self.lower_if(if_expr, expr),
+            While(while_expr) => self.lower_while(while_expr),
+            For(for_expr) => self.lower_for(for_expr),
+            Match(match_expr) => self.lower_match(match_expr, expr),
+            Error => unreachable!("programs with syntax errors are not lowered"),
+        }
+    }
+    pub(super) fn lower_string(&mut self, s: &str) -> Temp {
+        self.strings.push(s.as_bytes().to_vec());
+        let string = StrId(self.strings.len() as u32 - 1);
+        self.emit_value(Ty::Array(Box::new(Ty::Char), 1), |dst| Inst::String {
+            dst,
+            string,
+        })
+    }
+    fn lower_unop(&mut self, unop: &'a Unop, expr: &'a Expr) -> Temp {
+        let src = self.lower_expr(&unop.operand);
+        let ty = self.ty_of(expr);
+        let op = match unop.op {
+            UnopKind::Plus | UnopKind::PlusFlt => return src,
+            UnopKind::Minus if ty.is_float() => UnOp::FNeg,
+            UnopKind::MinusFlt => UnOp::FNeg,
+            UnopKind::Minus => UnOp::INeg,
+            UnopKind::Not => UnOp::Not,
+            UnopKind::Deref => return self.emit_value(ty, |dst| Inst::Load { dst, ptr: src }),
+            UnopKind::Delete => {
+                self.emit(Inst::Delete { ptr: src });
+                return self.emit_const(Const::Unit);
+            }
+        };
+        self.emit_value(ty, |dst| Inst::Unop { dst, op, src })
+    }
+    fn lower_binop(&mut self, binop: &'a Binop) -> Temp {
+        use BinopKind::*;
+        match binop.op {
+            And | Or => return self.lower_short_circuit(binop),
+            Semicolon => {
+                self.lower_expr(&binop.lhs);
+                return self.lower_expr(&binop.rhs);
+            }
+            _ => (),
+        }
+        let lhs = self.lower_expr(&binop.lhs);
+        let rhs = self.lower_expr(&binop.rhs);
+        let operand_ty = self.temp_ty(lhs).clone();
+        let float = operand_ty.is_float();
+        let cmp = |op| match float {
+            true => BinOp::FCmp(op),
+            false => BinOp::ICmp(op),
+        };
+        let op = match binop.op {
+            Add if float => BinOp::FAdd,
+            Add => BinOp::IAdd,
+            Sub if float => BinOp::FSub,
+            Sub => BinOp::ISub,
+            Mul if float => BinOp::FMul,
+            Mul => BinOp::IMul,
+            Div if float => BinOp::FDiv,
+            Div => BinOp::IDiv,
+            AddFlt => BinOp::FAdd,
+            SubFlt => BinOp::FSub,
+            MulFlt => BinOp::FMul,
+            DivFlt => BinOp::FDiv,
+            Mod => BinOp::IMod,
+            Pow if float => BinOp::FPow,
+            Pow => BinOp::IPow,
+            StrEq => return self.emit_eq(lhs, rhs, &operand_ty),
+            StrNotEq => {
+                let src = self.emit_eq(lhs, rhs, &operand_ty);
+                return self.emit_value(Ty::Bool, |dst| Inst::Unop {
+                    dst,
+                    op: UnOp::Not,
+                    src,
+                });
+            }
+            NatEq => cmp(CmpOp::Eq),
+            NatNotEq => cmp(CmpOp::Ne),
+            Lt => cmp(CmpOp::Lt),
+            Gt => cmp(CmpOp::Gt),
+            LEq => cmp(CmpOp::Le),
+            GEq => cmp(CmpOp::Ge),
+            Assign => {
+                self.emit(Inst::Store {
+                    ptr: lhs,
+                    value: rhs,
+                });
+                return self.emit_const(Const::Unit);
+            }
+            And | Or | Semicolon => unreachable!("handled above"),
+        };
+        let ty = match op {
+            BinOp::ICmp(_) | BinOp::FCmp(_) => Ty::Bool,
+            _ => operand_ty,
+        };
+        self.emit_value(ty, |dst| Inst::Binop { dst, op, lhs, rhs })
+    }
+    fn lower_short_circuit(&mut self, binop: &'a Binop) -> Temp {
+        let result = self.new_temp(Ty::Bool);
+        let lhs = self.lower_expr(&binop.lhs);
+        self.emit_copy(result, lhs);
+        let (rhs_block, join) = (self.new_block(), self.new_block());
+        let (then, else_) = match binop.op {
+            BinopKind::And => (rhs_block, join),
+            _ => (join, rhs_block),
+        };
+        self.terminate(Terminator::Branch {
+            cond: lhs,
+            then,
+            else_,
+        });
+        self.switch_to(rhs_block);
+        let rhs = self.lower_expr(&binop.rhs);
+        self.emit_copy(result, rhs);
+        self.terminate(Terminator::Jump(join));
+        self.switch_to(join);
+        result
+    }
+
+    fn lower_call(&mut self, call: &'a Call, expr: &'a Expr) -> Temp {
+        let ret = self.ty_of(expr);
+        let args: Vec<_> = call.args.iter().map(|arg| self.lower_expr(arg)).collect();
+        let arg_tys = args.iter().map(|arg| self.temp_ty(*arg).clone()).collect();
+        let callee_ty = Ty::new_multi_arg_func(arg_tys, ret);
+        let mut callee = self.lookup(&call.id);
+        if let BindingKind::Generic { .. } = self.binding(callee).kind {
+            callee = self.instantiate(callee, expr);
+        }
+        match self.binding(callee).kind {
+            BindingKind::Func {
+                direct,
+                arity,
+                top_level,
+            } if args.len() >= arity => {
+                let mut direct_args = Vec::with_capacity(arity + 1);
+                if !top_level {
+                    direct_args.push(self.local_value(callee));
+                }
+                direct_args.extend(&args[..arity]);
+                let ty = callee_ty.apply(arity).clone();
+                let result = self.emit_value(ty, |dst| Inst::Call {
+                    dst,
+                    func: direct,
+                    args: direct_args,
+                });
+                self.apply_closure(result, callee_ty.apply(arity), &args[arity..])
+            }
+            BindingKind::Constr { tag, arity } if args.len() == arity => {
+                let ty = callee_ty.apply(arity).clone();
+                self.emit_value(ty, |dst| Inst::NewConstr { dst, tag, args })
+            }
+            BindingKind::Builtin(builtin) if args.len() >= builtin.arity() => {
+                let arity = builtin.arity();
+                let ty = callee_ty.apply(arity).clone();
+                let result = self.emit_value(ty, |dst| Inst::CallRuntime {
+                    dst,
+                    func: Runtime::Builtin(builtin),
+                    args: args[..arity].to_vec(),
+                });
+                self.apply_closure(result, callee_ty.apply(arity), &args[arity..])
+            }
+            _ => {
+                let closure = self.value_of(callee, &callee_ty);
+                self.apply_closure(closure, &callee_ty, &args)
+            }
+        }
+    }
+    fn apply_closure(&mut self, mut closure: Temp, ty: &Ty, args: &[Temp]) -> Temp {
+        let mut ty = ty;
+        for arg in args {
+            ty = ty.apply(1);
+            let arg = *arg;
+            closure = self.emit_value(ty.clone(), |dst| Inst::CallClosure { dst, closure, arg });
+        }
+        closure
+    }
+    /// The value of the binding `id`, where it is used as a value of type `use_ty`.
+    pub(super) fn value_of(&mut self, id: BindingId, use_ty: &Ty) -> Temp {
+        let ty = self.binding(id).ty.clone();
+        match self.binding(id).kind {
+            BindingKind::Local
+            | BindingKind::Func {
+                top_level: false, ..
+            } => self.local_value(id),
+            BindingKind::Global(global) => {
+                self.emit_value(ty, |dst| Inst::GetGlobal { dst, global })
+            }
+            BindingKind::Func { direct, .. } => {
+                let code = self.code0(direct);
+                self.emit_value(ty, |dst| Inst::Closure { dst, code })
+            }
+            BindingKind::Generic { .. } => {
+                unreachable!("generic bindings are instantiated where they are called")
+            }
+            BindingKind::Constr { tag, arity: 0 } => self.emit_value(ty, |dst| Inst::NewConstr {
+                dst,
+                tag,
+                args: Vec::new(),
+            }),
+            BindingKind::Constr { .. } => {
+                let func = self.constr_fn(id);
+                let code = self.code0(func);
+                self.emit_value(ty, |dst| Inst::Closure { dst, code })
+            }
+            BindingKind::Builtin(builtin) => {
+                let func = self.builtin_fn(builtin, use_ty);
+                let code = self.code0(func);
+                self.emit_value(use_ty.clone(), |dst| Inst::Closure { dst, code })
+            }
+        }
+    }
+    /// For bindings whose type does not depend on the use.
+    fn value_of_binding(&mut self, id: BindingId) -> Temp {
+        let ty = self.binding(id).ty.clone();
+        self.value_of(id, &ty)
+    }
+    /// The temp holding a local binding in the current function, capturing it if needed.
+    pub(super) fn local_value(&mut self, id: BindingId) -> Temp {
+        if let Some(temp) = self.cur().locals.get(&id) {
+            return *temp;
+        }
+        let ty = self.binding(id).ty.clone();
+        let builder = self.cur_mut();
+        assert!(
+            builder.env.is_some(),
+            "functions without an environment cannot capture"
+        );
+        let dst = builder.new_temp(ty);
+        if builder.self_binding == Some(id) {
+            builder.emit_prelude(Inst::SelfClosure { dst });
+        } else {
+            let idx = builder.captures.len() as u32;
+            builder.captures.push(id);
+            builder.emit_prelude(Inst::Capture { dst, idx });
+        }
+        builder.locals.insert(id, dst);
+        dst
+    }
+
+    fn lower_array_access(&mut self, access: &'a ArrayAccess, expr: &'a Expr) -> Temp {
+        let array = self.lookup(&access.id);
+        let array = self.value_of_binding(array);
+        let indexes = access
+            .indexes
+            .iter()
+            .map(|index| self.lower_expr(index))
+            .collect();
+        let ty = self.ty_of(expr);
+        self.emit_value(ty, |dst| Inst::ArrayElem {
+            dst,
+            array,
+            indexes,
+        })
+    }
+    fn lower_if(&mut self, if_expr: &'a If, expr: &'a Expr) -> Temp {
+        let ty = self.ty_of(expr);
+        let result = self.new_temp(ty);
+        let cond = self.lower_expr(&if_expr.cond);
+        let (then, else_, join) = (self.new_block(), self.new_block(), self.new_block());
+        self.terminate(Terminator::Branch { cond, then, else_ });
+        self.switch_to(then);
+        let value = self.lower_expr(&if_expr.then_body);
+        self.emit_copy(result, value);
+        self.terminate(Terminator::Jump(join));
+        self.switch_to(else_);
+        let value = match &if_expr.else_body {
+            Some(else_body) => self.lower_expr(else_body),
+            None => self.emit_const(Const::Unit),
+        };
+        self.emit_copy(result, value);
+        self.terminate(Terminator::Jump(join));
+        self.switch_to(join);
+        result
+    }
+    fn lower_while(&mut self, while_expr: &'a While) -> Temp {
+        let (cond_block, body, exit) = (self.new_block(), self.new_block(), self.new_block());
+        self.terminate(Terminator::Jump(cond_block));
+        self.switch_to(cond_block);
+        let cond = self.lower_expr(&while_expr.cond);
+        self.terminate(Terminator::Branch {
+            cond,
+            then: body,
+            else_: exit,
+        });
+        self.switch_to(body);
+        self.lower_expr(&while_expr.body);
+        self.terminate(Terminator::Jump(cond_block));
+        self.switch_to(exit);
+        self.emit_const(Const::Unit)
+    }
+    fn lower_for(&mut self, for_expr: &'a For) -> Temp {
+        let from = self.lower_expr(&for_expr.from);
+        let to = self.lower_expr(&for_expr.to);
+        let counter = self.new_temp(Ty::Int);
+        self.emit_copy(counter, from);
+        let (cond_block, body, exit) = (self.new_block(), self.new_block(), self.new_block());
+        self.terminate(Terminator::Jump(cond_block));
+        self.switch_to(cond_block);
+        let (cmp, step) = match for_expr.ascending {
+            true => (CmpOp::Le, BinOp::IAdd),
+            false => (CmpOp::Ge, BinOp::ISub),
+        };
+        let cond = self.emit_value(Ty::Bool, |dst| Inst::Binop {
+            dst,
+            op: BinOp::ICmp(cmp),
+            lhs: counter,
+            rhs: to,
+        });
+        self.terminate(Terminator::Branch {
+            cond,
+            then: body,
+            else_: exit,
+        });
+        self.switch_to(body);
+        let outer_scope = self.scope.clone();
+        self.bind_local(&for_expr.id, counter);
+        self.lower_expr(&for_expr.body);
+        self.scope = outer_scope;
+        let one = self.emit_const(Const::Int(1));
+        self.emit(Inst::Binop {
+            dst: counter,
+            op: step,
+            lhs: counter,
+            rhs: one,
+        });
+        self.terminate(Terminator::Jump(cond_block));
+        self.switch_to(exit);
+        self.emit_const(Const::Unit)
+    }
+    fn lower_match(&mut self, match_expr: &'a Match, expr: &'a Expr) -> Temp {
+        let ty = self.ty_of(expr);
+        let result = self.new_temp(ty);
+        let to_match = self.lower_expr(&match_expr.to_match);
+        let join = self.new_block();
+        for clause in &match_expr.clauses {
+            let next_clause = self.new_block();
+            let outer_scope = self.scope.clone();
+            self.lower_pattern(&clause.pattern, to_match, next_clause);
+            let value = self.lower_expr(&clause.expr);
+            self.emit_copy(result, value);
+            self.terminate(Terminator::Jump(join));
+            self.scope = outer_scope;
+            self.switch_to(next_clause);
+        }
+        self.terminate(Terminator::Trap(Trap::MatchFailure));
+        self.switch_to(join);
+        result
+    }
+
+    /// Structural equality, physical for functions and arrays.
+    pub(super) fn emit_eq(&mut self, lhs: Temp, rhs: Temp, ty: &Ty) -> Temp {
+        let op = match ty {
+            Ty::Tuple(_) | Ty::Custom(_) | Ty::Ref(_) => {
+                let func = self.eq_fn(ty);
+                return self.emit_value(Ty::Bool, |dst| Inst::Call {
+                    dst,
+                    func,
+                    args: vec![lhs, rhs],
+                });
+            }
+            Ty::Float => BinOp::FCmp(CmpOp::Eq),
+            _ => BinOp::ICmp(CmpOp::Eq),
+        };
+        self.emit_value(Ty::Bool, |dst| Inst::Binop { dst, op, lhs, rhs })
+    }
+    fn eq_fn(&mut self, ty: &Ty) -> FuncId {
+        if let Some(func) = self.eq_fns.get(ty) {
+            return *func;
+        }
+        let signature = Signature {
+            name: format!("eq<{}>", ty),
+            env: None,
+            params: vec![ty.clone(), ty.clone()],
+            ret: Ty::Bool,
+        };
+        let func = self.reserve_function(signature);
+        self.eq_fns.insert(ty.clone(), func);
+        let params = self.begin_function(func, Subst::new());
+        let (lhs, rhs) = (params[0], params[1]);
+        let (unequal, join) = (self.new_block(), self.new_block());
+        match ty {
+            Ty::Ref(inner) => {
+                let lhs = self.emit_value((**inner).clone(), |dst| Inst::Load { dst, ptr: lhs });
+                let rhs = self.emit_value((**inner).clone(), |dst| Inst::Load { dst, ptr: rhs });
+                let equal = self.emit_eq(lhs, rhs, inner);
+                self.emit_check(equal, unequal);
+            }
+            Ty::Tuple(tys) => {
+                for (idx, ty) in tys.iter().enumerate() {
+                    let idx = idx as u32;
+                    let lhs =
+                        self.emit_value(ty.clone(), |dst| Inst::TupleGet { dst, src: lhs, idx });
+                    let rhs =
+                        self.emit_value(ty.clone(), |dst| Inst::TupleGet { dst, src: rhs, idx });
+                    let equal = self.emit_eq(lhs, rhs, ty);
+                    self.emit_check(equal, unequal);
+                }
+            }
+            Ty::Custom(name) => {
+                let lhs_tag = self.emit_value(Ty::Int, |dst| Inst::Tag { dst, src: lhs });
+                let rhs_tag = self.emit_value(Ty::Int, |dst| Inst::Tag { dst, src: rhs });
+                let equal = self.emit_value(Ty::Bool, |dst| Inst::Binop {
+                    dst,
+                    op: BinOp::ICmp(CmpOp::Eq),
+                    lhs: lhs_tag,
+                    rhs: rhs_tag,
+                });
+                self.emit_check(equal, unequal);
+                let constrs = self.custom(name).constrs.clone();
+                let fields_equal = self.new_block();
+                for (tag, constr) in constrs.iter().enumerate() {
+                    if constr.fields.is_empty() {
+                        continue;
+                    }
+                    let tag = tag as u32;
+                    let expected = self.emit_const(Const::Int(tag as i64));
+                    let is_tag = self.emit_value(Ty::Bool, |dst| Inst::Binop {
+                        dst,
+                        op: BinOp::ICmp(CmpOp::Eq),
+                        lhs: lhs_tag,
+                        rhs: expected,
+                    });
+                    let (this, next) = (self.new_block(), self.new_block());
+                    self.terminate(Terminator::Branch {
+                        cond: is_tag,
+                        then: this,
+                        else_: next,
+                    });
+                    self.switch_to(this);
+                    for (idx, ty) in constr.fields.iter().enumerate() {
+                        let idx = idx as u32;
+                        let get = |src| move |dst| Inst::ConstrGet { dst, src, tag, idx };
+                        let lhs = self.emit_value(ty.clone(), get(lhs));
+                        let rhs = self.emit_value(ty.clone(), get(rhs));
+                        let equal = self.emit_eq(lhs, rhs, ty);
+                        self.emit_check(equal, unequal);
+                    }
+                    self.terminate(Terminator::Jump(fields_equal));
+                    self.switch_to(next);
+                }
+                self.terminate(Terminator::Jump(fields_equal));
+                self.switch_to(fields_equal);
+            }
+            _ => unreachable!("only compound types have equality functions"),
+        }
+        let result = self.new_temp(Ty::Bool);
+        let equal = self.emit_const(Const::Bool(true));
+        self.emit_copy(result, equal);
+        self.terminate(Terminator::Jump(join));
+        self.switch_to(unequal);
+        let not_equal = self.emit_const(Const::Bool(false));
+        self.emit_copy(result, not_equal);
+        self.terminate(Terminator::Jump(join));
+        self.switch_to(join);
+        self.end_function(result);
+        func
+    }
+}
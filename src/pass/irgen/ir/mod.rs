@@ -0,0 +1,407 @@
+pub mod print;
+
+use crate::pass::sem::builtins::Builtin;
+
+// ! Implementation notes:
+// !   Every value is one machine word. Scalars are stored unboxed (floats as their bits),
+// !   everything else is a pointer to a heap record whose layout is given by its `Ty`.
+// !   Temps are plain variables (not SSA), so join points simply assign the same temp.
+// !   Generic definitions are monomorphized during lowering, every `Ty` is ground.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Temp(pub u32);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FuncId(pub u32);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GlobalId(pub u32);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StrId(pub u32);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EnvId(pub u32);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Unit,
+    Bool,
+    Char,
+    Int,
+    Float,
+    /// A closure of a (curried) function.
+    Func(Box<Ty>, Box<Ty>),
+    /// A pointer to a single heap cell.
+    Ref(Box<Ty>),
+    Array(Box<Ty>, u32),
+    Tuple(Vec<Ty>),
+    /// A user defined type, see [`Module::customs`].
+    Custom(String),
+    /// The closure record a function was entered through, with the given capture layout.
+    Env(EnvId),
+}
+impl Ty {
+    pub fn new_func(lhs: Ty, rhs: Ty) -> Self {
+        Ty::Func(Box::new(lhs), Box::new(rhs))
+    }
+    pub fn new_multi_arg_func(args: Vec<Ty>, ret: Ty) -> Self {
+        args.into_iter()
+            .rfold(ret, |acc, arg| Ty::new_func(arg, acc))
+    }
+    pub fn new_ref(inner: Ty) -> Self {
+        Ty::Ref(Box::new(inner))
+    }
+    /// Returns the type of the result after applying `cnt` arguments.
+    pub fn apply(&self, cnt: usize) -> &Ty {
+        (0..cnt).fold(self, |ty, _| match ty {
+            Ty::Func(_, rhs) => rhs,
+            _ => panic!("applied argument to non-function type {}", self),
+        })
+    }
+    pub fn is_float(&self) -> bool {
+        matches!(self, Ty::Float)
+    }
+    /// Scalars are compared by value, everything else is a pointer.
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, Ty::Unit | Ty::Bool | Ty::Char | Ty::Int | Ty::Float)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Const {
+    Unit,
+    Bool(bool),
+    Char(u8),
+    Int(i64),
+    Float(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    INeg,
+    FNeg,
+    Not,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    IAdd,
+    ISub,
+    IMul,
+    /// Traps on a zero divisor.
+    IDiv,
+    /// Traps on a zero divisor.
+    IMod,
+    IPow,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FPow,
+    /// Word comparison, used for every non-float scalar and for physical equality of pointers.
+    ICmp(CmpOp),
+    FCmp(CmpOp),
+}
+
+/// Functions provided by the runtime library that is linked with every program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Runtime {
+    /// Compares the contents of two character arrays.
+    StrEq,
+    /// A function of the standard library.
+    Builtin(&'static Builtin),
+}
+impl Runtime {
+    pub fn symbol(&self) -> String {
+        match self {
+            Runtime::StrEq => "llama_str_eq".to_string(),
+            Runtime::Builtin(builtin) => format!("llama_{}", builtin.id),
+        }
+    }
+}
+
+/// Runtime errors, either explicit (`Terminator::Trap`) or raised by the semantics of an `Inst`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+    MatchFailure,
+    DivisionByZero,
+    IndexOutOfBounds,
+    InvalidArraySize,
+}
+impl Trap {
+    /// Trap codes shared with the runtime library.
+    pub fn code(&self) -> i64 {
+        match self {
+            Trap::MatchFailure => 1,
+            Trap::DivisionByZero => 2,
+            Trap::IndexOutOfBounds => 3,
+            Trap::InvalidArraySize => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Inst {
+    Const {
+        dst: Temp,
+        value: Const,
+    },
+    Copy {
+        dst: Temp,
+        src: Temp,
+    },
+    Unop {
+        dst: Temp,
+        op: UnOp,
+        src: Temp,
+    },
+    Binop {
+        dst: Temp,
+        op: BinOp,
+        lhs: Temp,
+        rhs: Temp,
+    },
+    /// Allocates a fresh character array holding the literal (and its terminating '\0').
+    String {
+        dst: Temp,
+        string: StrId,
+    },
+
+    /// Allocates a zero-initialized heap cell.
+    NewRef {
+        dst: Temp,
+    },
+    Load {
+        dst: Temp,
+        ptr: Temp,
+    },
+    Store {
+        ptr: Temp,
+        value: Temp,
+    },
+    Delete {
+        ptr: Temp,
+    },
+
+    /// Allocates a zero-initialized array. Traps if any dimension is not positive.
+    NewArray {
+        dst: Temp,
+        dims: Vec<Temp>,
+    },
+    /// Address of an array element. Traps if any index is out of bounds.
+    ArrayElem {
+        dst: Temp,
+        array: Temp,
+        indexes: Vec<Temp>,
+    },
+    /// Size of the (1-based) `dim`-th dimension.
+    ArrayDim {
+        dst: Temp,
+        array: Temp,
+        dim: u32,
+    },
+
+    NewTuple {
+        dst: Temp,
+        elems: Vec<Temp>,
+    },
+    TupleGet {
+        dst: Temp,
+        src: Temp,
+        idx: u32,
+    },
+    /// The custom type is the type of `dst`.
+    NewConstr {
+        dst: Temp,
+        tag: u32,
+        args: Vec<Temp>,
+    },
+    Tag {
+        dst: Temp,
+        src: Temp,
+    },
+    /// The custom type is the type of `src`, which must have been built with `tag`.
+    ConstrGet {
+        dst: Temp,
+        src: Temp,
+        tag: u32,
+        idx: u32,
+    },
+
+    /// Allocates a closure record for `code`, with uninitialized captures.
+    Closure {
+        dst: Temp,
+        code: FuncId,
+    },
+    SetCapture {
+        closure: Temp,
+        env: EnvId,
+        idx: u32,
+        value: Temp,
+    },
+    /// Reads a capture of the closure record the current function was entered through.
+    Capture {
+        dst: Temp,
+        idx: u32,
+    },
+    /// The closure record the current function was entered through.
+    SelfClosure {
+        dst: Temp,
+    },
+
+    GetGlobal {
+        dst: Temp,
+        global: GlobalId,
+    },
+    SetGlobal {
+        global: GlobalId,
+        value: Temp,
+    },
+
+    /// If `func` has an environment, `args[0]` is the closure record to enter it through.
+    Call {
+        dst: Temp,
+        func: FuncId,
+        args: Vec<Temp>,
+    },
+    CallClosure {
+        dst: Temp,
+        closure: Temp,
+        arg: Temp,
+    },
+    CallRuntime {
+        dst: Temp,
+        func: Runtime,
+        args: Vec<Temp>,
+    },
+}
+impl Inst {
+    pub fn dst(&self) -> Option<Temp> {
+        use Inst::*;
+        match self {
+            Const { dst, .. }
+            | Copy { dst, .. }
+            | Unop { dst, .. }
+            | Binop { dst, .. }
+            | String { dst, .. }
+            | NewRef { dst }
+            | Load { dst, .. }
+            | NewArray { dst, .. }
+            | ArrayElem { dst, .. }
+            | ArrayDim { dst, .. }
+            | NewTuple { dst, .. }
+            | TupleGet { dst, .. }
+            | NewConstr { dst, .. }
+            | Tag { dst, .. }
+            | ConstrGet { dst, .. }
+            | Closure { dst, .. }
+            | Capture { dst, .. }
+            | SelfClosure { dst }
+            | GetGlobal { dst, .. }
+            | Call { dst, .. }
+            | CallClosure { dst, .. }
+            | CallRuntime { dst, .. } => Some(*dst),
+            Store { .. } | Delete { .. } | SetCapture { .. } | SetGlobal { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch {
+        cond: Temp,
+        then: BlockId,
+        else_: BlockId,
+    },
+    Return(Temp),
+    Trap(Trap),
+    /// Only found in blocks that no control flow reaches.
+    Unreachable,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub term: Terminator,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    /// Functions with an environment take the closure record they were entered through
+    /// as an implicit first argument.
+    pub env: Option<EnvId>,
+    pub params: Vec<Temp>,
+    pub ret: Ty,
+    /// The type of every temp, indexed by the temp's number.
+    pub temps: Vec<Ty>,
+    /// The first block is the entry block.
+    pub blocks: Vec<Block>,
+}
+impl Function {
+    pub fn temp_ty(&self, temp: Temp) -> &Ty {
+        &self.temps[temp.0 as usize]
+    }
+}
+
+/// The layout of a closure record: a code pointer followed by the captured values.
+#[derive(Debug, Clone)]
+pub struct Env {
+    pub captures: Vec<Ty>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+    pub ty: Ty,
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomType {
+    pub name: String,
+    pub constrs: Vec<Constructor>,
+}
+#[derive(Debug, Clone)]
+pub struct Constructor {
+    pub name: String,
+    pub fields: Vec<Ty>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub functions: Vec<Function>,
+    pub envs: Vec<Env>,
+    pub globals: Vec<Global>,
+    pub strings: Vec<Vec<u8>>,
+    pub customs: Vec<CustomType>,
+    /// Evaluates the top level definitions in order.
+    pub entry: FuncId,
+}
+impl Module {
+    pub fn function(&self, id: FuncId) -> &Function {
+        &self.functions[id.0 as usize]
+    }
+    pub fn env(&self, id: EnvId) -> &Env {
+        &self.envs[id.0 as usize]
+    }
+    pub fn global(&self, id: GlobalId) -> &Global {
+        &self.globals[id.0 as usize]
+    }
+    pub fn string(&self, id: StrId) -> &[u8] {
+        &self.strings[id.0 as usize]
+    }
+    pub fn custom(&self, name: &str) -> &CustomType {
+        self.customs
+            .iter()
+            .find(|c| c.name == name)
+            .expect("custom type should have been declared")
+    }
+}
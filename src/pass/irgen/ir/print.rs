@@ -0,0 +1,268 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+impl Module {
+    pub fn print(&self, mut w: impl std::io::Write) -> std::io::Result<()> {
+        write!(w, "{}", self)
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for custom in &self.customs {
+            writeln!(f, "{}", custom)?;
+        }
+        // *Note: Empty environments are left out, an environment that is not declared
+        // holds no captures.
+        for (i, env) in self.envs.iter().enumerate() {
+            if env.captures.is_empty() {
+                continue;
+            }
+            writeln!(
+                f,
+                "env {} {{ {} }}",
+                EnvId(i as u32),
+                comma_separated(&env.captures)
+            )?;
+        }
+        for (i, global) in self.globals.iter().enumerate() {
+            writeln!(
+                f,
+                "global {} {}: {}",
+                GlobalId(i as u32),
+                global.name,
+                global.ty
+            )?;
+        }
+        for (i, string) in self.strings.iter().enumerate() {
+            writeln!(
+                f,
+                "string {} {:?}",
+                StrId(i as u32),
+                String::from_utf8_lossy(string)
+            )?;
+        }
+        for (i, func) in self.functions.iter().enumerate() {
+            writeln!(f)?;
+            if FuncId(i as u32) == self.entry {
+                writeln!(f, "; entry point")?;
+            }
+            write!(f, "{}", FunctionPrinter(self, func))?;
+        }
+        Ok(())
+    }
+}
+
+struct FunctionPrinter<'m>(&'m Module, &'m Function);
+impl Display for FunctionPrinter<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let FunctionPrinter(module, func) = self;
+        let mut params: Vec<String> = func.env.iter().map(|env| format!("env {}", env)).collect();
+        params.extend(
+            func.params
+                .iter()
+                .map(|p| format!("{}: {}", p, func.temp_ty(*p))),
+        );
+        writeln!(
+            f,
+            "fn {}({}) -> {} {{",
+            func.name,
+            params.join(", "),
+            func.ret
+        )?;
+        for (i, block) in func.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(i as u32))?;
+            for inst in &block.insts {
+                write!(f, "    ")?;
+                if let Some(dst) = inst.dst() {
+                    write!(f, "{}: {} = ", dst, func.temp_ty(dst))?;
+                }
+                writeln!(f, "{}", InstPrinter(module, inst))?;
+            }
+            writeln!(f, "    {}", block.term)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+struct InstPrinter<'m>(&'m Module, &'m Inst);
+impl Display for InstPrinter<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let InstPrinter(module, inst) = self;
+        let func_name = |id: &FuncId| &module.function(*id).name;
+        use Inst::*;
+        match inst {
+            Const { value, .. } => write!(f, "const {}", value),
+            Copy { src, .. } => write!(f, "copy {}", src),
+            Unop { op, src, .. } => write!(f, "{:?} {}", op, src),
+            Binop { op, lhs, rhs, .. } => write!(f, "{} {}, {}", op, lhs, rhs),
+            String { string, .. } => write!(f, "string {}", string),
+            NewRef { .. } => write!(f, "new_ref"),
+            Load { ptr, .. } => write!(f, "load {}", ptr),
+            Store { ptr, value } => write!(f, "store {}, {}", ptr, value),
+            Delete { ptr } => write!(f, "delete {}", ptr),
+            NewArray { dims, .. } => write!(f, "new_array [{}]", comma_separated(dims)),
+            ArrayElem { array, indexes, .. } => {
+                write!(f, "array_elem {}[{}]", array, comma_separated(indexes))
+            }
+            ArrayDim { array, dim, .. } => write!(f, "array_dim {}, {}", array, dim),
+            NewTuple { elems, .. } => write!(f, "new_tuple ({})", comma_separated(elems)),
+            TupleGet { src, idx, .. } => write!(f, "tuple_get {}.{}", src, idx),
+            NewConstr { tag, args, .. } => {
+                write!(f, "new_constr #{} ({})", tag, comma_separated(args))
+            }
+            Tag { src, .. } => write!(f, "tag {}", src),
+            ConstrGet { src, tag, idx, .. } => write!(f, "constr_get {}#{}.{}", src, tag, idx),
+            Closure { code, .. } => write!(f, "closure {}", func_name(code)),
+            SetCapture {
+                closure,
+                env,
+                idx,
+                value,
+            } => write!(f, "set_capture {}: {}.{}, {}", closure, env, idx, value),
+            Capture { idx, .. } => write!(f, "capture {}", idx),
+            SelfClosure { .. } => write!(f, "self"),
+            GetGlobal { global, .. } => {
+                write!(f, "get_global {} {}", global, module.global(*global).name)
+            }
+            SetGlobal { global, value } => write!(
+                f,
+                "set_global {} {}, {}",
+                global,
+                module.global(*global).name,
+                value
+            ),
+            Call { func, args, .. } => {
+                write!(f, "call {}({})", func_name(func), comma_separated(args))
+            }
+            CallClosure { closure, arg, .. } => write!(f, "call_closure {}({})", closure, arg),
+            CallRuntime { func, args, .. } => {
+                write!(
+                    f,
+                    "call_runtime {}({})",
+                    func.symbol(),
+                    comma_separated(args)
+                )
+            }
+        }
+    }
+}
+
+impl Display for Terminator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Jump(block) => write!(f, "jump {}", block),
+            Terminator::Branch { cond, then, else_ } => {
+                write!(f, "branch {}, {}, {}", cond, then, else_)
+            }
+            Terminator::Return(temp) => write!(f, "ret {}", temp),
+            Terminator::Trap(trap) => write!(f, "trap {:?}", trap),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl Display for CustomType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "type {} =", self.name)?;
+        for (i, constr) in self.constrs.iter().enumerate() {
+            let sep = if i == 0 { "" } else { " |" };
+            write!(f, "{} #{} {}", sep, i, constr.name)?;
+            if !constr.fields.is_empty() {
+                write!(f, " of {}", comma_separated(&constr.fields))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Unit => write!(f, "unit"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Char => write!(f, "char"),
+            Ty::Int => write!(f, "int"),
+            Ty::Float => write!(f, "float"),
+            Ty::Func(lhs, rhs) => write!(f, "{} -> {}", Operand(lhs), rhs),
+            Ty::Ref(inner) => write!(f, "{} ref", Operand(inner)),
+            Ty::Array(inner, dims) => write!(
+                f,
+                "{}[{}]",
+                Operand(inner),
+                vec!["*"; *dims as usize].join(", ")
+            ),
+            Ty::Tuple(tys) => write!(f, "({})", comma_separated(tys)),
+            Ty::Custom(id) => write!(f, "{}", id),
+            Ty::Env(env) => write!(f, "{}", env),
+        }
+    }
+}
+
+/// Parenthesizes function types, where they are an operand of another type.
+struct Operand<'t>(&'t Ty);
+impl Display for Operand<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Ty::Func(..) => write!(f, "({})", self.0),
+            ty => write!(f, "{}", ty),
+        }
+    }
+}
+
+impl Display for Const {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Const::Unit => write!(f, "()"),
+            Const::Bool(b) => write!(f, "{}", b),
+            Const::Char(c) => write!(f, "{:?}", *c as char),
+            Const::Int(i) => write!(f, "{}", i),
+            Const::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+impl Display for BinOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BinOp::ICmp(op) => write!(f, "ICmp{:?}", op),
+            BinOp::FCmp(op) => write!(f, "FCmp{:?}", op),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl Display for Temp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+impl Display for BlockId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+impl Display for GlobalId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", self.0)
+    }
+}
+impl Display for StrId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "str{}", self.0)
+    }
+}
+impl Display for EnvId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "env{}", self.0)
+    }
+}
+
+fn comma_separated<T: Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,408 @@
+mod builder;
+mod def;
+mod expr;
+pub mod ir;
+mod mono;
+mod pattern;
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    parse::ast::{
+        data_map::NodeRef,
+        def::{Definition, Letdef, Typedef},
+        Program,
+    },
+    pass::sem::{
+        builtins::{Builtin, BUILTINS},
+        sem_table::SemTable,
+    },
+};
+
+use self::{
+    builder::FnBuilder,
+    ir::{
+        Const, Constructor, CustomType, Env, EnvId, FuncId, Function, Global, GlobalId, Inst,
+        Module, Temp, Terminator, Ty,
+    },
+    mono::{mono_type, Subst},
+};
+
+// ! Implementation notes:
+// !   Bindings live in a persistent scope, so generic definitions can keep the scope
+// !   they were defined in and be lowered again for every instance they are used with.
+// !   Every function gets a direct entry point taking all of its arguments at once.
+// !   Function values are closure records, entered through a chain of curried wrappers
+// !   (see `IrGen::code0`) that are only generated if the function is used as a value.
+// !   Local functions find out what they capture while their body is lowered.
+
+pub fn irgen<'a>(ast: &'a Program, sem: &SemTable<'a>) -> Module {
+    let mut gen = IrGen::new(sem);
+    let entry = gen.lower_program(ast);
+    gen.finish(entry)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BindingId(u32);
+
+#[derive(Debug, Clone)]
+struct Binding<'a> {
+    ty: Ty,
+    kind: BindingKind<'a>,
+}
+#[derive(Debug, Clone)]
+enum BindingKind<'a> {
+    /// Held by a temp of the function that defined it, and captured by nested ones.
+    Local,
+    Global(GlobalId),
+    /// Local functions are also held by a temp, top level ones have no environment.
+    Func {
+        direct: FuncId,
+        arity: usize,
+        top_level: bool,
+    },
+    /// Stands for an instance of one of the functions of `group`, picked on every use.
+    Generic {
+        group: Rc<GenericGroup<'a>>,
+        idx: usize,
+    },
+    Constr {
+        tag: u32,
+        arity: usize,
+    },
+    /// Called through the runtime, possibly at a different type on every use.
+    Builtin(&'static Builtin),
+}
+
+/// What is needed to lower a generic `let` definition again, for another instance.
+#[derive(Debug)]
+struct GenericGroup<'a> {
+    letdef: &'a Letdef,
+    scope: Scope<'a>,
+    subst: Subst,
+    top_level: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Scope<'a>(Option<Rc<ScopeEntry<'a>>>);
+#[derive(Debug)]
+struct ScopeEntry<'a> {
+    name: &'a str,
+    binding: BindingId,
+    parent: Scope<'a>,
+}
+
+/// A generic group and the substitution it is instantiated with, sorted by unknown id.
+type InstanceKey = (*const Letdef, Vec<(u32, Ty)>);
+
+#[derive(Debug, Clone)]
+struct Signature {
+    name: String,
+    env: Option<EnvId>,
+    params: Vec<Ty>,
+    ret: Ty,
+}
+
+struct IrGen<'a, 's> {
+    sem: &'s SemTable<'a>,
+
+    functions: Vec<Option<Function>>,
+    signatures: Vec<Signature>,
+    envs: Vec<Env>,
+    globals: Vec<Global>,
+    strings: Vec<Vec<u8>>,
+    customs: Vec<CustomType>,
+
+    bindings: Vec<Binding<'a>>,
+    scope: Scope<'a>,
+    /// The functions being lowered, innermost last.
+    builders: Vec<FnBuilder>,
+    /// The capture layout of every function with an environment.
+    captures: HashMap<FuncId, Vec<BindingId>>,
+
+    /// The functions of each instance of a generic group.
+    instances: HashMap<InstanceKey, Vec<Option<BindingId>>>,
+    curried: HashMap<FuncId, FuncId>,
+    constr_fns: HashMap<BindingId, FuncId>,
+    builtin_fns: HashMap<(&'static str, Ty), FuncId>,
+    eq_fns: HashMap<Ty, FuncId>,
+    name_counts: HashMap<String, u32>,
+    empty_env: EnvId,
+}
+
+impl<'a, 's> IrGen<'a, 's> {
+    fn new(sem: &'s SemTable<'a>) -> Self {
+        Self {
+            sem,
+            functions: Vec::new(),
+            signatures: Vec::new(),
+            envs: vec![Env {
+                captures: Vec::new(),
+            }],
+            globals: Vec::new(),
+            strings: Vec::new(),
+            customs: Vec::new(),
+            bindings: Vec::new(),
+            scope: Scope::default(),
+            builders: Vec::new(),
+            captures: HashMap::new(),
+            instances: HashMap::new(),
+            curried: HashMap::new(),
+            constr_fns: HashMap::new(),
+            builtin_fns: HashMap::new(),
+            eq_fns: HashMap::new(),
+            name_counts: HashMap::new(),
+            empty_env: EnvId(0),
+        }
+    }
+    fn finish(self, entry: FuncId) -> Module {
+        Module {
+            functions: self
+                .functions
+                .into_iter()
+                .map(|f| f.expect("every reserved function should have been lowered"))
+                .collect(),
+            envs: self.envs,
+            globals: self.globals,
+            strings: self.strings,
+            customs: self.customs,
+            entry,
+        }
+    }
+    fn lower_program(&mut self, ast: &'a Program) -> FuncId {
+        for builtin in BUILTINS {
+            let ty = self.ty_in(builtin, &Subst::new());
+            let binding = self.new_binding(ty, BindingKind::Builtin(builtin));
+            self.bind(builtin.id, binding);
+        }
+        for def in &ast.definitions {
+            if let Definition::Type(typedef) = def {
+                self.declare_typedef(typedef);
+            }
+        }
+        let entry = self.reserve_function(Signature {
+            name: "main".to_string(),
+            env: None,
+            params: Vec::new(),
+            ret: Ty::Unit,
+        });
+        self.begin_function(entry, Subst::new());
+        for def in &ast.definitions {
+            match def {
+                Definition::Let(letdef) => self.lower_letdef(letdef, true),
+                Definition::Type(typedef) => self.bind_typedef(typedef),
+                Definition::Error(_) => unreachable!("programs with syntax errors are not lowered"),
+            }
+        }
+        let unit = self.emit_const(Const::Unit);
+        self.end_function(unit);
+        entry
+    }
+    fn declare_typedef(&mut self, typedef: &'a Typedef) {
+        for tdef in &typedef.tdefs {
+            let Ty::Custom(name) = self.ty_in(tdef, &Subst::new()) else {
+                unreachable!("a type definition has a custom type")
+            };
+            let constrs = tdef
+                .constrs
+                .iter()
+                .map(|constr| {
+                    let mut fields = Vec::with_capacity(constr.types.len());
+                    let mut ty = self.ty_in(constr, &Subst::new());
+                    while let Ty::Func(lhs, rhs) = ty {
+                        fields.push(*lhs);
+                        ty = *rhs;
+                    }
+                    Constructor {
+                        name: constr.id.clone(),
+                        fields,
+                    }
+                })
+                .collect();
+            self.customs.push(CustomType { name, constrs });
+        }
+    }
+    fn bind_typedef(&mut self, typedef: &'a Typedef) {
+        for tdef in &typedef.tdefs {
+            for (tag, constr) in tdef.constrs.iter().enumerate() {
+                let ty = self.ty_of(constr);
+                let kind = BindingKind::Constr {
+                    tag: tag as u32,
+                    arity: constr.types.len(),
+                };
+                let binding = self.new_binding(ty, kind);
+                self.bind(&constr.id, binding);
+            }
+        }
+    }
+}
+
+/// Bindings and scopes.
+impl<'a, 's> IrGen<'a, 's> {
+    fn new_binding(&mut self, ty: Ty, kind: BindingKind<'a>) -> BindingId {
+        self.bindings.push(Binding { ty, kind });
+        BindingId(self.bindings.len() as u32 - 1)
+    }
+    fn binding(&self, id: BindingId) -> &Binding<'a> {
+        &self.bindings[id.0 as usize]
+    }
+    fn bind(&mut self, name: &'a str, binding: BindingId) {
+        let parent = std::mem::take(&mut self.scope);
+        self.scope = Scope(Some(Rc::new(ScopeEntry {
+            name,
+            binding,
+            parent,
+        })));
+    }
+    /// Binds `name` to the value held by `temp` in the current function.
+    fn bind_local(&mut self, name: &'a str, temp: Temp) -> BindingId {
+        let ty = self.cur().temp_ty(temp).clone();
+        let binding = self.new_binding(ty, BindingKind::Local);
+        self.cur_mut().locals.insert(binding, temp);
+        self.bind(name, binding);
+        binding
+    }
+    fn lookup(&self, name: &str) -> BindingId {
+        let mut scope = &self.scope;
+        while let Some(entry) = &scope.0 {
+            if entry.name == name {
+                return entry.binding;
+            }
+            scope = &entry.parent;
+        }
+        panic!("'{}' should have been resolved by sem", name)
+    }
+    fn new_global(&mut self, name: &str, ty: Ty) -> GlobalId {
+        self.globals.push(Global {
+            name: name.to_string(),
+            ty,
+        });
+        GlobalId(self.globals.len() as u32 - 1)
+    }
+    fn new_env(&mut self, captures: Vec<Ty>) -> EnvId {
+        self.envs.push(Env { captures });
+        EnvId(self.envs.len() as u32 - 1)
+    }
+    fn custom(&self, name: &str) -> &CustomType {
+        self.customs
+            .iter()
+            .find(|c| c.name == name)
+            .expect("custom type should have been declared")
+    }
+    fn unique_name(&mut self, name: &str) -> String {
+        let count = self.name_counts.entry(name.to_string()).or_insert(0);
+        *count += 1;
+        match *count {
+            1 => name.to_string(),
+            n => format!("{}.{}", name, n - 1),
+        }
+    }
+}
+
+/// Building the current function.
+impl<'a, 's> IrGen<'a, 's> {
+    fn cur(&self) -> &FnBuilder {
+        self.builders.last().expect("should be inside a function")
+    }
+    fn cur_mut(&mut self) -> &mut FnBuilder {
+        self.builders
+            .last_mut()
+            .expect("should be inside a function")
+    }
+    /// The type of `node` in the instance currently being lowered.
+    fn ty_of(&self, node: impl Into<NodeRef<'a>>) -> Ty {
+        self.ty_in(node, &self.cur().subst)
+    }
+    fn ty_in(&self, node: impl Into<NodeRef<'a>>, subst: &Subst) -> Ty {
+        let ty = self
+            .sem
+            .types
+            .get_type(node)
+            .expect("node should have a type after sem");
+        mono_type(&self.sem.types, ty, subst)
+    }
+    fn temp_ty(&self, temp: Temp) -> &Ty {
+        self.cur().temp_ty(temp)
+    }
+    fn new_temp(&mut self, ty: Ty) -> Temp {
+        self.cur_mut().new_temp(ty)
+    }
+    fn emit(&mut self, inst: Inst) {
+        self.cur_mut().emit(inst)
+    }
+    /// Emits the instruction built by `inst` for a fresh destination temp of type `ty`.
+    fn emit_value(&mut self, ty: Ty, inst: impl FnOnce(Temp) -> Inst) -> Temp {
+        let dst = self.new_temp(ty);
+        self.emit(inst(dst));
+        dst
+    }
+    fn emit_const(&mut self, value: Const) -> Temp {
+        let ty = match value {
+            Const::Unit => Ty::Unit,
+            Const::Bool(_) => Ty::Bool,
+            Const::Char(_) => Ty::Char,
+            Const::Int(_) => Ty::Int,
+            Const::Float(_) => Ty::Float,
+        };
+        self.emit_value(ty, |dst| Inst::Const { dst, value })
+    }
+    fn emit_copy(&mut self, dst: Temp, src: Temp) {
+        self.emit(Inst::Copy { dst, src })
+    }
+    fn new_block(&mut self) -> ir::BlockId {
+        self.cur_mut().new_block()
+    }
+    fn switch_to(&mut self, block: ir::BlockId) {
+        self.cur_mut().switch_to(block)
+    }
+    fn terminate(&mut self, term: Terminator) {
+        self.cur_mut().terminate(term)
+    }
+    /// Continues in a fresh block if `cond` holds, jumps to `otherwise` if not.
+    fn emit_check(&mut self, cond: Temp, otherwise: ir::BlockId) {
+        let next = self.new_block();
+        self.terminate(Terminator::Branch {
+            cond,
+            then: next,
+            else_: otherwise,
+        });
+        self.switch_to(next);
+    }
+
+    fn reserve_function(&mut self, signature: Signature) -> FuncId {
+        self.functions.push(None);
+        self.signatures.push(signature);
+        FuncId(self.functions.len() as u32 - 1)
+    }
+    fn signature(&self, id: FuncId) -> &Signature {
+        &self.signatures[id.0 as usize]
+    }
+    /// Starts lowering the body of a reserved function, returns its parameters.
+    fn begin_function(&mut self, id: FuncId, subst: Subst) -> Vec<Temp> {
+        let signature = self.signature(id).clone();
+        let mut builder = FnBuilder::new(id, signature.env, subst);
+        let params = signature
+            .params
+            .into_iter()
+            .map(|ty| builder.new_param(ty))
+            .collect();
+        self.builders.push(builder);
+        params
+    }
+    fn end_function(&mut self, result: Temp) {
+        let mut builder = self.builders.pop().expect("should be inside a function");
+        builder.terminate(Terminator::Return(result));
+        let id = builder.id;
+        if let Some(env) = builder.env {
+            let captured: Vec<_> = builder
+                .captures
+                .iter()
+                .map(|binding| self.binding(*binding).ty.clone())
+                .collect();
+            self.envs[env.0 as usize].captures.extend(captured);
+            self.captures.insert(id, builder.captures.clone());
+        }
+        let Signature { name, ret, .. } = self.signature(id).clone();
+        self.functions[id.0 as usize] = Some(builder.finish(name, ret));
+    }
+}
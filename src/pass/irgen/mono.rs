@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use crate::pass::sem::types::{
+    inference::{ArrayDims, Constraints, InfererHelpers},
+    type_map::TypeMap,
+    Type, TypeId, TypeKind,
+};
+
+use super::ir::Ty;
+
+/// Maps (the ids of) the unknowns of a generic definition to the types of one of its instances.
+pub type Subst = HashMap<u32, Ty>;
+
+/// Converts a type into the type it has in the instance described by `subst`.
+pub fn mono_type(types: &TypeMap, ty: TypeId, subst: &Subst) -> Ty {
+    match types.get(types.resolve_type(ty)) {
+        Type::Unknown(id) => subst
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| default_type(types.constraints_of(*id))),
+        Type::Unit => Ty::Unit,
+        Type::Int => Ty::Int,
+        Type::Char => Ty::Char,
+        Type::Bool => Ty::Bool,
+        Type::Float => Ty::Float,
+        Type::Func { lhs, rhs } => {
+            Ty::new_func(mono_type(types, *lhs, subst), mono_type(types, *rhs, subst))
+        }
+        Type::Ref(inner) => Ty::new_ref(mono_type(types, *inner, subst)),
+        Type::Array { inner, dims } => {
+            let dims = match types.dims_of(*dims) {
+                ArrayDims::Known(n) | ArrayDims::LowerBounded(n) => n,
+            };
+            Ty::Array(Box::new(mono_type(types, *inner, subst)), dims)
+        }
+        Type::Tuple(elems) => Ty::Tuple(
+            elems
+                .iter()
+                .map(|ty| mono_type(types, *ty, subst))
+                .collect(),
+        ),
+        // Types of the same name may shadow each other, the uid tells them apart.
+        Type::Custom { uid } => Ty::Custom(format!("{}_{}", types.get_custom_tdef(*uid).id, uid)),
+        Type::Poison => unreachable!("only programs that failed sem have poisoned types"),
+    }
+}
+
+/// An unknown that nothing pins down never has its value inspected,
+/// so any type satisfying its constraints will do.
+fn default_type(constraints: &Constraints) -> Ty {
+    [(TypeKind::Unit, Ty::Unit), (TypeKind::Int, Ty::Int)]
+        .into_iter()
+        .find(|(kind, _)| constraints.are_satisfied_by_kind(kind))
+        .map(|(_, ty)| ty)
+        .unwrap_or(Ty::Unit)
+}
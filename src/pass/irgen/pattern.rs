@@ -0,0 +1,96 @@
+use crate::parse::ast::expr::{Pattern, PatternKind};
+
+use super::{
+    ir::{BinOp, BlockId, CmpOp, Const, Inst, Runtime, Temp, Ty},
+    BindingKind, IrGen,
+};
+
+impl<'a, 's> IrGen<'a, 's> {
+    /// Continues in a fresh block (with the pattern's variables bound) if `value` matches `pattern`,
+    /// jumps to `no_match` if not.
+    pub(super) fn lower_pattern(&mut self, pattern: &'a Pattern, value: Temp, no_match: BlockId) {
+        let literal = match &pattern.kind {
+            PatternKind::IntLiteral(i) => Const::Int(*i as i64),
+            PatternKind::FloatLiteral(x) => Const::Float(*x),
+            PatternKind::CharLiteral(c) => Const::Char(*c),
+            PatternKind::BoolLiteral(b) => Const::Bool(*b),
+            PatternKind::StringLiteral(s) => {
+                let literal = self.lower_string(s);
+                let equal = self.emit_value(Ty::Bool, |dst| Inst::CallRuntime {
+                    dst,
+                    func: Runtime::StrEq,
+                    args: vec![value, literal],
+                });
+                return self.emit_check(equal, no_match);
+            }
+            PatternKind::IdLower(id) => {
+                self.bind_local(id, value);
+                return;
+            }
+            PatternKind::Tuple(patterns) => {
+                let Ty::Tuple(tys) = self.temp_ty(value).clone() else {
+                    unreachable!("tuple patterns match tuples")
+                };
+                for (idx, (pattern, ty)) in patterns.iter().zip(tys).enumerate() {
+                    let idx = idx as u32;
+                    let elem = self.emit_value(ty, |dst| Inst::TupleGet {
+                        dst,
+                        src: value,
+                        idx,
+                    });
+                    self.lower_pattern(pattern, elem, no_match);
+                }
+                return;
+            }
+            PatternKind::IdUpper { id, args } => {
+                return self.lower_constr_pattern(id, args, value, no_match)
+            }
+        };
+        let op = match literal {
+            Const::Float(_) => BinOp::FCmp(CmpOp::Eq),
+            _ => BinOp::ICmp(CmpOp::Eq),
+        };
+        let literal = self.emit_const(literal);
+        let equal = self.emit_value(Ty::Bool, |dst| Inst::Binop {
+            dst,
+            op,
+            lhs: value,
+            rhs: literal,
+        });
+        self.emit_check(equal, no_match);
+    }
+    fn lower_constr_pattern(
+        &mut self,
+        id: &str,
+        args: &'a [Pattern],
+        value: Temp,
+        no_match: BlockId,
+    ) {
+        let BindingKind::Constr { tag, .. } = self.binding(self.lookup(id)).kind else {
+            unreachable!("constructor patterns refer to constructors")
+        };
+        let Ty::Custom(custom) = self.temp_ty(value).clone() else {
+            unreachable!("constructor patterns match custom types")
+        };
+        let fields = self.custom(&custom).constrs[tag as usize].fields.clone();
+        let actual = self.emit_value(Ty::Int, |dst| Inst::Tag { dst, src: value });
+        let expected = self.emit_const(Const::Int(tag as i64));
+        let equal = self.emit_value(Ty::Bool, |dst| Inst::Binop {
+            dst,
+            op: BinOp::ICmp(CmpOp::Eq),
+            lhs: actual,
+            rhs: expected,
+        });
+        self.emit_check(equal, no_match);
+        for (idx, (pattern, ty)) in args.iter().zip(fields).enumerate() {
+            let idx = idx as u32;
+            let field = self.emit_value(ty, |dst| Inst::ConstrGet {
+                dst,
+                src: value,
+                tag,
+                idx,
+            });
+            self.lower_pattern(pattern, field, no_match);
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::{
+    diagnostic::{
+        codes::{self, Code},
+        Diagnostic,
+    },
+    parse::ast::{
+        annotation::TypeAnnotation,
+        attribute::Attribute,
+        data_map::{NodeRef, NodeRefInner},
+        def::{Def, DefKind, Definition, Letdef, TDef, Typedef},
+        expr::{Expr, ExprKind, Pattern, PatternKind, UnopKind},
+        Program, Span,
+    },
+    pass::sem::{
+        sem_table::SemTable,
+        types::{inference::InfererHelpers, TypeId},
+    },
+};
+
+// ! Implementation notes:
+// !   Uses are the references sem resolved (see `sem::references`), a use from inside the
+// !   binding's own definition (a recursive call, a type naming itself) does not count.
+// !   The program's entry runs the top level definitions in order, so the ones that are not
+// !   functions are where it starts from. A top level function is reachable if a reachable
+// !   definition uses it anywhere in its body, even from a local function that is never called.
+// !   A mutable is only reported if every use of it reads it, through `!` or `dim`. Any other
+// !   use may assign it, directly with `:=` or by handing it to code that does.
+// !   Constants of type unit are evaluated for their effects, they are never reported unused,
+// !   neither is a top level `main`, the conventional entry of a program.
+
+/// Checks a program that passed sem for bindings that are never used, mutables that are never
+/// assigned and top level functions that the program never reaches.
+/// Bindings that follow an `allow` attribute naming the lint, e.g.
+/// `(*@ allow(unused_parameter) *)`, are not reported, neither are the ones defined inside one
+/// that does (see `Allowances`).
+pub fn lint<'a>(ast: &'a Program, table: &SemTable<'a>) -> Vec<LintWarning> {
+    let linter = Linter::walk(ast, table);
+    linter.report(&linter.allowances(&ast.attributes))
+}
+
+/// What the `allow` attributes of a program cover. Each attribute covers the first binding
+/// that starts after it in the same file, along with everything defined inside of it.
+pub struct Allowances<'a>(Vec<(&'a Attribute, Span)>);
+impl<'a> Allowances<'a> {
+    pub fn new(ast: &'a Program, table: &SemTable<'a>) -> Self {
+        Linter::walk(ast, table).allowances(&ast.attributes)
+    }
+    /// Whether an attribute covering `span` allows `code`, by its name or by the code itself.
+    pub fn allows(&self, code: &Code, span: &Span) -> bool {
+        self.0.iter().any(|(attribute, covered)| {
+            covered.contains(span) && (attribute.allows(code.name) || attribute.allows(code.code))
+        })
+    }
+}
+
+/// Found by the lint pass, the program is still valid.
+#[derive(Error, Debug)]
+pub enum LintWarning {
+    #[error("Unused binding {} (at {})", id, span)]
+    UnusedBinding { id: String, span: Span },
+    #[error("Unused parameter {} (at {})", id, span)]
+    UnusedParameter { id: String, span: Span },
+    #[error("Unused constructor {} (at {})", id, span)]
+    UnusedConstructor { id: String, span: Span },
+    #[error("Unused type {} (at {})", id, span)]
+    UnusedType { id: String, span: Span },
+    #[error("Mutable {} is never assigned (at {})", id, span)]
+    UnassignedMutable { id: String, span: Span },
+    #[error("Function {} is unreachable from the program entry (at {})", id, span)]
+    UnreachableFunction { id: String, span: Span },
+}
+impl LintWarning {
+    pub fn code(&self) -> &'static Code {
+        use LintWarning::*;
+        match self {
+            UnusedBinding { .. } => &codes::UNUSED_BINDING,
+            UnusedParameter { .. } => &codes::UNUSED_PARAMETER,
+            UnusedConstructor { .. } => &codes::UNUSED_CONSTRUCTOR,
+            UnusedType { .. } => &codes::UNUSED_TYPE,
+            UnassignedMutable { .. } => &codes::UNASSIGNED_MUTABLE,
+            UnreachableFunction { .. } => &codes::UNREACHABLE_FUNCTION,
+        }
+    }
+    /// The name attributes refer to the lint by.
+    pub fn name(&self) -> &'static str {
+        self.code().name
+    }
+    pub fn span(&self) -> &Span {
+        use LintWarning::*;
+        match self {
+            UnusedBinding { span, .. }
+            | UnusedParameter { span, .. }
+            | UnusedConstructor { span, .. }
+            | UnusedType { span, .. }
+            | UnassignedMutable { span, .. }
+            | UnreachableFunction { span, .. } => span,
+        }
+    }
+}
+impl From<&LintWarning> for Diagnostic {
+    fn from(warning: &LintWarning) -> Self {
+        use LintWarning::*;
+        let (message, label) = match warning {
+            UnusedBinding { id, .. } => (format!("Unused binding {}", id), "never used"),
+            UnusedParameter { id, .. } => (format!("Unused parameter {}", id), "never used"),
+            UnusedConstructor { id, .. } => (format!("Unused constructor {}", id), "never used"),
+            UnusedType { id, .. } => (format!("Unused type {}", id), "never used"),
+            UnassignedMutable { id, .. } => (
+                format!("Mutable {} is never assigned", id),
+                "only ever read",
+            ),
+            UnreachableFunction { id, .. } => (
+                format!("Function {} is unreachable from the program entry", id),
+                "never called from the program entry",
+            ),
+        };
+        let help = format!("put `(*@ allow({}) *)` before it", warning.name());
+        Diagnostic::new(warning.code(), message)
+            .with_primary(warning.span(), label)
+            .with_help(Some(help))
+    }
+}
+
+struct Linter<'a, 't> {
+    table: &'t SemTable<'a>,
+    /// Every binding the program makes, in the order they appear.
+    bindings: Vec<NodeRef<'a>>,
+    /// The bindings whose definitions are being walked, innermost last.
+    enclosing: Vec<NodeRef<'a>>,
+    /// The top level binding whose definition is being walked.
+    owner: Option<NodeRef<'a>>,
+    top_level: HashSet<NodeRef<'a>>,
+    used: HashSet<NodeRef<'a>>,
+    /// The bindings with a use that does more than read them.
+    assigned: HashSet<NodeRef<'a>>,
+    /// The bindings used in the definition of each top level binding.
+    owned_uses: HashMap<NodeRef<'a>, Vec<NodeRef<'a>>>,
+}
+
+impl<'a, 't> Linter<'a, 't> {
+    fn new(table: &'t SemTable<'a>) -> Self {
+        Self {
+            table,
+            bindings: Vec::new(),
+            enclosing: Vec::new(),
+            owner: None,
+            top_level: HashSet::new(),
+            used: HashSet::new(),
+            assigned: HashSet::new(),
+            owned_uses: HashMap::new(),
+        }
+    }
+    fn walk(ast: &'a Program, table: &'t SemTable<'a>) -> Self {
+        let mut linter = Self::new(table);
+        for definition in &ast.definitions {
+            match definition {
+                Definition::Let(letdef) => linter.letdef(letdef, true),
+                Definition::Type(typedef) => linter.typedef(typedef),
+                Definition::Error(_) => (),
+            }
+        }
+        linter
+    }
+    fn letdef(&mut self, letdef: &'a Letdef, top_level: bool) {
+        for def in &letdef.defs {
+            self.add_binding(def.into());
+            if top_level {
+                self.top_level.insert(def.into());
+                self.owner = Some(def.into());
+            }
+            self.enclosing.push(def.into());
+            if let Some(annotation) = &def.type_ {
+                self.annotation(annotation);
+            }
+            match &def.kind {
+                DefKind::Const { expr } => self.expr(expr, false),
+                DefKind::Variable => (),
+                DefKind::Array { dims } => dims.iter().for_each(|dim| self.expr(dim, false)),
+                DefKind::Function { pars, expr } => {
+                    for par in pars {
+                        self.add_binding(par.into());
+                        if let Some(annotation) = &par.type_ {
+                            self.annotation(annotation);
+                        }
+                    }
+                    self.expr(expr, false);
+                }
+            }
+            self.enclosing.pop();
+        }
+    }
+    fn typedef(&mut self, typedef: &'a Typedef) {
+        for tdef in &typedef.tdefs {
+            self.add_binding(tdef.into());
+            self.owner = Some(tdef.into());
+            self.enclosing.push(tdef.into());
+            for constr in &tdef.constrs {
+                self.add_binding(constr.into());
+                constr.types.iter().for_each(|t| self.annotation(t));
+            }
+            self.enclosing.pop();
+        }
+    }
+    fn add_binding(&mut self, binding: NodeRef<'a>) {
+        self.bindings.push(binding);
+    }
+    fn annotation(&mut self, annotation: &'a TypeAnnotation) {
+        match annotation {
+            TypeAnnotation::Func { lhs, rhs } => {
+                self.annotation(lhs);
+                self.annotation(rhs);
+            }
+            TypeAnnotation::Ref(inner) | TypeAnnotation::Array { inner, .. } => {
+                self.annotation(inner)
+            }
+            TypeAnnotation::Tuple(types) => types.iter().for_each(|t| self.annotation(t)),
+            TypeAnnotation::Custom { .. } => self.use_at(annotation.into(), true),
+            TypeAnnotation::Unit
+            | TypeAnnotation::Int
+            | TypeAnnotation::Char
+            | TypeAnnotation::Bool
+            | TypeAnnotation::Float => (),
+        }
+    }
+    /// `reads` is whether the use only reads the value of its binding.
+    fn expr(&mut self, expr: &'a Expr, reads: bool) {
+        match &expr.kind {
+            ExprKind::UnitLiteral
+            | ExprKind::IntLiteral(_)
+            | ExprKind::FloatLiteral(_)
+            | ExprKind::CharLiteral(_)
+            | ExprKind::StringLiteral(_)
+            | ExprKind::BoolLiteral(_)
+            | ExprKind::Error => (),
+            ExprKind::Tuple(exprs) => exprs.iter().for_each(|e| self.expr(e, false)),
+            ExprKind::Unop(unop) => self.expr(&unop.operand, matches!(unop.op, UnopKind::Deref)),
+            ExprKind::Binop(binop) => {
+                self.expr(&binop.lhs, false);
+                self.expr(&binop.rhs, false);
+            }
+            ExprKind::Call(call) => {
+                self.use_at(expr.into(), reads);
+                call.args.iter().for_each(|arg| self.expr(arg, false));
+            }
+            ExprKind::ConstrCall(call) => {
+                self.use_at(expr.into(), true);
+                call.args.iter().for_each(|arg| self.expr(arg, false));
+            }
+            ExprKind::ArrayAccess(access) => {
+                self.use_at(expr.into(), reads);
+                access
+                    .indexes
+                    .iter()
+                    .for_each(|index| self.expr(index, false));
+            }
+            ExprKind::Dim(_) => self.use_at(expr.into(), true),
+            ExprKind::New(annotation) => self.annotation(annotation),
+            ExprKind::LetIn(letin) => {
+                self.letdef(&letin.letdef, false);
+                self.expr(&letin.expr, false);
+            }
+            ExprKind::If(if_) => {
+                self.expr(&if_.cond, false);
+                self.expr(&if_.then_body, false);
+                if let Some(else_body) = &if_.else_body {
+                    self.expr(else_body, false);
+                }
+            }
+            ExprKind::While(while_) => {
+                self.expr(&while_.cond, false);
+                self.expr(&while_.body, false);
+            }
+            ExprKind::For(for_) => {
+                self.expr(&for_.from, false);
+                self.expr(&for_.to, false);
+                self.expr(&for_.body, false);
+            }
+            ExprKind::Match(match_) => {
+                self.expr(&match_.to_match, false);
+                for clause in &match_.clauses {
+                    self.pattern(&clause.pattern);
+                    self.expr(&clause.expr, false);
+                }
+            }
+        }
+    }
+    fn pattern(&mut self, pattern: &'a Pattern) {
+        match &pattern.kind {
+            PatternKind::IdUpper { args, .. } => {
+                self.use_at(pattern.into(), true);
+                args.iter().for_each(|arg| self.pattern(arg));
+            }
+            PatternKind::Tuple(patterns) => patterns.iter().for_each(|p| self.pattern(p)),
+            PatternKind::IntLiteral(_)
+            | PatternKind::FloatLiteral(_)
+            | PatternKind::CharLiteral(_)
+            | PatternKind::StringLiteral(_)
+            | PatternKind::BoolLiteral(_)
+            | PatternKind::IdLower(_) => (),
+        }
+    }
+    fn use_at(&mut self, site: NodeRef<'a>, reads: bool) {
+        let Some(binding) = self.table.references.binding_of(site) else {
+            return;
+        };
+        if !reads {
+            self.assigned.insert(binding.clone());
+        }
+        if self.enclosing.contains(binding) {
+            return;
+        }
+        self.used.insert(binding.clone());
+        if let Some(owner) = &self.owner {
+            self.owned_uses
+                .entry(owner.clone())
+                .or_default()
+                .push(binding.clone());
+        }
+    }
+    /// The top level bindings the program's entry reaches.
+    fn reachable(&self) -> HashSet<NodeRef<'a>> {
+        let mut to_visit: Vec<_> = self
+            .top_level
+            .iter()
+            .filter(|binding| {
+                !matches!(
+                    binding,
+                    NodeRef::Def(Def {
+                        kind: DefKind::Function { .. },
+                        ..
+                    })
+                )
+            })
+            .cloned()
+            .collect();
+        let mut reachable: HashSet<_> = to_visit.iter().cloned().collect();
+        while let Some(binding) = to_visit.pop() {
+            for used in self.owned_uses.get(&binding).into_iter().flatten() {
+                if self.top_level.contains(used) && reachable.insert(used.clone()) {
+                    to_visit.push(used.clone());
+                }
+            }
+        }
+        reachable
+    }
+    fn allowances(&self, attributes: &'a [Attribute]) -> Allowances<'a> {
+        let mut allowances = Vec::new();
+        for attribute in attributes {
+            let end = &attribute.span.end;
+            let binding = self
+                .bindings
+                .iter()
+                .map(|binding| binding.get_span())
+                .filter(|span| {
+                    span.start.filename == end.filename
+                        && (span.start.line, span.start.column) >= (end.line, end.column)
+                })
+                .min_by_key(|span| (span.start.line, span.start.column));
+            if let Some(span) = binding {
+                allowances.push((attribute, span));
+            }
+        }
+        Allowances(allowances)
+    }
+    fn report(&self, allowances: &Allowances<'a>) -> Vec<LintWarning> {
+        let reachable = self.reachable();
+        let mut warnings = Vec::new();
+        for binding in &self.bindings {
+            let warning = match binding {
+                NodeRef::Def(def) => self.def_warning(def, &reachable),
+                NodeRef::Par(par) if !self.used.contains(binding) => {
+                    Some(LintWarning::UnusedParameter {
+                        id: par.id.clone(),
+                        span: par.span.clone(),
+                    })
+                }
+                NodeRef::TDef(tdef) if self.is_unused_type(tdef) => Some(LintWarning::UnusedType {
+                    id: tdef.id.clone(),
+                    span: tdef.span.clone(),
+                }),
+                // An unused type is reported instead of each of its constructors.
+                NodeRef::Constr(constr)
+                    if !self.used.contains(binding)
+                        && !self.is_unused_type(self.table.get_constr_tdef(constr)) =>
+                {
+                    Some(LintWarning::UnusedConstructor {
+                        id: constr.id.clone(),
+                        span: constr.span.clone(),
+                    })
+                }
+                _ => None,
+            };
+            let Some(warning) = warning else {
+                continue;
+            };
+            if !allowances.allows(warning.code(), warning.span()) {
+                warnings.push(warning);
+            }
+        }
+        warnings
+    }
+    /// Neither the type nor any of its constructors is used.
+    fn is_unused_type(&self, tdef: &'a TDef) -> bool {
+        !self.used.contains(&tdef.into())
+            && tdef
+                .constrs
+                .iter()
+                .all(|constr| !self.used.contains(&constr.into()))
+    }
+    fn def_warning(&self, def: &'a Def, reachable: &HashSet<NodeRef<'a>>) -> Option<LintWarning> {
+        let binding = NodeRef::from(def);
+        let id = def.id.clone();
+        let span = def.span.clone();
+        match &def.kind {
+            _ if def.id == "main" && self.top_level.contains(&binding) => None,
+            DefKind::Const { .. } if !self.used.contains(&binding) => {
+                let ty = self
+                    .table
+                    .types
+                    .get_type(def)
+                    .expect("checked definitions have a type");
+                (self.table.types.resolve_type(ty) != TypeId::UNIT)
+                    .then_some(LintWarning::UnusedBinding { id, span })
+            }
+            _ if !self.used.contains(&binding) => Some(LintWarning::UnusedBinding { id, span }),
+            DefKind::Variable | DefKind::Array { .. } if !self.assigned.contains(&binding) => {
+                Some(LintWarning::UnassignedMutable { id, span })
+            }
+            DefKind::Function { .. }
+                if self.top_level.contains(&binding) && !reachable.contains(&binding) =>
+            {
+                Some(LintWarning::UnreachableFunction { id, span })
+            }
+            _ => None,
+        }
+    }
+}
@@ -1 +1,5 @@
+pub mod codegen;
+pub mod interp;
+pub mod irgen;
+pub mod lint;
 pub mod sem;
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use super::types::{type_map::TypeMap, TypeId};
+
+/// A function of the standard library, predeclared in the root scope.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Builtin {
+    pub id: &'static str,
+    /// Written like a type annotation, `'a` style names stand for (generalized) unknowns.
+    pub signature: &'static str,
+}
+
+macro_rules! builtins {
+    ($($id:ident : $signature:literal),* $(,)?) => {
+        pub static BUILTINS: &[Builtin] = &[
+            $(Builtin { id: stringify!($id), signature: $signature }),*
+        ];
+    };
+}
+builtins! {
+    print_int: "int -> unit",
+    print_bool: "bool -> unit",
+    print_char: "char -> unit",
+    print_float: "float -> unit",
+    print_string: "char[] -> unit",
+
+    read_int: "unit -> int",
+    read_bool: "unit -> bool",
+    read_char: "unit -> char",
+    read_float: "unit -> float",
+    read_string: "int -> char[] -> unit",
+
+    abs: "int -> int",
+    fabs: "float -> float",
+    sqrt: "float -> float",
+    sin: "float -> float",
+    cos: "float -> float",
+    tan: "float -> float",
+    atan: "float -> float",
+    exp: "float -> float",
+    ln: "float -> float",
+    pi: "unit -> float",
+
+    incr: "int ref -> unit",
+    decr: "int ref -> unit",
+
+    float_of_int: "int -> float",
+    int_of_float: "float -> int",
+    round: "float -> int",
+    int_of_char: "char -> int",
+    char_of_int: "int -> char",
+
+    strlen: "char[] -> int",
+    strcmp: "char[] -> char[] -> int",
+    strcpy: "char[] -> char[] -> unit",
+    strcat: "char[] -> char[] -> unit",
+
+    exit: "int -> 'a",
+}
+
+impl Builtin {
+    /// Number of arguments the builtin takes.
+    pub fn arity(&self) -> usize {
+        self.signature
+            .split_whitespace()
+            .filter(|t| *t == "->")
+            .count()
+    }
+    /// Builds the type of the builtin, with fresh unknowns for its type variables.
+    pub fn make_type(&self, types: &mut TypeMap) -> TypeId {
+        let mut tokens = self.signature.split_whitespace().peekable();
+        let mut vars = HashMap::new();
+        let ty = parse_func(&mut tokens, types, &mut vars);
+        assert!(
+            tokens.next().is_none(),
+            "trailing tokens in the signature of {}",
+            self.id
+        );
+        ty
+    }
+}
+
+type Tokens = std::iter::Peekable<std::str::SplitWhitespace<'static>>;
+
+fn parse_func(
+    tokens: &mut Tokens,
+    types: &mut TypeMap,
+    vars: &mut HashMap<&'static str, TypeId>,
+) -> TypeId {
+    let lhs = parse_atom(tokens, types, vars);
+    if tokens.next_if_eq(&"->").is_some() {
+        let rhs = parse_func(tokens, types, vars);
+        types.new_func(lhs, rhs)
+    } else {
+        lhs
+    }
+}
+fn parse_atom(
+    tokens: &mut Tokens,
+    types: &mut TypeMap,
+    vars: &mut HashMap<&'static str, TypeId>,
+) -> TypeId {
+    let token = tokens.next().expect("signature ended early");
+    let (name, dims) = match token.split_once('[') {
+        Some((name, dims)) => (name, Some(1 + dims.matches(',').count() as u32)),
+        None => (token, None),
+    };
+    let mut ty = match name {
+        "unit" => types.get_unit(),
+        "int" => types.get_int(),
+        "char" => types.get_char(),
+        "bool" => types.get_bool(),
+        "float" => types.get_float(),
+        var if var.starts_with('\'') => *vars.entry(var).or_insert_with(|| types.new_unknown()),
+        other => panic!("unknown type '{}' in builtin signature", other),
+    };
+    if let Some(dims) = dims {
+        ty = types.new_known_array(ty, dims);
+    }
+    while tokens.next_if_eq(&"ref").is_some() {
+        ty = types.new_ref(ty);
+    }
+    ty
+}
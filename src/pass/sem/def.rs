@@ -1,6 +1,5 @@
-use std::rc::Rc;
-
 use crate::parse::ast::{
+    data_map::NodeRefInner,
     def::{Def, DefKind, Par},
     expr::Expr,
 };
@@ -8,9 +7,9 @@ use crate::parse::ast::{
 use super::{
     expr::SemExpr,
     sem_table::SemTable,
-    types::inference::{InferenceGroup, Inferer},
-    types::Type,
-    SemResult,
+    types::inference::{Constraints, InferenceGroup, Inferer},
+    types::{validity, TypeId},
+    SemResult, SemanticError,
 };
 
 pub trait SemDef<'a> {
@@ -19,21 +18,32 @@ pub trait SemDef<'a> {
 impl<'a> SemDef<'a> for SemTable<'a> {
     fn sem_def(&mut self, def: &'a Def) -> SemResult<()> {
         let mut inf_group = self.new_inference_group();
-        let annotation_type: Option<Rc<Type>> = def.type_.as_ref().map(|t| t.into());
-        let node_type: Rc<Type> = match &def.kind {
+        let annotation_type: Option<TypeId> = match &def.type_ {
+            Some(t) => {
+                let res = self
+                    .annotation_type(t, &def.span)
+                    .map(|ty| self.types.new_given(ty, "type annotation", &def.span));
+                Some(self.poison_on_error(res))
+            }
+            None => None,
+        };
+        let node_type: TypeId = match &def.kind {
             DefKind::Array { dims } => {
                 self.sem_array_def(&mut inf_group, dims)?;
-                match annotation_type {
+                // *Note: An array's annotation is the type of its elements.
+                let inner = match annotation_type {
                     Some(t) => t,
                     None => self.types.new_unknown(),
-                }
+                };
+                let array = self.types.new_known_array(inner, dims.len() as u32);
+                self.types.new_given(array, "array definition", &def.span)
             }
             DefKind::Const { expr } => {
                 let expr_type = self.sem_expr(&mut inf_group, expr)?;
                 match annotation_type {
                     Some(t) => {
                         inf_group.insert_unification(
-                            t.clone(),
+                            t,
                             expr_type,
                             "expression type and annotation must match",
                             &def.span,
@@ -45,38 +55,35 @@ impl<'a> SemDef<'a> for SemTable<'a> {
             }
             DefKind::Function { pars, expr } => {
                 let func_type = self.sem_func_def(&mut inf_group, pars, expr)?;
-                match annotation_type {
-                    Some(t) => inf_group.insert_unification(
+                if let Some(t) = annotation_type {
+                    inf_group.insert_unification(
                         t,
                         self.types
                             .get_type(expr)
                             .expect("function expression should have just had a time"),
                         "function expression type and annotation must match",
                         &def.span,
-                    ),
-                    None => (),
-                };
+                    );
+                }
                 func_type
             }
+            // The parser already makes a variable's annotation a ref.
             DefKind::Variable => match annotation_type {
                 Some(t) => t,
-                None => self.types.new_unknown(),
+                None => self.types.new_unknown_ref(),
             },
         };
         // *Note: lookup first. If it's already there, then instead of inserting, insert a unification.
         if let Some(ty) = self.types.get_type(def) {
             // TODO: Test that the 'ty' type is unknown (I think that's the only case where this is valid)
             // debug!("Recursive def type {} and sem'ed type {}", ty, node_type);
-            inf_group.insert_unification(
-                ty.clone(),
-                node_type,
-                "recursive definition's type",
-                &def.span,
-            );
+            inf_group.insert_unification(ty, node_type, "recursive definition's type", &def.span);
         } else {
             self.types.insert(def, node_type);
         }
-        self.types.solve_group(inf_group)?;
+        for error in self.types.solve_group(inf_group) {
+            self.record_error(error);
+        }
         Ok(())
     }
 }
@@ -84,7 +91,7 @@ impl<'a> SemDefHelpers<'a> for SemTable<'a> {
     fn sem_array_def(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
-        dims: &'a Vec<Expr>,
+        dims: &'a [Expr],
     ) -> SemResult<()> {
         for dim in dims {
             let expr_type = self.sem_expr(inf_group, dim)?;
@@ -101,25 +108,48 @@ impl<'a> SemDefHelpers<'a> for SemTable<'a> {
     fn sem_func_def(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
-        pars: &'a Vec<Par>,
+        pars: &'a [Par],
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         self.push_scope();
         let mut par_types = Vec::with_capacity(pars.len());
         for par in pars {
             // Insert all params in the current(new) scope and create a new unknown type for each.
-            let par_type = par
-                .type_
-                .as_ref()
-                .map(|t| t.into())
-                .unwrap_or_else(|| self.types.new_unknown());
-            self.insert_scope_binding(&par.id, par);
-            self.types.insert(par, par_type.clone());
+            let par_type = match &par.type_ {
+                Some(t) => {
+                    let res = self
+                        .annotation_type(t, &par.span)
+                        .map(|ty| self.types.new_given(ty, "type annotation", &par.span));
+                    self.poison_on_error(res)
+                }
+                None => self.types.new_unknown(),
+            };
+            // The scope is the function's own, so only another parameter can be there.
+            if let Some(previous) = self.insert_scope_binding(&par.id, par) {
+                self.record_error(SemanticError::Redefinition {
+                    what: "Parameter",
+                    id: par.id.clone(),
+                    span: par.span.clone(),
+                    previous: Box::new(previous.get_span()),
+                });
+            }
+            self.types.insert(par, par_type);
             par_types.push(par_type);
         }
         let expr_type = self.sem_expr(inf_group, expr)?;
         self.pop_scope();
-        let func_type = Type::new_multi_arg_func(par_types, expr_type);
+        let result_type = self
+            .types
+            .new_unknown_with_constraint(Constraints::disallow_array(
+                validity::FUNCTION_RETURNS_ARRAY,
+            ));
+        inf_group.insert_unification(
+            expr_type,
+            result_type,
+            "function result must not be an array",
+            &expr.span,
+        );
+        let func_type = self.types.new_multi_arg_func(par_types, expr_type);
         Ok(func_type)
     }
 }
@@ -127,12 +157,12 @@ trait SemDefHelpers<'a> {
     fn sem_array_def(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
-        dims: &'a Vec<Expr>,
+        dims: &'a [Expr],
     ) -> SemResult<()>;
     fn sem_func_def(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
-        pars: &'a Vec<Par>,
+        pars: &'a [Par],
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
 }
@@ -1,241 +1,306 @@
-use std::rc::Rc;
-
 // use log::debug;
 
 use super::types::inference::InferenceGroup;
-use super::types::Type;
+use super::types::{validity, Type, TypeId};
+use super::{patterns::SemPatterns, SemDefHelpers, SemanticError};
 use super::{sem_table::SemTable, SemResult};
-use super::{SemDefHelpers, SemanticError};
 use crate::parse::ast::expr::{
     ArrayAccess, Binop, Call, Dim, Expr, ExprKind, For, If, LetIn, Match, Pattern, PatternKind,
     Unop, While,
 };
+use crate::parse::ast::Span;
 use crate::pass::sem::types::inference::Constraints;
 
 pub trait SemExpr<'a> {
+    /// Errors are recorded rather than returned, the expression then has the poison type.
+    fn sem_expr(&mut self, inf_group: &mut InferenceGroup<'a>, expr: &'a Expr)
+        -> SemResult<TypeId>;
+}
+impl<'a> SemExpr<'a> for SemTable<'a> {
     fn sem_expr(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId> {
+        let depth = self.scope_depth();
+        let res = self.sem_expr_kind(inf_group, expr);
+        if res.is_err() {
+            // The scopes the failing expression pushed are still there.
+            self.truncate_scopes(depth);
+        }
+        let expr_type = self.poison_on_error(res);
+        self.types.insert(expr, expr_type);
+        Ok(expr_type)
+    }
 }
-impl<'a> SemExpr<'a> for SemTable<'a> {
-    fn sem_expr(
+
+impl<'a> SemExprHelpers<'a> for SemTable<'a> {
+    fn sem_expr_kind(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         use ExprKind::*;
-        let expr_type = match &expr.kind {
+        Ok(match &expr.kind {
             UnitLiteral => self.types.get_unit(),
-            IntLiteral(_) => self.types.get_int(),
-            FloatLiteral(_) => self.types.get_float(),
-            CharLiteral(_) => self.types.get_char(),
-            StringLiteral(_) => Type::new_known_array(self.types.get_char(), 1),
-            BoolLiteral(_) => self.types.get_bool(),
-            Tuple(expr_vec) => Type::new_tuple(
-                expr_vec
+            IntLiteral(_) => {
+                let int = self.types.get_int();
+                self.types.new_given(int, "integer literal", &expr.span)
+            }
+            FloatLiteral(_) => {
+                let float = self.types.get_float();
+                self.types.new_given(float, "float literal", &expr.span)
+            }
+            CharLiteral(_) => {
+                let char = self.types.get_char();
+                self.types.new_given(char, "char literal", &expr.span)
+            }
+            StringLiteral(_) => {
+                let string = self.types.new_known_array(self.types.get_char(), 1);
+                self.types.new_given(string, "string literal", &expr.span)
+            }
+            BoolLiteral(_) => {
+                let bool = self.types.get_bool();
+                self.types.new_given(bool, "boolean literal", &expr.span)
+            }
+            Tuple(expr_vec) => {
+                let types = expr_vec
                     .iter()
                     .map(|expr| self.sem_expr(inf_group, expr))
-                    .collect::<Result<Vec<_>, _>>()?,
-            ),
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.types.new_tuple(types)
+            }
             Unop(unop) => self.sem_unop(inf_group, unop, expr)?,
             Binop(binop) => self.sem_binop(inf_group, binop, expr)?,
-            Call(call) if call.args.len() == 0 => self.sem_constant_call(call, expr)?,
+            Call(call) if call.args.is_empty() => self.sem_constant_call(call, expr)?,
             Call(call) => self.sem_func_call(inf_group, call, expr)?,
             ConstrCall(call) => self.sem_constructor_call(inf_group, call, expr)?,
             ArrayAccess(array_access) => self.sem_array_access(inf_group, array_access, expr)?,
             Dim(dim) => self.sem_dim(inf_group, dim, expr)?,
-            New(annotation) => Type::new_ref(annotation.into()),
+            New(annotation) => {
+                let ty = self.annotation_type(annotation, &expr.span)?;
+                if let Type::Array { .. } = self.types.get(ty) {
+                    return Err(SemanticError::InvalidType {
+                        given: self.types.pretty(ty).to_string(),
+                        msg: validity::NEW_ARRAY,
+                        span: expr.span.clone(),
+                    });
+                }
+                self.types.new_ref(ty)
+            }
             LetIn(let_in) => self.sem_letin(inf_group, let_in)?,
             If(if_expr) => self.sem_if(inf_group, if_expr, expr)?,
             While(while_expr) => self.sem_while(inf_group, while_expr, expr)?,
             For(for_expr) => self.sem_for(inf_group, for_expr, expr)?,
             Match(match_expr) => self.sem_match(inf_group, match_expr, expr)?,
-        };
-        self.types.insert(expr, expr_type.clone());
-        Ok(expr_type)
+            // *Note: The parser has reported it already.
+            Error => self.types.get_poison(),
+        })
     }
-}
-
-impl<'a> SemExprHelpers<'a> for SemTable<'a> {
     fn sem_unop(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         unop: &'a Unop,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         let op_type = self.sem_expr(inf_group, &unop.operand)?;
+        let since = inf_group.len();
         use crate::parse::ast::expr::UnopKind::*;
-        match unop.op {
+        let result_type = match unop.op {
             Plus | Minus => {
                 let unknown_numeric = self
                     .types
                     .new_unknown_with_constraint(Constraints::allow_numeric());
                 inf_group.insert_unification(
-                    op_type.clone(),
+                    op_type,
                     unknown_numeric,
                     "unary '+/-' operand must be numeric",
                     &expr.span,
                 );
-                Ok(op_type)
+                unknown_numeric
+            }
+            PlusFlt | MinusFlt => {
+                inf_group.insert_unification(
+                    op_type,
+                    self.types.get_float(),
+                    "unary '+./-.' operand must be a float",
+                    &expr.span,
+                );
+                self.types.get_float()
             }
             Not => {
                 inf_group.insert_unification(
-                    op_type.clone(),
+                    op_type,
                     self.types.get_bool(),
                     "unary 'not' operand must be boolean",
                     &expr.span,
                 );
-                Ok(self.types.get_bool())
+                self.types.get_bool()
             }
             Deref => {
                 let inner = self.types.new_unknown();
-                let unknown_ref = Type::new_ref(inner.clone());
+                let unknown_ref = self.types.new_ref(inner);
                 inf_group.insert_unification(
-                    op_type.clone(),
-                    unknown_ref.clone(),
+                    op_type,
+                    unknown_ref,
                     "cannot dereference non-reference",
                     &expr.span,
                 );
-                Ok(inner)
+                inner
             }
             Delete => {
                 inf_group.insert_unification(
-                    op_type.clone(),
+                    op_type,
                     self.types.new_unknown_ref(),
                     "cannot delete non-reference",
                     &expr.span,
                 );
-                Ok(self.types.get_unit())
+                self.types.get_unit()
             }
-        }
+        };
+        Ok(self.operation_result(inf_group, since, result_type, &expr.span))
     }
     fn sem_binop(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         binop: &'a Binop,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         let lhs_type = self.sem_expr(inf_group, &binop.lhs)?;
         let rhs_type = self.sem_expr(inf_group, &binop.rhs)?;
+        let since = inf_group.len();
         use crate::parse::ast::expr::BinopKind::*;
-        match binop.op {
+        let result_type = match binop.op {
             Add | Sub | Mul | Div | Pow => {
                 let unknown_numeric = self
                     .types
                     .new_unknown_with_constraint(Constraints::allow_numeric());
                 inf_group.insert_unification(
-                    lhs_type.clone(),
-                    unknown_numeric.clone(),
+                    lhs_type,
+                    unknown_numeric,
                     "binary '+-*/ **' left operand must be numeric",
                     &expr.span,
                 );
                 inf_group.insert_unification(
-                    rhs_type.clone(),
-                    unknown_numeric.clone(),
+                    rhs_type,
+                    unknown_numeric,
                     "binary '+-*/ **' right operand must be numeric",
                     &expr.span,
                 );
-                // *Note: Consider whether this one should be inserted or not.
-                // inf_group.insert_unification(lhs_type.clone(), rhs_type.clone());
-                // *Note: Consider deciding what to return based on what is less unknown.
-                Ok(rhs_type)
+                unknown_numeric
+            }
+            AddFlt | SubFlt | MulFlt | DivFlt => {
+                inf_group.insert_unification(
+                    lhs_type,
+                    self.types.get_float(),
+                    "binary '+. -. *. /.' left operand must be a float",
+                    &expr.span,
+                );
+                inf_group.insert_unification(
+                    rhs_type,
+                    self.types.get_float(),
+                    "binary '+. -. *. /.' right operand must be a float",
+                    &expr.span,
+                );
+                self.types.get_float()
             }
             Mod => {
                 inf_group.insert_unification(
-                    lhs_type.clone(),
+                    lhs_type,
                     self.types.get_int(),
                     "mod left operand must be an integer",
                     &expr.span,
                 );
                 inf_group.insert_unification(
-                    rhs_type.clone(),
+                    rhs_type,
                     self.types.get_int(),
                     "mod right operand must be an integer",
                     &expr.span,
                 );
-                Ok(self.types.get_int())
+                self.types.get_int()
             }
             NatEq | NatNotEq | StrEq | StrNotEq => {
                 let unknown_non_array_non_func = self
                     .types
                     .new_unknown_with_constraint(Constraints::disallow_array_and_func());
                 inf_group.insert_unification(
-                    lhs_type.clone(),
-                    unknown_non_array_non_func.clone(),
+                    lhs_type,
+                    unknown_non_array_non_func,
                     "cannot equality compare arrays or functions",
                     &expr.span,
                 );
                 inf_group.insert_unification(
-                    rhs_type.clone(),
-                    unknown_non_array_non_func.clone(),
+                    rhs_type,
+                    unknown_non_array_non_func,
                     "cannot equality compare arrays or functions",
                     &expr.span,
                 );
-                Ok(self.types.get_bool())
+                self.types.get_bool()
             }
             Lt | Gt | LEq | GEq => {
                 let unknown_comparable = self
                     .types
                     .new_unknown_with_constraint(Constraints::allow_comparables());
                 inf_group.insert_unification(
-                    lhs_type.clone(),
-                    unknown_comparable.clone(),
+                    lhs_type,
+                    unknown_comparable,
                     "only int/float/char are ordered",
                     &expr.span,
                 );
                 inf_group.insert_unification(
-                    rhs_type.clone(),
-                    unknown_comparable.clone(),
+                    rhs_type,
+                    unknown_comparable,
                     "only int/float/char are ordered",
                     &expr.span,
                 );
-                Ok(self.types.get_bool())
+                self.types.get_bool()
             }
             And | Or => {
                 inf_group.insert_unification(
-                    lhs_type.clone(),
+                    lhs_type,
                     self.types.get_bool(),
                     "'&&, ||' operands must be booleans",
                     &expr.span,
                 );
                 inf_group.insert_unification(
-                    rhs_type.clone(),
+                    rhs_type,
                     self.types.get_bool(),
                     "'&&, ||' operands must be booleans",
                     &expr.span,
                 );
-                Ok(self.types.get_bool())
+                self.types.get_bool()
             }
-            Semicolon => Ok(rhs_type),
+            Semicolon => return Ok(rhs_type),
             Assign => {
                 let unknown = self.types.new_unknown();
-                let unknown_ref = Type::new_ref(unknown.clone());
+                let unknown_ref = self.types.new_ref(unknown);
                 inf_group.insert_unification(
-                    lhs_type.clone(),
+                    lhs_type,
                     unknown_ref,
                     "lhs of ':=' operator must be a reference",
                     &expr.span,
                 );
                 inf_group.insert_unification(
-                    rhs_type.clone(),
+                    rhs_type,
                     unknown,
                     "rhs of ':=' operator must be same as pointed at by lhs",
                     &expr.span,
                 );
-                Ok(self.types.get_unit())
+                self.types.get_unit()
             }
-        }
+        };
+        Ok(self.operation_result(inf_group, since, result_type, &expr.span))
     }
-    fn sem_constant_call(&mut self, call: &'a Call, expr: &'a Expr) -> SemResult<Rc<Type>> {
-        let called_node = self
-            .lookup(&call.id)
-            .ok_or_else(|| SemanticError::LookupError {
-                id: call.id.clone(),
-                span: expr.span.clone(),
-            })?;
-        let called_type = self.types.get_node_type_or_instantiation(&called_node);
+    fn sem_constant_call(&mut self, call: &'a Call, expr: &'a Expr) -> SemResult<TypeId> {
+        let called_node =
+            self.lookup_use(&call.id, expr)
+                .ok_or_else(|| SemanticError::LookupError {
+                    id: call.id.clone(),
+                    span: expr.span.clone(),
+                })?;
+        let called_type = self
+            .types
+            .get_node_type_or_instantiation(&called_node, expr);
         Ok(called_type)
     }
     fn sem_func_call(
@@ -243,14 +308,16 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
         inf_group: &mut InferenceGroup<'a>,
         call: &'a Call,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
-        let called_node = self
-            .lookup(&call.id)
-            .ok_or_else(|| SemanticError::LookupError {
-                id: call.id.clone(),
-                span: expr.span.clone(),
-            })?;
-        let called_type = self.types.get_node_type_or_instantiation(&called_node);
+    ) -> SemResult<TypeId> {
+        let called_node =
+            self.lookup_use(&call.id, expr)
+                .ok_or_else(|| SemanticError::LookupError {
+                    id: call.id.clone(),
+                    span: expr.span.clone(),
+                })?;
+        let called_type = self
+            .types
+            .get_node_type_or_instantiation(&called_node, expr);
         let arg_types = call
             .args
             .iter()
@@ -258,12 +325,15 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
             .collect::<SemResult<Vec<_>>>()?;
         // *NOTE: Think about trying to apply the argument_types to the called_type, for performance perhaps.
         let expr_type = self.types.new_unknown();
+        let since = inf_group.len();
         inf_group.insert_unification(
             called_type,
-            Type::new_multi_arg_func(arg_types, expr_type.clone()),
+            self.types.new_multi_arg_func(arg_types, expr_type),
             "function call must match function signature",
             &expr.span,
         );
+        // *Note: The result is only unified after the arguments, it is still fresh on failure.
+        inf_group.poison_on_failure(since, expr_type);
         Ok(expr_type)
     }
     fn sem_constructor_call(
@@ -271,13 +341,13 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
         inf_group: &mut InferenceGroup<'a>,
         call: &'a Call,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
-        let called_node = self
-            .lookup(&call.id)
-            .ok_or_else(|| SemanticError::LookupError {
-                id: call.id.clone(),
-                span: expr.span.clone(),
-            })?;
+    ) -> SemResult<TypeId> {
+        let called_node =
+            self.lookup_use(&call.id, expr)
+                .ok_or_else(|| SemanticError::LookupError {
+                    id: call.id.clone(),
+                    span: expr.span.clone(),
+                })?;
         let constructor_type = self
             .types
             .get_node_type(&called_node)
@@ -288,13 +358,15 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
             .map(|arg| self.sem_expr(inf_group, arg))
             .collect::<SemResult<Vec<_>>>()?;
         let expr_type = self.types.new_unknown();
+        let since = inf_group.len();
         // *Note: partial constructor call is allowed. That's why we don't unify with the custom type directly.
         inf_group.insert_unification(
             constructor_type,
-            Type::new_multi_arg_func(arg_types, expr_type.clone()),
+            self.types.new_multi_arg_func(arg_types, expr_type),
             "constructor call must match constructor signature",
             &expr.span,
         );
+        inf_group.poison_on_failure(since, expr_type);
         Ok(expr_type)
     }
     fn sem_array_access(
@@ -302,9 +374,9 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
         inf_group: &mut InferenceGroup<'a>,
         array_access: &'a ArrayAccess,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         let array_node =
-            self.lookup(&array_access.id)
+            self.lookup_use(&array_access.id, expr)
                 .ok_or_else(|| SemanticError::LookupError {
                     id: array_access.id.clone(),
                     span: expr.span.clone(),
@@ -318,34 +390,40 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
             .iter()
             .map(|index| self.sem_expr(inf_group, index))
             .collect::<SemResult<Vec<_>>>()?;
-        let contained_type = self.types.new_unknown();
+        let contained_type = self
+            .types
+            .new_unknown_with_constraint(Constraints::disallow_array(validity::ARRAY_OF_ARRAYS));
         inf_group.insert_unification(
             called_array_type,
-            Type::new_known_array(contained_type.clone(), index_types.len() as u32),
+            self.types
+                .new_known_array(contained_type, index_types.len() as u32),
             "array access must match array signature",
             &expr.span,
         );
-        Ok(Type::new_ref(contained_type))
+        Ok(self.types.new_ref(contained_type))
     }
     fn sem_dim(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         dim: &'a Dim,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
-        let array_node = self
-            .lookup(&dim.id)
-            .ok_or_else(|| SemanticError::LookupError {
-                id: dim.id.clone(),
-                span: expr.span.clone(),
-            })?;
+    ) -> SemResult<TypeId> {
+        let array_node =
+            self.lookup_use(&dim.id, expr)
+                .ok_or_else(|| SemanticError::LookupError {
+                    id: dim.id.clone(),
+                    span: expr.span.clone(),
+                })?;
         let called_array_type = self
             .types
             .get_node_type(&array_node)
             .expect("array node should have a type associated with it");
+        let contained_type = self
+            .types
+            .new_unknown_with_constraint(Constraints::disallow_array(validity::ARRAY_OF_ARRAYS));
         inf_group.insert_unification(
             called_array_type,
-            Type::new_bounded_array(self.types.new_unknown(), dim.dim as u32),
+            self.types.new_bounded_array(contained_type, dim.dim as u32),
             "dim call must be on an array that has at least as meany dimensions as the call",
             &expr.span,
         );
@@ -355,7 +433,7 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         letin: &'a LetIn,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         self.push_scope();
         self.sem_letdef(&letin.letdef)?;
         let expr_type = self.sem_expr(inf_group, &letin.expr)?;
@@ -367,10 +445,10 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
         inf_group: &mut InferenceGroup<'a>,
         if_expr: &'a If,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         let cond_type = self.sem_expr(inf_group, &if_expr.cond)?;
         inf_group.insert_unification(
-            cond_type.clone(),
+            cond_type,
             self.types.get_bool(),
             "if expression condition must be a boolean",
             &expr.span,
@@ -384,7 +462,7 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
             .unwrap_or(self.types.get_unit());
         inf_group.insert_unification(
             then_type,
-            else_type.clone(),
+            else_type,
             "if expression branches must be of the same type",
             &expr.span,
         );
@@ -395,10 +473,10 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
         inf_group: &mut InferenceGroup<'a>,
         while_expr: &'a While,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         let cond_type = self.sem_expr(inf_group, &while_expr.cond)?;
         inf_group.insert_unification(
-            cond_type.clone(),
+            cond_type,
             self.types.get_bool(),
             "while expression condition must be a boolean",
             &expr.span,
@@ -417,7 +495,7 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
         inf_group: &mut InferenceGroup<'a>,
         for_expr: &'a For,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         let init_type = self.sem_expr(inf_group, &for_expr.from)?;
         let end_type = self.sem_expr(inf_group, &for_expr.to)?;
         inf_group.insert_unification(
@@ -450,28 +528,66 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
         inf_group: &mut InferenceGroup<'a>,
         match_expr: &'a Match,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>> {
+    ) -> SemResult<TypeId> {
         let to_match_type = self.sem_expr(inf_group, &match_expr.to_match)?;
         let return_type = self.types.new_unknown();
+        let mut patterns_ok = true;
         for clause in &match_expr.clauses {
             self.push_scope();
-            self.sem_pattern(inf_group, &clause.pattern, to_match_type.clone())?;
+            // The clause's body is still checked, with what its pattern binds poisoned.
+            if let Err(error) = self.sem_pattern(inf_group, &clause.pattern, to_match_type) {
+                self.record_error(error);
+                self.poison_bindings(&clause.pattern);
+                patterns_ok = false;
+            }
             let clause_type = self.sem_expr(inf_group, &clause.expr)?;
             inf_group.insert_unification(
                 clause_type,
-                return_type.clone(),
+                return_type,
                 "match expression clauses must all have the same type",
                 &expr.span,
             );
             self.pop_scope();
         }
+        // *Note: Exhaustiveness is only meaningful for patterns that check.
+        if patterns_ok {
+            self.check_match(match_expr, expr);
+        }
         Ok(return_type)
     }
+    fn operation_result(
+        &mut self,
+        inf_group: &mut InferenceGroup<'a>,
+        since: usize,
+        result_type: TypeId,
+        span: &'a Span,
+    ) -> TypeId {
+        // *Note: A fresh unknown, so that only the operation's own uses are poisoned.
+        let result = self.types.new_unknown();
+        inf_group.poison_on_failure(since, result);
+        inf_group.insert_unification(result, result_type, "result of the operation", span);
+        result
+    }
+    fn poison_bindings(&mut self, pattern: &'a Pattern) {
+        match &pattern.kind {
+            PatternKind::IdLower(id) => {
+                self.insert_scope_binding(id, pattern);
+                if self.types.get_type(pattern).is_none() {
+                    let poison = self.types.get_poison();
+                    self.types.insert(pattern, poison);
+                }
+            }
+            PatternKind::Tuple(patterns) | PatternKind::IdUpper { args: patterns, .. } => {
+                patterns.iter().for_each(|p| self.poison_bindings(p))
+            }
+            _ => (),
+        }
+    }
     fn sem_pattern(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         pattern: &'a Pattern,
-        to_match_type: Rc<Type>,
+        to_match_type: TypeId,
     ) -> SemResult<()> {
         use PatternKind::*;
         match &pattern.kind {
@@ -495,7 +611,7 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
                         "char literal pattern must match a char",
                     ),
                     StringLiteral(_) => (
-                        Type::new_known_array(self.types.get_char(), 1),
+                        self.types.new_known_array(self.types.get_char(), 1),
                         "string literal pattern must match a string",
                     ),
                     _ => unreachable!(),
@@ -508,31 +624,29 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
             }
             IdUpper { id, args } => {
                 let constructor_node =
-                    self.lookup(id).ok_or_else(|| SemanticError::LookupError {
-                        id: id.clone(),
-                        span: pattern.span.clone(),
-                    })?;
+                    self.lookup_use(id, pattern)
+                        .ok_or_else(|| SemanticError::LookupError {
+                            id: id.clone(),
+                            span: pattern.span.clone(),
+                        })?;
                 let constructor_type = self
                     .types
                     .get_node_type(&constructor_node)
                     .expect("constructor node should have a type associated with it");
-                if let Type::Func { lhs, rhs } = &*constructor_type {
-                    let mut constr_param_types = vec![lhs.clone()];
-                    let constr_ret_type = loop {
-                        if let Type::Func { lhs, rhs } = &**rhs {
-                            constr_param_types.push(lhs.clone());
-                            if !matches!(&**rhs, Type::Func { .. }) {
-                                break rhs.clone();
-                            }
-                        } else {
-                            break rhs.clone();
-                        }
-                    };
+                if let Type::Func { lhs, rhs } = *self.types.get(constructor_type) {
+                    let mut constr_param_types = vec![lhs];
+                    let mut constr_ret_type = rhs;
+                    while let Type::Func { lhs, rhs } = *self.types.get(constr_ret_type) {
+                        constr_param_types.push(lhs);
+                        constr_ret_type = rhs;
+                    }
                     if constr_param_types.len() != args.len() {
-                        return Err(SemanticError::GeneralError {
-                            msg: format!("partial application of constructor {} in match patterns is not allowed", id),
-                            span: pattern.span.clone(),
-                        });
+                        return Err(constructor_arity_error(
+                            id,
+                            constr_param_types.len(),
+                            args.len(),
+                            &pattern.span,
+                        ));
                     }
                     for (arg, param_type) in args.iter().zip(constr_param_types) {
                         self.sem_pattern(inf_group, arg, param_type)?;
@@ -543,12 +657,9 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
                         "constructor pattern must match the type of the matched expression",
                         &pattern.span,
                     );
-                } else if matches!(&*constructor_type, Type::Custom { .. }) {
+                } else if matches!(self.types.get(constructor_type), Type::Custom { .. }) {
                     if !args.is_empty() {
-                        return Err(SemanticError::GeneralError { 
-                            msg: format!("constructor {} invoked with arguments in match pattern, but takes none", id),
-                            span: pattern.span.clone(),
-                        })
+                        return Err(constructor_arity_error(id, 0, args.len(), &pattern.span));
                     }
                     inf_group.insert_unification(
                         to_match_type,
@@ -556,12 +667,11 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
                         "constructor pattern must match the type of the matched expression",
                         &pattern.span,
                     )
-                } else {    
-                    return Err(SemanticError::GeneralError {
-                        msg: format!(
-                            "{} type signature is not a function (constructors are functions)",
-                            id
-                        ),
+                } else {
+                    return Err(SemanticError::InvalidConstructorPattern {
+                        id: id.clone(),
+                        msg: "type signature is not a function (constructors are functions)"
+                            .to_string(),
                         span: pattern.span.clone(),
                     });
                 }
@@ -570,10 +680,10 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
                 let mut elem_types = Vec::new();
                 for elem in elems {
                     let elem_type = self.types.new_unknown();
-                    self.sem_pattern(inf_group, elem, elem_type.clone())?;
+                    self.sem_pattern(inf_group, elem, elem_type)?;
                     elem_types.push(elem_type);
                 }
-                let tuple_type = Type::new_tuple(elem_types);
+                let tuple_type = self.types.new_tuple(elem_types);
                 inf_group.insert_unification(
                     to_match_type,
                     tuple_type,
@@ -587,76 +697,114 @@ impl<'a> SemExprHelpers<'a> for SemTable<'a> {
 }
 
 trait SemExprHelpers<'a> {
+    fn sem_expr_kind(
+        &mut self,
+        inf_group: &mut InferenceGroup<'a>,
+        expr: &'a Expr,
+    ) -> SemResult<TypeId>;
     fn sem_unop(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         unop: &'a Unop,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_binop(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         binop: &'a Binop,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
-    fn sem_constant_call(&mut self, call: &'a Call, expr: &'a Expr) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
+    fn sem_constant_call(&mut self, call: &'a Call, expr: &'a Expr) -> SemResult<TypeId>;
     fn sem_func_call(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         call: &'a Call,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_constructor_call(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         call: &'a Call,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_array_access(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         array_access: &'a ArrayAccess,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_dim(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         dim: &'a Dim,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_letin(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         letin: &'a LetIn,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_if(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         if_expr: &'a If,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_while(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         while_expr: &'a While,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_for(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         for_expr: &'a For,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_match(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         match_expr: &'a Match,
         expr: &'a Expr,
-    ) -> SemResult<Rc<Type>>;
+    ) -> SemResult<TypeId>;
     fn sem_pattern(
         &mut self,
         inf_group: &mut InferenceGroup<'a>,
         pattern: &'a Pattern,
-        to_match_type: Rc<Type>,
+        to_match_type: TypeId,
     ) -> SemResult<()>;
+    /// Binds what `pattern` binds that has no type yet to the poison type, after it failed.
+    fn poison_bindings(&mut self, pattern: &'a Pattern);
+    /// The type of an operation that is `result_type` unless the unifications inserted since
+    /// there were `since` of them fail, in which case it is poisoned.
+    fn operation_result(
+        &mut self,
+        inf_group: &mut InferenceGroup<'a>,
+        since: usize,
+        result_type: TypeId,
+        span: &'a Span,
+    ) -> TypeId;
+}
+
+/// A constructor pattern given `given` arguments, when the constructor takes `takes`.
+fn constructor_arity_error(id: &str, takes: usize, given: usize, span: &Span) -> SemanticError {
+    let msg = if given < takes {
+        format!(
+            "given {} of its {} arguments, constructors can't be partially applied in patterns",
+            given, takes
+        )
+    } else {
+        format!(
+            "takes {} argument{}, but is given {}",
+            takes,
+            if takes == 1 { "" } else { "s" },
+            given
+        )
+    };
+    SemanticError::InvalidConstructorPattern {
+        id: id.to_string(),
+        msg,
+        span: span.clone(),
+    }
 }
@@ -1,78 +1,142 @@
+pub mod builtins;
 mod def;
 mod expr;
-mod sem_table;
-mod types;
-
-use std::rc::Rc;
+mod patterns;
+pub mod references;
+pub mod sem_table;
+pub mod types;
 
+use crate::diagnostic::{
+    codes::{self, Code},
+    Diagnostic, Errors, PhaseError,
+};
 use crate::parse::ast::{
-    def::{DefKind, Definition, Letdef, Typedef},
+    data_map::NodeRefInner,
+    def::{Def, DefKind, Definition, Letdef, Typedef},
+    expr::{Expr, ExprKind},
     Program, Span,
 };
 use thiserror::Error;
 
 use self::{
     def::SemDef,
+    expr::SemExpr,
     sem_table::SemTable,
-    types::{inference::InfererHelpers, Type},
+    types::{
+        inference::{Blame, Inferer, InfererHelpers},
+        scheme::TypeScheme,
+        Type, TypeId,
+    },
 };
 
-pub fn sem<'a>(ast: &'a Program) -> SemResult<SemTable<'a>> {
+/// Checks the whole program, even past its first error.
+/// In strict mode, numeric types that nothing pins down are defaulted to int (see `SemTable::strict`).
+pub fn sem<'a>(ast: &'a Program, strict: bool) -> Result<SemTable<'a>, SemanticErrors> {
+    let (sem_table, errors) = sem_recovering(ast, strict);
+    errors.map(|_| sem_table)
+}
+/// Checks the whole program, even past errors. Returns the table, with the warnings found
+/// along the way, and every error found.
+pub fn sem_recovering<'a>(
+    ast: &'a Program,
+    strict: bool,
+) -> (SemTable<'a>, Result<(), SemanticErrors>) {
     let mut sem_table = SemTable::new(ast);
+    sem_table.strict = strict;
     for def in &ast.definitions {
-        match def {
-            Definition::Let(letdef) => sem_table.sem_letdef(letdef)?,
-            Definition::Type(typedef) => sem_table.sem_typedef(typedef)?,
+        // The bindings of a failed definition are kept, so that its uses don't fail too.
+        if let Err(error) = sem_table.sem_top_level_definition(def) {
+            sem_table.record_error(error);
         }
     }
-    Ok(sem_table)
+    let errors = sem_table.take_errors();
+    (sem_table, errors)
+}
+/// Checks top level input one piece at a time, on top of everything checked before it.
+pub trait SemIncremental<'a> {
+    /// On error, the bindings the definition made are undone.
+    fn sem_definition(&mut self, def: &'a Definition) -> Result<(), SemanticErrors>;
+    /// Infers the type of an expression in the root scope.
+    fn sem_top_level_expr(&mut self, expr: &'a Expr) -> Result<TypeId, SemanticErrors>;
+}
+impl<'a> SemIncremental<'a> for SemTable<'a> {
+    fn sem_definition(&mut self, def: &'a Definition) -> Result<(), SemanticErrors> {
+        let checkpoint = self.checkpoint();
+        if let Err(error) = self.sem_top_level_definition(def) {
+            self.record_error(error);
+        }
+        let res = self.take_errors();
+        if res.is_err() {
+            self.rollback(checkpoint);
+        }
+        res
+    }
+    fn sem_top_level_expr(&mut self, expr: &'a Expr) -> Result<TypeId, SemanticErrors> {
+        let mut inf_group = self.new_inference_group();
+        let ty = self.sem_expr(&mut inf_group, expr)?;
+        for error in self.types.solve_group(inf_group) {
+            self.record_error(error);
+        }
+        self.take_errors()?;
+        Ok(ty)
+    }
 }
 trait SemDefHelpers<'a> {
+    fn sem_top_level_definition(&mut self, def: &'a Definition) -> SemResult<()>;
     fn sem_letdef(&mut self, letdef: &'a Letdef) -> SemResult<()>;
+    fn sem_letdef_defs(&mut self, letdef: &'a Letdef);
+    /// Resolves the numeric unknowns `def_type` would be generic in to int, with a warning.
+    fn default_numerics(&mut self, def: &'a Def, def_type: TypeId, level: u32);
     fn sem_typedef(&mut self, typedef: &'a Typedef) -> SemResult<()>;
 }
 impl<'a> SemDefHelpers<'a> for SemTable<'a> {
+    fn sem_top_level_definition(&mut self, def: &'a Definition) -> SemResult<()> {
+        match def {
+            Definition::Let(letdef) => self.sem_letdef(letdef),
+            Definition::Type(typedef) => self.sem_typedef(typedef),
+            // Already reported as a syntax error.
+            Definition::Error(_) => Ok(()),
+        }
+    }
     fn sem_typedef(&mut self, typedef: &'a Typedef) -> SemResult<()> {
-        for tdef in &typedef.tdefs {
-            self.insert_scope_binding(&tdef.id, tdef);
-            // if let Some(previous_node) = self.insert_scope_binding(&tdef.id, tdef) {
-            //     return Err(SemanticError::GeneralError {
-            //         msg: format!(
-            //             "Type {} already defined at {}",
-            //             tdef.id,
-            //             previous_node.get_span().start
-            //         ),
-            //         span: tdef.span.clone(),
-            //     });
-            // }
-            self.types.insert(tdef, Type::new_custom(tdef.id.clone()));
+        // *Note: A type may shadow an earlier one of the same name, but not one of its own group.
+        for (i, tdef) in typedef.tdefs.iter().enumerate() {
+            if let Some(previous) = typedef.tdefs[..i].iter().find(|t| t.id == tdef.id) {
+                self.record_error(SemanticError::Redefinition {
+                    what: "Type",
+                    id: tdef.id.clone(),
+                    span: tdef.span.clone(),
+                    previous: Box::new(previous.span.clone()),
+                });
+            }
+            self.insert_type_binding(&tdef.id, tdef);
+            let tdef_type = self.types.new_custom(tdef);
+            self.types.insert(tdef, tdef_type);
         }
         for tdef in &typedef.tdefs {
             for constr in &tdef.constrs {
                 // *Done(?): Fix this to work with constructors that take no arguments
-                self.insert_scope_binding(&constr.id, constr);
+                if let Some(previous) = self.insert_scope_binding(&constr.id, constr) {
+                    self.record_error(SemanticError::Redefinition {
+                        what: "Constructor",
+                        id: constr.id.clone(),
+                        span: constr.span.clone(),
+                        previous: Box::new(previous.get_span()),
+                    });
+                }
+                self.insert_constr_tdef(constr, tdef);
                 let tdef_type = self
                     .types
                     .get_type(tdef)
                     .expect("type should have just been inserted");
-                // if let Some(previous_node) = self.insert_scope_binding(&constr.id, constr) {
-                //     return Err(SemanticError::GeneralError {
-                //         msg: format!(
-                //             "Constructor {} already defined at {}",
-                //             constr.id,
-                //             previous_node.get_span().start
-                //         ),
-                //         span: constr.span.clone(),
-                //     });
-                // }
-                if constr.types.len() > 0 {
-                    self.types.insert(
-                        constr,
-                        Type::new_multi_arg_func(
-                            constr.types.iter().map(|t| t.into()).collect(),
-                            tdef_type,
-                        ),
-                    )
+                if !constr.types.is_empty() {
+                    let mut arg_types = Vec::with_capacity(constr.types.len());
+                    for t in &constr.types {
+                        let res = self.annotation_type(t, &constr.span);
+                        arg_types.push(self.poison_on_error(res));
+                    }
+                    let constr_type = self.types.new_multi_arg_func(arg_types, tdef_type);
+                    self.types.insert(constr, constr_type)
                 } else {
                     self.types.insert(constr, tdef_type)
                 }
@@ -81,6 +145,74 @@ impl<'a> SemDefHelpers<'a> for SemTable<'a> {
         Ok(())
     }
     fn sem_letdef(&mut self, letdef: &'a Letdef) -> SemResult<()> {
+        for (i, def) in letdef.defs.iter().enumerate() {
+            if let Some(previous) = letdef.defs[..i].iter().find(|d| d.id == def.id) {
+                self.record_error(SemanticError::Redefinition {
+                    what: "Definition",
+                    id: def.id.clone(),
+                    span: def.span.clone(),
+                    previous: Box::new(previous.span.clone()),
+                });
+            }
+        }
+        self.types.enter_level();
+        self.sem_letdef_defs(letdef);
+        self.types.exit_level();
+        // *Note: A recursive group is solved monomorphically, its members only become
+        // generic once all of them have been sem'ed.
+        let level = self.types.level();
+        for def in &letdef.defs {
+            let def_type = self
+                .types
+                .get_type(def)
+                .expect("should have a type after sem");
+            if !is_generalizable(def) {
+                self.types.lower_levels(def_type, level);
+                continue;
+            }
+            if self.strict {
+                self.default_numerics(def, def_type, level);
+            }
+            let scheme =
+                TypeScheme::generalize(&self.types, def_type, |id| self.types.level_of(id) > level);
+            if scheme.is_generic() {
+                self.types.insert_scheme(def, scheme);
+            }
+        }
+        Ok(())
+    }
+    fn default_numerics(&mut self, def: &'a Def, def_type: TypeId, level: u32) {
+        let mut numerics = Vec::new();
+        self.types.visit_unknowns(def_type, &mut |id| {
+            if self.types.level_of(id) > level && self.types.constraints_of(id).is_numeric() {
+                numerics.push(id);
+            }
+        });
+        if numerics.is_empty() {
+            return;
+        }
+        let mut inf_group = self.new_inference_group();
+        for id in numerics {
+            let unknown = self.types.intern(Type::Unknown(id));
+            inf_group.insert_unification(
+                unknown,
+                self.types.get_int(),
+                "numeric type defaulted to int",
+                &def.span,
+            );
+        }
+        for error in self.types.solve_group(inf_group) {
+            self.record_error(error);
+        }
+        self.warnings.push(SemanticWarning::NumericDefault {
+            id: def.id.clone(),
+            span: def.span.clone(),
+        });
+    }
+    /// Sems the definitions one `let` deeper, their unknowns are generic unless they
+    /// end up reachable from the environment.
+    /// A definition that fails gets the poison type, and the rest of the group is still checked.
+    fn sem_letdef_defs(&mut self, letdef: &'a Letdef) {
         if letdef.rec {
             for def in &letdef.defs {
                 // *DONE: Insert an unknown type for each def as well I think
@@ -90,85 +222,276 @@ impl<'a> SemDefHelpers<'a> for SemTable<'a> {
             }
         }
         for def in &letdef.defs {
-            self.sem_def(def)?;
+            let depth = self.scope_depth();
+            if let Err(error) = self.sem_def(def) {
+                self.record_error(error);
+                // The scopes the failing definition pushed are still there.
+                self.truncate_scopes(depth);
+                if self.types.get_type(def).is_none() {
+                    let poison = self.types.get_poison();
+                    self.types.insert(def, poison);
+                }
+            }
         }
         if !letdef.rec {
             for def in &letdef.defs {
                 self.insert_scope_binding(&def.id, def);
             }
         }
-        // if it's recursive and any of the definitions is not fully known
-        // (can be relaxed later on perhaps)
-        if letdef.rec {
-            if let Some(def) = letdef.defs.iter().find(|def| {
-                self.types
-                    .get_type(*def)
-                    .map(|t| !self.types.deep_resolve_type(t).is_fully_known())
-                    .unwrap_or(false)
-            }) {
-                return Err(SemanticError::GeneralError {
-                    msg: format!(
-                        "Recursive generic def is not (yet) supported (of type {})",
-                        self.types
-                            .deep_resolve_type(self.types.get_type(def).unwrap())
-                    ),
-                    // msg: "Recursive generic definitions are not (yet) allowed".to_string(),
-                    span: def.span.clone(),
-                });
-            }
-        }
-        for def in &letdef.defs {
-            let def_type = self
-                .types
-                .get_type(def)
-                .expect("should have a type after sem");
-            let def_type = self.types.deep_resolve_type(def_type);
-            if !def_type.is_fully_known()
-                && !matches!(def.kind, DefKind::Variable | DefKind::Array { .. })
-            {
-                self.types.mark_generic(def);
-            }
-        }
-        Ok(())
     }
 }
 
-type SemResult<T> = Result<T, SemanticError>;
+/// The value restriction: mutable definitions, and constants that are not syntactic values
+/// (their evaluation may create a ref), keep the unknowns of their type monomorphic.
+fn is_generalizable(def: &Def) -> bool {
+    match &def.kind {
+        DefKind::Function { .. } => true,
+        DefKind::Const { expr } => is_syntactic_value(expr),
+        DefKind::Variable | DefKind::Array { .. } => false,
+    }
+}
+fn is_syntactic_value(expr: &Expr) -> bool {
+    use ExprKind::*;
+    match &expr.kind {
+        UnitLiteral | IntLiteral(_) | FloatLiteral(_) | CharLiteral(_) | BoolLiteral(_) => true,
+        Call(call) => call.args.is_empty(),
+        ConstrCall(call) => call.args.iter().all(is_syntactic_value),
+        Tuple(exprs) => exprs.iter().all(is_syntactic_value),
+        _ => false,
+    }
+}
+
+pub type SemResult<T> = Result<T, SemanticError>;
+
+/// The sides of a unification that failed, boxed to keep `SemanticError` small.
+#[derive(Debug)]
+pub struct Sides {
+    pub lhs: String,
+    pub rhs: String,
+    pub lhs_resolved: String,
+    pub rhs_resolved: String,
+    /// Why each side has the type it has, back to where it came from.
+    pub lhs_blame: Blame,
+    pub rhs_blame: Blame,
+}
 
 #[derive(Error, Debug)]
 pub enum SemanticError {
     #[error("Identifier {} not found (at {})", id, span)]
     LookupError { id: String, span: Span },
     #[error(
-        "{}: {} = {} ({} {} at {})",
+        "{}: {} = {} ({} {} at {}), expected {} because of {}, found {} because of {}",
         msg,
-        lhs_resolved,
-        rhs_resolved,
-        if lhs != lhs_resolved || rhs != rhs_resolved {
-            format!("originally {} = {}", lhs, rhs)
+        sides.lhs_resolved,
+        sides.rhs_resolved,
+        if sides.lhs != sides.lhs_resolved || sides.rhs != sides.rhs_resolved {
+            format!("originally {} = {}", sides.lhs, sides.rhs)
         } else {
             "".to_string()
         },
         unification_reason,
         span,
+        sides.lhs_resolved,
+        sides.lhs_blame,
+        sides.rhs_resolved,
+        sides.rhs_blame,
     )]
     InferenceError {
         msg: String,
-        lhs: Rc<Type>,
-        rhs: Rc<Type>,
-        lhs_resolved: Rc<Type>,
-        rhs_resolved: Rc<Type>,
         span: Span,
         unification_reason: String,
+        sides: Box<Sides>,
+    },
+    #[error("Type {} not found (at {})", id, span)]
+    UnknownType { id: String, span: Span },
+    #[error("Invalid type given: {} ({}) (at {})", given, msg, span)]
+    InvalidType {
+        given: String,
+        msg: &'static str,
+        span: Span,
+    },
+    #[error("Invalid pattern for constructor {}: {} (at {})", id, msg, span)]
+    InvalidConstructorPattern { id: String, msg: String, span: Span },
+    #[error(
+        "{} {} is defined twice (at {}), first at {}",
+        what,
+        id,
+        span,
+        previous
+    )]
+    Redefinition {
+        what: &'static str,
+        id: String,
+        span: Span,
+        /// Boxed to keep `SemanticError` small.
+        previous: Box<Span>,
     },
-    #[error("{} (at {})", msg, span)]
-    GeneralError { msg: String, span: Span },
     // #[error("Type mismatch: expected {}, got {} ({})", expected, given, msg)]
     // TypeMismatch {
     //     expected: Type,
     //     given: Type,
     //     msg: &'static str,
     // },
-    // #[error("Invalid type given: {} ({})", given, msg)]
-    // InvalidType { given: Type, msg: &'static str },
+}
+
+impl SemanticError {
+    pub fn span(&self) -> &Span {
+        use SemanticError::*;
+        match self {
+            LookupError { span, .. }
+            | InferenceError { span, .. }
+            | UnknownType { span, .. }
+            | InvalidType { span, .. }
+            | InvalidConstructorPattern { span, .. }
+            | Redefinition { span, .. } => span,
+        }
+    }
+    pub fn code(&self) -> &'static Code {
+        use SemanticError::*;
+        match self {
+            LookupError { .. } => &codes::UNKNOWN_IDENTIFIER,
+            InferenceError { .. } => &codes::TYPE_MISMATCH,
+            UnknownType { .. } => &codes::UNKNOWN_TYPE,
+            InvalidType { .. } => &codes::INVALID_TYPE,
+            InvalidConstructorPattern { .. } => &codes::INVALID_CONSTRUCTOR_PATTERN,
+            Redefinition { .. } => &codes::REDEFINITION,
+        }
+    }
+}
+
+impl From<&SemanticError> for Diagnostic {
+    fn from(error: &SemanticError) -> Self {
+        use SemanticError::*;
+        match error {
+            LookupError { id, span } => {
+                Diagnostic::new(error.code(), format!("Identifier {} not found", id))
+                    .with_primary(span, "not found in this scope")
+            }
+            InferenceError {
+                msg,
+                span,
+                unification_reason,
+                sides,
+            } => {
+                let Sides {
+                    lhs,
+                    rhs,
+                    lhs_resolved,
+                    rhs_resolved,
+                    lhs_blame,
+                    rhs_blame,
+                } = sides.as_ref();
+                let diagnostic = Diagnostic::new(
+                    error.code(),
+                    format!("{}: {} = {}", msg, lhs_resolved, rhs_resolved),
+                )
+                .with_primary(span, unification_reason.as_str());
+                let diagnostic = if lhs != lhs_resolved || rhs != rhs_resolved {
+                    diagnostic.with_note(format!("originally {} = {}", lhs, rhs))
+                } else {
+                    diagnostic
+                };
+                // The sides are blamed on where their types came from, not their last hop.
+                let (lhs_origin, rhs_origin) = (lhs_blame.origin(), rhs_blame.origin());
+                diagnostic
+                    .with_secondary(
+                        &lhs_origin.span,
+                        format!("expected {} because of {}", lhs_resolved, lhs_origin.reason),
+                    )
+                    .with_secondary(
+                        &rhs_origin.span,
+                        format!("found {} because of {}", rhs_resolved, rhs_origin.reason),
+                    )
+            }
+            UnknownType { id, span } => {
+                Diagnostic::new(error.code(), format!("Type {} not found", id))
+                    .with_primary(span, "not defined")
+            }
+            InvalidType { given, msg, span } => {
+                Diagnostic::new(error.code(), format!("Invalid type given: {}", given))
+                    .with_primary(span, *msg)
+            }
+            InvalidConstructorPattern { id, msg, span } => Diagnostic::new(
+                error.code(),
+                format!("Invalid pattern for constructor {}", id),
+            )
+            .with_primary(span, msg.as_str()),
+            Redefinition {
+                what,
+                id,
+                span,
+                previous,
+            } => Diagnostic::new(error.code(), format!("{} {} is defined twice", what, id))
+                .with_primary(span, "defined again here")
+                .with_secondary(previous, "first defined here"),
+        }
+    }
+}
+
+/// Every error a sem run found, sorted by where they are (see `SemTable::take_errors`).
+pub type SemanticErrors = Errors<SemanticError>;
+impl PhaseError for SemanticError {
+    const KIND: &'static str = "semantic";
+}
+
+/// Found by sem, but the program is still valid.
+#[derive(Error, Debug)]
+pub enum SemanticWarning {
+    #[error(
+        "Non-exhaustive match, {} is not matched (at {})",
+        counterexample,
+        span
+    )]
+    NonExhaustiveMatch { counterexample: String, span: Span },
+    #[error("Unreachable match clause (at {})", span)]
+    UnreachableClause { span: Span },
+    #[error(
+        "Ambiguous numeric type in the type of {} defaulted to int (at {})",
+        id,
+        span
+    )]
+    NumericDefault { id: String, span: Span },
+}
+impl SemanticWarning {
+    pub fn code(&self) -> &'static Code {
+        use SemanticWarning::*;
+        match self {
+            NonExhaustiveMatch { .. } => &codes::NON_EXHAUSTIVE_MATCH,
+            UnreachableClause { .. } => &codes::UNREACHABLE_CLAUSE,
+            NumericDefault { .. } => &codes::NUMERIC_DEFAULT,
+        }
+    }
+    pub fn span(&self) -> &Span {
+        use SemanticWarning::*;
+        match self {
+            NonExhaustiveMatch { span, .. }
+            | UnreachableClause { span }
+            | NumericDefault { span, .. } => span,
+        }
+    }
+}
+impl From<&SemanticWarning> for Diagnostic {
+    fn from(warning: &SemanticWarning) -> Self {
+        use SemanticWarning::*;
+        match warning {
+            NonExhaustiveMatch {
+                counterexample,
+                span,
+            } => Diagnostic::new(warning.code(), "Non-exhaustive match")
+                .with_primary(span, format!("{} is not matched", counterexample)),
+            UnreachableClause { span } => {
+                Diagnostic::new(warning.code(), "Unreachable match clause")
+                    .with_primary(span, "the clauses before it match everything it does")
+            }
+            NumericDefault { id, span } => Diagnostic::new(
+                warning.code(),
+                format!(
+                    "Ambiguous numeric type in the type of {} defaulted to int",
+                    id
+                ),
+            )
+            .with_primary(span, "")
+            .with_help(Some("annotate it to pick int or float".to_string())),
+        }
+    }
 }
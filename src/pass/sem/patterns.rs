@@ -0,0 +1,254 @@
+use std::fmt;
+
+use crate::parse::ast::{
+    data_map::NodeRef,
+    def::TDef,
+    expr::{Expr, Match, Pattern, PatternKind},
+};
+
+use super::{sem_table::SemTable, SemanticWarning};
+
+// ! Implementation notes:
+// !   Both checks ask whether a row of patterns is useful with respect to a matrix of rows,
+// !   that is whether some value is matched by the row but by none of the matrix
+// !   (see Maranget, "Warnings for pattern matching").
+// !     - A clause is unreachable if its pattern is not useful after the clauses above it.
+// !     - A match is exhaustive if a wildcard is not useful after all of its clauses,
+// !       otherwise the value found is the counterexample.
+// !   Constructors are told apart by the patterns alone, so no types are needed.
+// !   Int, float, char and string literals never cover their type.
+
+pub trait SemPatterns<'a> {
+    /// Warns about the clauses of a (sem'ed) match that are unreachable, and about
+    /// values none of them match.
+    fn check_match(&mut self, match_expr: &'a Match, expr: &'a Expr);
+}
+impl<'a> SemPatterns<'a> for SemTable<'a> {
+    fn check_match(&mut self, match_expr: &'a Match, expr: &'a Expr) {
+        let mut rows: Vec<Vec<Pat>> = Vec::with_capacity(match_expr.clauses.len());
+        for clause in &match_expr.clauses {
+            let row = vec![self.lower_pattern(&clause.pattern)];
+            if useful(&rows, &row).is_none() {
+                self.warnings.push(SemanticWarning::UnreachableClause {
+                    span: clause.span.clone(),
+                });
+            }
+            rows.push(row);
+        }
+        if let Some(witness) = useful(&rows, &[Pat::Wild]) {
+            self.warnings.push(SemanticWarning::NonExhaustiveMatch {
+                counterexample: witness[0].to_string(),
+                span: expr.span.clone(),
+            });
+        }
+    }
+}
+trait SemPatternsHelpers<'a> {
+    fn lower_pattern(&self, pattern: &'a Pattern) -> Pat<'a>;
+}
+impl<'a> SemPatternsHelpers<'a> for SemTable<'a> {
+    fn lower_pattern(&self, pattern: &'a Pattern) -> Pat<'a> {
+        use PatternKind::*;
+        let (ctor, args) = match &pattern.kind {
+            IdLower(_) => return Pat::Wild,
+            IntLiteral(n) => (Ctor::Int(*n), &[][..]),
+            FloatLiteral(x) => (Ctor::Float(*x), &[][..]),
+            CharLiteral(c) => (Ctor::Char(*c), &[][..]),
+            StringLiteral(s) => (Ctor::Str(s), &[][..]),
+            BoolLiteral(b) => (Ctor::Bool(*b), &[][..]),
+            Tuple(patterns) => (Ctor::Tuple(patterns.len()), &patterns[..]),
+            IdUpper { id, args } => {
+                let Some(NodeRef::Constr(constr)) = self.lookup(id) else {
+                    unreachable!("constructor patterns are checked before their match")
+                };
+                let tdef = self.get_constr_tdef(constr);
+                let idx = tdef
+                    .constrs
+                    .iter()
+                    .position(|c| std::ptr::eq(c, constr))
+                    .expect("constructor belongs to its type");
+                (Ctor::Variant { tdef, idx }, &args[..])
+            }
+        };
+        Pat::Ctor(ctor, args.iter().map(|p| self.lower_pattern(p)).collect())
+    }
+}
+
+#[derive(Clone)]
+enum Pat<'a> {
+    /// Matches anything, like an identifier pattern.
+    Wild,
+    Ctor(Ctor<'a>, Vec<Pat<'a>>),
+}
+#[derive(Clone)]
+enum Ctor<'a> {
+    Variant { tdef: &'a TDef, idx: usize },
+    Tuple(usize),
+    Bool(bool),
+    Int(i32),
+    Float(f64),
+    Char(u8),
+    Str(&'a str),
+}
+impl PartialEq for Ctor<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        use Ctor::*;
+        match (self, other) {
+            (Variant { tdef: lt, idx: li }, Variant { tdef: rt, idx: ri }) => {
+                std::ptr::eq(*lt, *rt) && li == ri
+            }
+            (Tuple(l), Tuple(r)) => l == r,
+            (Bool(l), Bool(r)) => l == r,
+            (Int(l), Int(r)) => l == r,
+            (Float(l), Float(r)) => l.to_bits() == r.to_bits(),
+            (Char(l), Char(r)) => l == r,
+            (Str(l), Str(r)) => l == r,
+            _ => false,
+        }
+    }
+}
+impl<'a> Ctor<'a> {
+    fn arity(&self) -> usize {
+        match self {
+            Ctor::Variant { tdef, idx } => tdef.constrs[*idx].types.len(),
+            Ctor::Tuple(n) => *n,
+            _ => 0,
+        }
+    }
+    /// Whether `ctors` (all of the same type) cover every value of their type.
+    fn are_complete(ctors: &[Ctor<'a>]) -> bool {
+        match ctors.first() {
+            None => false,
+            Some(Ctor::Variant { tdef, .. }) => ctors.len() == tdef.constrs.len(),
+            Some(Ctor::Tuple(_)) => true,
+            Some(Ctor::Bool(_)) => ctors.len() == 2,
+            Some(_) => false,
+        }
+    }
+    /// A constructor of the same type as `ctors` (which are incomplete) that is not one of them.
+    fn missing(ctors: &[Ctor<'a>]) -> Ctor<'a> {
+        match &ctors[0] {
+            Ctor::Variant { tdef, .. } => (0..tdef.constrs.len())
+                .map(|idx| Ctor::Variant { tdef, idx })
+                .find(|ctor| !ctors.contains(ctor))
+                .expect("incomplete constructors miss one"),
+            Ctor::Bool(b) => Ctor::Bool(!b),
+            Ctor::Int(_) => (0..).map(Ctor::Int).find(|c| !ctors.contains(c)).unwrap(),
+            Ctor::Float(_) => (0..)
+                .map(|n| Ctor::Float(n as f64))
+                .find(|c| !ctors.contains(c))
+                .unwrap(),
+            Ctor::Char(_) => (b'a'..=u8::MAX)
+                .chain(0..b'a')
+                .map(Ctor::Char)
+                .find(|c| !ctors.contains(c))
+                .unwrap_or(Ctor::Char(0)),
+            Ctor::Str(_) => ["", "a", "b", "c"]
+                .into_iter()
+                .map(Ctor::Str)
+                .find(|c| !ctors.contains(c))
+                .unwrap_or(Ctor::Str("?")),
+            Ctor::Tuple(_) => unreachable!("tuples are always complete"),
+        }
+    }
+}
+
+/// If some value is matched by `row` but by no row of `rows`, returns patterns describing it.
+fn useful<'a>(rows: &[Vec<Pat<'a>>], row: &[Pat<'a>]) -> Option<Vec<Pat<'a>>> {
+    let Some((head, tail)) = row.split_first() else {
+        return rows.is_empty().then(Vec::new);
+    };
+    match head {
+        Pat::Ctor(ctor, args) => {
+            let row: Vec<_> = args.iter().chain(tail).cloned().collect();
+            useful(&specialize(rows, ctor), &row).map(|witness| rebuild(ctor, witness))
+        }
+        Pat::Wild => {
+            let mut ctors: Vec<Ctor> = Vec::new();
+            for row in rows {
+                if let Pat::Ctor(ctor, _) = &row[0] {
+                    if !ctors.contains(ctor) {
+                        ctors.push(ctor.clone());
+                    }
+                }
+            }
+            if Ctor::are_complete(&ctors) {
+                return ctors.iter().find_map(|ctor| {
+                    let row: Vec<_> = std::iter::repeat_n(Pat::Wild, ctor.arity())
+                        .chain(tail.iter().cloned())
+                        .collect();
+                    useful(&specialize(rows, ctor), &row).map(|witness| rebuild(ctor, witness))
+                });
+            }
+            let default: Vec<_> = rows
+                .iter()
+                .filter(|row| matches!(row[0], Pat::Wild))
+                .map(|row| row[1..].to_vec())
+                .collect();
+            let mut witness = useful(&default, tail)?;
+            let head = if ctors.is_empty() {
+                Pat::Wild
+            } else {
+                let ctor = Ctor::missing(&ctors);
+                Pat::Ctor(ctor.clone(), vec![Pat::Wild; ctor.arity()])
+            };
+            witness.insert(0, head);
+            Some(witness)
+        }
+    }
+}
+/// The rows that match `ctor`, with their first column replaced by its arguments.
+fn specialize<'a>(rows: &[Vec<Pat<'a>>], ctor: &Ctor<'a>) -> Vec<Vec<Pat<'a>>> {
+    rows.iter()
+        .filter_map(|row| {
+            let args = match &row[0] {
+                Pat::Ctor(row_ctor, args) if row_ctor == ctor => args.clone(),
+                Pat::Ctor(..) => return None,
+                Pat::Wild => vec![Pat::Wild; ctor.arity()],
+            };
+            Some(args.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+/// Undoes `specialize` on a witness, its first columns become the arguments of `ctor`.
+fn rebuild<'a>(ctor: &Ctor<'a>, mut witness: Vec<Pat<'a>>) -> Vec<Pat<'a>> {
+    let tail = witness.split_off(ctor.arity());
+    std::iter::once(Pat::Ctor(ctor.clone(), witness))
+        .chain(tail)
+        .collect()
+}
+
+impl fmt::Display for Pat<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pat::Wild => write!(f, "_"),
+            Pat::Ctor(Ctor::Variant { tdef, idx }, args) => {
+                write!(f, "{}", tdef.constrs[*idx].id)?;
+                for arg in args {
+                    match arg {
+                        Pat::Ctor(Ctor::Variant { .. }, args) if !args.is_empty() => {
+                            write!(f, " ({})", arg)?
+                        }
+                        _ => write!(f, " {}", arg)?,
+                    }
+                }
+                Ok(())
+            }
+            Pat::Ctor(Ctor::Tuple(_), args) => {
+                write!(f, "(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Pat::Ctor(Ctor::Bool(b), _) => write!(f, "{}", b),
+            Pat::Ctor(Ctor::Int(n), _) => write!(f, "{}", n),
+            Pat::Ctor(Ctor::Float(x), _) => write!(f, "{:?}", x),
+            Pat::Ctor(Ctor::Char(c), _) => write!(f, "'{}'", std::ascii::escape_default(*c)),
+            Pat::Ctor(Ctor::Str(s), _) => write!(f, "{:?}", s),
+        }
+    }
+}
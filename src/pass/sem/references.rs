@@ -0,0 +1,143 @@
+use crate::parse::ast::{
+    data_map::{DataMap, NodeRef},
+    Program,
+};
+
+// ! Implementation notes:
+// !   Every identifier that refers to a binding (calls, array accesses, `dim`s, constructor
+// !   calls, constructor patterns and type names in annotations) is resolved once, during sem,
+// !   while its scope is there.
+// !   The binding it resolved to is kept for its site, and the site for the binding's uses.
+// !   Sites are also kept in the order they were resolved, so that the ones of input that
+// !   failed to check can be forgotten (see `SemTable::rollback`).
+
+/// Which binding each use refers to, and the uses of each binding.
+#[derive(Debug)]
+pub struct References<'a> {
+    /// The binding (a `Def`, `Par`, `For`, pattern, `Constr`, `TDef` or builtin) each site refers to.
+    bindings: DataMap<'a, NodeRef<'a>>,
+    /// The sites referring to each binding, in the order they were resolved.
+    uses: DataMap<'a, Vec<NodeRef<'a>>>,
+    /// Every site, in the order they were resolved.
+    sites: Vec<NodeRef<'a>>,
+}
+
+impl<'a> References<'a> {
+    pub fn new(p: &'a Program) -> Self {
+        Self {
+            bindings: DataMap::new(p),
+            uses: DataMap::new(p),
+            sites: Vec::new(),
+        }
+    }
+    /// Records that `site` refers to `binding`.
+    pub fn insert(&mut self, site: impl Into<NodeRef<'a>>, binding: NodeRef<'a>) {
+        let site = site.into();
+        if let Some(previous) = self.bindings.insert(site.clone(), binding.clone()) {
+            // *Note: Only happens if a node is sem'ed twice, its use is then only counted once.
+            self.remove_use(&site, &previous);
+        }
+        match self.uses.get_node_mut(&binding) {
+            Some(uses) => uses.push(site.clone()),
+            None => {
+                self.uses.insert(binding, vec![site.clone()]);
+            }
+        }
+        self.sites.push(site);
+    }
+    /// The binding `site` refers to, if it is a use that has been resolved.
+    #[inline(always)]
+    pub fn binding_of(&self, site: impl Into<NodeRef<'a>>) -> Option<&NodeRef<'a>> {
+        self.bindings.get(site)
+    }
+    /// The sites that refer to `binding`, in the order they were resolved.
+    pub fn uses_of(&self, binding: impl Into<NodeRef<'a>>) -> &[NodeRef<'a>] {
+        self.uses
+            .get(binding)
+            .map(|uses| uses.as_slice())
+            .unwrap_or_default()
+    }
+    /// How many sites have been resolved, see `truncate`.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.sites.len()
+    }
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+    /// Forgets the sites resolved since there were `len` of them.
+    pub fn truncate(&mut self, len: usize) {
+        while self.sites.len() > len {
+            let site = self.sites.pop().expect("there are more than `len` sites");
+            if let Some(binding) = self.bindings.remove(&site) {
+                self.remove_use(&site, &binding);
+            }
+        }
+    }
+    fn remove_use(&mut self, site: &NodeRef<'a>, binding: &NodeRef<'a>) {
+        if let Some(uses) = self.uses.get_node_mut(binding) {
+            uses.retain(|s| s != site);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        lex::IntoLexer,
+        parse::{
+            ast::{
+                def::{DefKind, Definition},
+                expr::ExprKind,
+            },
+            IntoParser,
+        },
+        pass::sem::sem,
+        scan::Scanner,
+    };
+
+    #[test]
+    fn uses_resolve_to_the_binding_in_scope() {
+        let source = "let x = 1\nlet f x = x + 1\nlet y = f x\n";
+        let p = Scanner::from_source("test", source.to_string())
+            .preprocess()
+            .into_lexer(true)
+            .into_parser()
+            .program()
+            .expect("should parse");
+        let table = sem(&p, false).expect("should type check");
+        let def = |i: usize| match &p.definitions[i] {
+            Definition::Let(letdef) => &letdef.defs[0],
+            Definition::Type(_) | Definition::Error(_) => unreachable!("only lets are defined"),
+        };
+        let DefKind::Function { pars, expr } = &def(1).kind else {
+            unreachable!("f is a function")
+        };
+        let ExprKind::Binop(binop) = &expr.kind else {
+            unreachable!("f's body is an addition")
+        };
+        // The `x` in f's body is its parameter, not the global.
+        assert_eq!(
+            table.references.binding_of(&*binop.lhs),
+            Some(&NodeRef::Par(&pars[0]))
+        );
+        let DefKind::Const { expr: call } = &def(2).kind else {
+            unreachable!("y is a constant")
+        };
+        let ExprKind::Call(f_call) = &call.kind else {
+            unreachable!("y is a call of f")
+        };
+        assert_eq!(
+            table.references.binding_of(call),
+            Some(&NodeRef::Def(def(1)))
+        );
+        assert_eq!(table.references.uses_of(def(1)), [NodeRef::Expr(call)]);
+        assert_eq!(
+            table.references.uses_of(def(0)),
+            [NodeRef::Expr(&f_call.args[0])]
+        );
+        assert!(table.references.uses_of(def(2)).is_empty());
+    }
+}
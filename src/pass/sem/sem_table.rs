@@ -2,27 +2,114 @@ use std::collections::HashMap;
 
 use log::trace;
 
-use crate::parse::ast::{data_map::NodeRef, Program};
+use crate::parse::ast::{
+    annotation::TypeAnnotation,
+    data_map::{DataMap, NodeRef},
+    def::{Constr, TDef},
+    Program, Span,
+};
 
+use super::builtins::BUILTINS;
+use super::references::References;
 use super::types::inference::InferenceGroup;
+use super::types::scheme::TypeScheme;
 use super::types::type_map::TypeMap;
+use super::types::{validity, TypeId};
+use super::{SemResult, SemanticError, SemanticErrors, SemanticWarning};
 
 // type TypeMap<'a> = ast::data_map::DataMap<'a, Type>;
 type Scope<'a> = HashMap<&'a str, NodeRef<'a>>;
+/// The scopes of a `SemTable` at some point, types inferred since then are kept.
+pub struct Checkpoint<'a>(Vec<Scope<'a>>, HashMap<&'a str, &'a TDef>, usize);
+
 #[derive(Debug)]
 pub struct SemTable<'a> {
     scopes: Vec<Scope<'a>>,
+    /// Types are only defined at the top level, and have a namespace of their own.
+    type_scope: HashMap<&'a str, &'a TDef>,
 
     // *NOTE: Type substitutions in TypeMap will be applied in bulk after inference.
     pub types: TypeMap<'a>,
+    /// The type each constructor belongs to.
+    constr_tdefs: DataMap<'a, &'a TDef>,
+    /// The binding each use of an identifier resolved to, kept after the scopes are gone.
+    pub references: References<'a>,
+    pub warnings: Vec<SemanticWarning>,
+    /// Found so far, see `take_errors`.
+    errors: Vec<SemanticError>,
+    /// Numeric unknowns left at generalization are defaulted to int instead of generalized.
+    pub strict: bool,
 }
 
 impl<'a> SemTable<'a> {
+    /// The root scope holds the standard library.
     pub fn new(ast: &'a Program) -> Self {
-        Self {
+        let mut table = Self {
             scopes: vec![Scope::new()],
+            type_scope: HashMap::new(),
             types: TypeMap::new(ast),
+            constr_tdefs: DataMap::new(ast),
+            references: References::new(ast),
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            strict: false,
+        };
+        for builtin in BUILTINS {
+            table.insert_scope_binding(builtin.id, builtin);
+            let ty = builtin.make_type(&mut table.types);
+            if !table.types.is_fully_known(ty) {
+                let scheme = TypeScheme::generalize(&table.types, ty, |_| true);
+                table.types.insert_scheme(builtin, scheme);
+            }
+            table.types.insert(builtin, ty);
+        }
+        table
+    }
+    /// Remembers the bindings of every scope, see `rollback`.
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint(
+            self.scopes.clone(),
+            self.type_scope.clone(),
+            self.references.len(),
+        )
+    }
+    /// Forgets every binding made since `checkpoint` was taken, and the uses resolved since.
+    pub fn rollback(&mut self, checkpoint: Checkpoint<'a>) {
+        self.scopes = checkpoint.0;
+        self.type_scope = checkpoint.1;
+        self.references.truncate(checkpoint.2);
+    }
+    /// Checking goes on after an error, it is reported along with the rest by `take_errors`.
+    pub fn record_error(&mut self, error: SemanticError) {
+        trace!("Recording error: {}", error);
+        self.errors.push(error);
+    }
+    /// The type of a check's result, or the poison type if it failed.
+    pub fn poison_on_error(&mut self, res: SemResult<TypeId>) -> TypeId {
+        res.unwrap_or_else(|error| {
+            self.record_error(error);
+            self.types.get_poison()
+        })
+    }
+    /// Fails with the errors recorded since the last call, if any, sorted by where they are.
+    pub fn take_errors(&mut self) -> Result<(), SemanticErrors> {
+        if self.errors.is_empty() {
+            return Ok(());
         }
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|error| {
+            let start = &error.span().start;
+            (start.line, start.column)
+        });
+        Err(SemanticErrors::new(errors))
+    }
+    #[inline(always)]
+    pub fn scope_depth(&self) -> usize {
+        self.scopes.len()
+    }
+    /// Pops the scopes pushed since the depth was `depth`.
+    pub fn truncate_scopes(&mut self, depth: usize) {
+        self.scopes.truncate(depth);
     }
     pub fn push_scope(&mut self) {
         trace!("Pushing scope.");
@@ -66,6 +153,88 @@ impl<'a> SemTable<'a> {
         }
         None
     }
+    /// Looks up the binding an identifier at `site` refers to, and remembers it for the site.
+    pub fn lookup_use(&mut self, name: &str, site: impl Into<NodeRef<'a>>) -> Option<NodeRef<'a>> {
+        let binding = self.lookup(name)?;
+        self.references.insert(site, binding.clone());
+        Some(binding)
+    }
+    /// Shadows any previous type of the same name.
+    pub fn insert_type_binding(&mut self, name: &'a str, tdef: &'a TDef) {
+        self.type_scope.insert(name, tdef);
+    }
+    pub fn lookup_type(&self, name: &str) -> Option<&'a TDef> {
+        self.type_scope.get(name).copied()
+    }
+    /// The type an annotation (found at `span`) stands for, its type names must be in scope
+    /// and it must be valid. The type each of its names resolved to is kept as a reference.
+    pub fn annotation_type(
+        &mut self,
+        annotation: &'a TypeAnnotation,
+        span: &Span,
+    ) -> SemResult<TypeId> {
+        let ty = self.annotation_type_unchecked(annotation, span)?;
+        validity::check_validity(&self.types, ty).map_err(|msg| SemanticError::InvalidType {
+            given: self.types.pretty(ty).to_string(),
+            msg,
+            span: span.clone(),
+        })?;
+        Ok(ty)
+    }
+    fn annotation_type_unchecked(
+        &mut self,
+        annotation: &'a TypeAnnotation,
+        span: &Span,
+    ) -> SemResult<TypeId> {
+        use TypeAnnotation::*;
+        Ok(match annotation {
+            Unit => self.types.get_unit(),
+            Int => self.types.get_int(),
+            Char => self.types.get_char(),
+            Bool => self.types.get_bool(),
+            Float => self.types.get_float(),
+            Func { lhs, rhs } => {
+                let lhs = self.annotation_type_unchecked(lhs, span)?;
+                let rhs = self.annotation_type_unchecked(rhs, span)?;
+                self.types.new_func(lhs, rhs)
+            }
+            Ref(inner) => {
+                let inner = self.annotation_type_unchecked(inner, span)?;
+                self.types.new_ref(inner)
+            }
+            Array { inner, dim_cnt } => {
+                let inner = self.annotation_type_unchecked(inner, span)?;
+                self.types.new_known_array(inner, *dim_cnt)
+            }
+            Tuple(types) => {
+                let mut inner_types = Vec::with_capacity(types.len());
+                for t in types {
+                    inner_types.push(self.annotation_type_unchecked(t, span)?);
+                }
+                self.types.new_tuple(inner_types)
+            }
+            Custom { id } => {
+                let tdef = self
+                    .lookup_type(id)
+                    .ok_or_else(|| SemanticError::UnknownType {
+                        id: id.clone(),
+                        span: span.clone(),
+                    })?;
+                self.references.insert(annotation, tdef.into());
+                self.types
+                    .get_type(tdef)
+                    .expect("types in scope have been given one")
+            }
+        })
+    }
+    pub fn insert_constr_tdef(&mut self, constr: &'a Constr, tdef: &'a TDef) {
+        self.constr_tdefs.insert(constr, tdef);
+    }
+    pub fn get_constr_tdef(&self, constr: &'a Constr) -> &'a TDef {
+        self.constr_tdefs
+            .get(constr)
+            .expect("constructors are inserted along with their type")
+    }
     #[inline(always)]
     pub fn new_inference_group(&self) -> InferenceGroup<'a> {
         InferenceGroup::new()
@@ -79,6 +248,7 @@ mod test {
     fn new_empty_program() -> ast::Program {
         ast::Program {
             definitions: Vec::new(),
+            attributes: Vec::new(),
         }
     }
     fn new_test_table<'a>(p: &'a Program) -> SemTable<'a> {
@@ -104,16 +274,64 @@ mod test {
         let mut table = new_test_table(&p);
         let foo_node = new_const_def("foo");
         assert_eq!(table.insert_scope_binding("foo", &foo_node), None);
-        assert!(matches!(table.lookup("foo"), Some(_)));
+        assert!(table.lookup("foo").is_some());
         // assert!(matches!(table.lookup_strict("foo"), Some(_)));
         table.push_scope();
         let bar_node = new_const_def("bar");
         assert_eq!(table.insert_scope_binding("bar", &bar_node), None);
-        assert!(matches!(table.lookup("bar"), Some(_)));
+        assert!(table.lookup("bar").is_some());
         // assert!(matches!(table.lookup_strict("bar"), Some(_)));
-        assert!(matches!(table.lookup("foo"), Some(_)));
+        assert!(table.lookup("foo").is_some());
         // assert!(matches!(table.lookup_strict("foo"), None));
         table.pop_scope();
-        assert!(matches!(table.lookup("bar"), None));
+        assert!(table.lookup("bar").is_none());
+    }
+    #[test]
+    fn root_scope_has_the_standard_library() {
+        let p = new_empty_program();
+        let table = new_test_table(&p);
+        let print_int = table.lookup("print_int").expect("print_int is predeclared");
+        assert_eq!(
+            table
+                .types
+                .show(table.types.get_node_type(&print_int).unwrap())
+                .to_string(),
+            "int -> (unit)"
+        );
+        assert!(!table.types.is_generic(print_int));
+        assert!(table.types.is_generic(table.lookup("exit").unwrap()));
+    }
+    #[test]
+    fn uses_of_generic_bindings_record_their_instantiation() {
+        use crate::{lex::IntoLexer, parse::IntoParser, pass::sem::sem, scan::Scanner};
+        let source = "let pair x y = (x, y)\nlet p = pair 1 'c'\n";
+        let p = Scanner::from_source("test", source.to_string())
+            .preprocess()
+            .into_lexer(true)
+            .into_parser()
+            .program()
+            .expect("should parse");
+        let table = sem(&p, false).expect("should type check");
+        let pair = table.lookup("pair").unwrap();
+        let scheme = table.types.get_scheme(&pair).expect("pair is generic");
+        assert_eq!(scheme.quantified.len(), 2);
+        assert_eq!(
+            scheme.pretty(&table.types).to_string(),
+            "'a -> 'b -> ('a, 'b)"
+        );
+        let ast::def::Definition::Let(letdef) = &p.definitions[1] else {
+            unreachable!("second definition is a let")
+        };
+        let ast::def::DefKind::Const { expr } = &letdef.defs[0].kind else {
+            unreachable!("p is a constant")
+        };
+        let instantiation: Vec<_> = table
+            .types
+            .get_instantiation(expr)
+            .expect("the use of pair is recorded")
+            .iter()
+            .map(|ty| table.types.pretty(*ty).to_string())
+            .collect();
+        assert_eq!(instantiation, ["int", "char"]);
     }
 }
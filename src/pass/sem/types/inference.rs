@@ -1,27 +1,42 @@
-use std::{cell::RefCell, collections::HashSet, rc::Rc};
+use std::{collections::HashSet, rc::Rc};
 
 use colored::Colorize;
 use log::trace;
 
 use crate::{
     parse::ast::Span,
-    pass::sem::{SemResult, SemanticError},
+    pass::sem::{SemResult, SemanticError, Sides},
 };
 
-use super::{type_map::TypeMap, Type, TypeKind};
+use super::{type_map::TypeMap, validity, Pretty, Type, TypeId, TypeKind, UnknownNames};
 
 pub trait Inferer<'a> {
-    fn solve_group(&mut self, group: InferenceGroup<'a>) -> SemResult<()>;
+    /// Returns the errors of the unifications that failed, their unknowns are poisoned
+    /// and solving goes on with the rest.
+    /// Those whose sides contain the poison type are not reported, it was reported already.
+    fn solve_group(&mut self, group: InferenceGroup<'a>) -> Vec<SemanticError>;
 }
 
 impl<'a> Inferer<'a> for TypeMap<'a> {
-    fn solve_group(&mut self, group: InferenceGroup<'a>) -> SemResult<()> {
+    fn solve_group(&mut self, group: InferenceGroup<'a>) -> Vec<SemanticError> {
         trace!("{}", "↓↓↓  Solving inference group   ↓↓↓".underline());
+        let mut errors = Vec::new();
         for unification in group.0 {
-            self.unify(unification)?;
+            let (lhs, rhs, poisons) = (unification.lhs, unification.rhs, unification.poisons);
+            if let Err(err) = self.unify(unification) {
+                // A failure involving something that already failed is only a cascade of it.
+                if !self.contains_poison(lhs) && !self.contains_poison(rhs) {
+                    errors.push(err);
+                }
+                self.poison_unknowns(lhs);
+                self.poison_unknowns(rhs);
+                if let Some(result) = poisons {
+                    self.poison_unknowns(result);
+                }
+            }
         }
         trace!("{}", "↑↑↑Done solving inference group↑↑↑".underline());
-        Ok(())
+        errors
     }
 }
 
@@ -31,27 +46,39 @@ impl<'a> InfererHelpers<'a> for TypeMap<'a> {
         //     "Applying unification: {}",
         //     format!("{} = {}", unification.lhs, unification.rhs).green()
         // );
-        let lhs = self.deep_resolve_type(unification.lhs.clone());
-        let rhs = self.deep_resolve_type(unification.rhs.clone());
+        // A side keeps the blame of the unification its type came from, if any.
+        let unification = Unification {
+            lhs_blame: self.blame_of(unification.lhs).or(unification.lhs_blame),
+            rhs_blame: self.blame_of(unification.rhs).or(unification.rhs_blame),
+            ..unification
+        };
+        let lhs = self.resolve_type(unification.lhs);
+        let rhs = self.resolve_type(unification.rhs);
         trace!(
             "Unifying: {} {} {}",
-            lhs.to_string().blue(),
+            self.show(lhs).to_string().blue(),
             "=".red(),
-            rhs.to_string().blue(),
+            self.show(rhs).to_string().blue(),
         );
+        // *Note: Types are interned and unified unknowns resolve to the same type, so
+        // equal types have the same id.
         if lhs == rhs {
             trace!("Unifying equal types is a no-op");
             return Ok(());
         }
         use Type::*;
-        match (&*lhs, &*rhs) {
-            (Unknown(_, _), _) | (_, Unknown(_, _)) => {
-                let (unknown, resolved) = if let Unknown(_, _) = &*lhs {
-                    (lhs, rhs)
+        match (self.get(lhs).clone(), self.get(rhs).clone()) {
+            // *Note: Unknowns are left as they are, so that the other uses of what failed to
+            // check are still checked against each other.
+            (Poison, _) | (_, Poison) => Ok(()),
+            (Unknown(_), _) | (_, Unknown(_)) => {
+                let (unknown, resolved, resolved_blame) = if let Unknown(_) = self.get(lhs) {
+                    (lhs, rhs, &unification.rhs_blame)
                 } else {
-                    (rhs, lhs)
+                    (rhs, lhs, &unification.lhs_blame)
                 };
-                self.try_add_resolution(unknown, resolved)
+                let (blame, from) = (unification.blame(), resolved_blame.clone());
+                self.try_add_resolution(unknown, resolved, blame, from)
                     .map_err(|e| self.unification_into_error(unification, &e))
             }
             (
@@ -64,288 +91,434 @@ impl<'a> InfererHelpers<'a> for TypeMap<'a> {
                     rhs: rhs2,
                 },
             ) => {
-                self.unify(Unification {
-                    lhs: lhs1.clone(),
-                    rhs: lhs2.clone(),
-                    span: unification.span,
-                    msg: unification.msg,
-                })?;
-                self.unify(Unification {
-                    lhs: rhs1.clone(),
-                    rhs: rhs2.clone(),
-                    span: unification.span,
-                    msg: unification.msg,
-                })
+                self.unify(unification.sub(lhs1, lhs2))?;
+                self.unify(unification.sub(rhs1, rhs2))
             }
-            (Ref(lhs_inner), Ref(rhs_inner)) => self.unify(Unification {
-                lhs: lhs_inner.clone(),
-                rhs: rhs_inner.clone(),
-                span: unification.span,
-                msg: unification.msg,
-            }),
+            (Ref(lhs_inner), Ref(rhs_inner)) => self.unify(unification.sub(lhs_inner, rhs_inner)),
             (
                 Array {
                     inner: lhs_inner,
-                    dim_cnt: lhs_dims,
+                    dims: lhs_dims,
                 },
                 Array {
                     inner: rhs_inner,
-                    dim_cnt: rhs_dims,
+                    dims: rhs_dims,
                 },
             ) => {
-                if !lhs_dims
-                    .borrow()
-                    .borrow()
-                    .are_compatible(&*rhs_dims.borrow().borrow())
-                {
-                    return Err(self.unification_into_error(
-                        unification,
-                        &format!(
-                            "Can't match dims {} with {}",
-                            lhs_dims.borrow().borrow(),
-                            rhs_dims.borrow().borrow()
-                        ),
-                    ));
-                }
-                self.unify(Unification {
-                    lhs: lhs_inner.clone(),
-                    rhs: rhs_inner.clone(),
-                    span: unification.span,
-                    msg: unification.msg,
-                })?;
-                ArrayDims::consolidate(&lhs_dims, &rhs_dims);
-                Ok(())
+                let (lhs_cnt, rhs_cnt) = (self.dims_of(lhs_dims), self.dims_of(rhs_dims));
+                let Some(merged) = ArrayDims::merge(lhs_cnt, rhs_cnt) else {
+                    let msg = format!("Can't match dims {} with {}", lhs_cnt, rhs_cnt);
+                    return Err(self.unification_into_error(unification, &msg));
+                };
+                self.dims
+                    .union(lhs_dims, rhs_dims, |root, _| *root = merged);
+                self.unify(unification.sub(lhs_inner, rhs_inner))
             }
             (Tuple(lhs_types), Tuple(rhs_types)) => {
                 if lhs_types.len() != rhs_types.len() {
                     return Err(self.unification_into_error(unification, "Tuple sizes don't match"));
                 }
-                lhs_types
-                    .iter()
-                    .zip(rhs_types.iter())
-                    .try_for_each(|(lhs, rhs)| {
-                        self.unify(Unification {
-                            lhs: lhs.clone(),
-                            rhs: rhs.clone(),
-                            span: unification.span,
-                            msg: unification.msg,
-                        })
-                    })
+                for (lhs, rhs) in lhs_types.into_iter().zip(rhs_types) {
+                    self.unify(unification.sub(lhs, rhs))?;
+                }
+                Ok(())
+            }
+            (Custom { uid: lhs_uid }, Custom { uid: rhs_uid })
+                if self.get_custom_tdef(lhs_uid).id == self.get_custom_tdef(rhs_uid).id =>
+            {
+                let msg = format!(
+                    "Two different types are named {}, defined at {} and {}",
+                    self.get_custom_tdef(lhs_uid).id,
+                    self.get_custom_tdef(lhs_uid).span.start,
+                    self.get_custom_tdef(rhs_uid).span.start
+                );
+                Err(self.unification_into_error(unification, &msg))
             }
-            _ => return Err(self.unification_into_error(unification, "Couldn't unify")),
+            _ => Err(self.unification_into_error(unification, "Couldn't unify")),
         }
     }
-    fn deep_resolve_type(&self, ty: Rc<Type>) -> Rc<Type> {
-        let ty = self.resolve_type(ty);
-        use Type::*;
-        match &*ty {
-            Func { lhs, rhs } => {
-                let lhs = self.deep_resolve_type(lhs.clone());
-                let rhs = self.deep_resolve_type(rhs.clone());
-                Type::new_func(lhs, rhs)
-            }
-            Ref(inner) => Type::new_ref(self.deep_resolve_type(inner.clone())),
-            // *Note: Think if this is a problem, if we need to refcell `inner` for Array and Ref
-            Array { inner, dim_cnt } => Rc::new(Type::Array {
-                inner: self.deep_resolve_type(inner.clone()),
-                dim_cnt: RefCell::new(dim_cnt.borrow().clone()),
-            }),
-            Tuple(types) => Type::new_tuple(
-                types
-                    .iter()
-                    .map(|ty| self.deep_resolve_type(ty.clone()))
-                    .collect(),
-            ),
-            _ => ty,
+    fn poison_unknowns(&mut self, ty: TypeId) {
+        let mut ids = HashSet::new();
+        self.visit_unknowns(ty, &mut |id| {
+            ids.insert(id);
+        });
+        for id in ids {
+            self.unknowns.get_mut(id).ty = self.get_poison();
         }
     }
-    fn resolve_type(&self, ty: Rc<Type>) -> Rc<Type> {
-        // TODO: Make another one that shortens the chain by applying the resolution to all the intermediate types
-        // let mut type_ids = Vec::new();
-        let mut retval = ty.clone();
-        while let Type::Unknown(id, _) = &*retval {
-            // type_ids.push(*id);
-            if let Some(resolved) = self.unifications.get(id) {
-                retval = resolved.clone();
-            } else {
-                break;
-            }
+    fn resolve_type(&self, ty: TypeId) -> TypeId {
+        match self.get(ty) {
+            Type::Unknown(id) => self.unknowns.get(*id).ty,
+            _ => ty,
         }
-        // if type_ids.len() > 1 {
-        //     for id in &type_ids[1..type_ids.len() - 1] {
-        //         self.unifications.insert(*id, retval.clone());
-        //     }
-        // }
-        retval
-    }
-    fn try_add_resolution(&mut self, unknown: Rc<Type>, resolved: Rc<Type>) -> Result<(), String> {
-        let (unknown_id, unknown_constraint) = match &*unknown {
-            Type::Unknown(id, constraint_refcell) => (*id, constraint_refcell.borrow()),
-            _ => unreachable!("try_add_resolution called on non-unknown type"),
+    }
+    fn try_add_resolution(
+        &mut self,
+        unknown: TypeId,
+        resolved: TypeId,
+        blame: Blame,
+        from: Option<Blame>,
+    ) -> Result<(), String> {
+        // The unknown got its type through `blame`, from wherever the resolved side got its.
+        let blame = Blame {
+            from: from.map(Rc::new),
+            ..blame
+        };
+        let Type::Unknown(unknown_id) = *self.get(unknown) else {
+            unreachable!("try_add_resolution called on non-unknown type")
+        };
+        let unknown_constraint = self.constraints_of(unknown_id).clone();
+        let violation = |reason: Option<&str>| match reason {
+            Some(reason) => format!("Constraints violated, {}", reason),
+            None => "Constraints violated".to_string(),
         };
-        if !Self::fulfills_constraints(&unknown, &resolved) {
-            return Err(format!("Constraints violated"));
-        }
-        if self.occurs(unknown_id, &resolved) {
-            return Err(format!(
-                "Occurs check failed, recursive unknown type implied"
-            ));
-        }
-        match &*resolved {
-            Type::Unknown(_, constraint_refcell) => {
-                let mut resolved_constraints = constraint_refcell.borrow_mut();
-                resolved_constraints.consolidate(&unknown_constraint)
+        // Unknowns take on the constraints of both sides, which must still be satisfiable.
+        if let Type::Unknown(resolved_id) = *self.get(resolved) {
+            let mut constraints = self.constraints_of(resolved_id).clone();
+            if !constraints.consolidate(&unknown_constraint) {
+                return Err(violation(constraints.reason()));
             }
-            _ => (),
+            self.unknowns.union(unknown_id, resolved_id, |root, other| {
+                root.constraints = constraints;
+                root.blame = Some(blame);
+                root.level = root.level.min(other.level);
+            });
+            return Ok(());
+        }
+        let satisfiable = if unknown_constraint.is_hereditary() {
+            self.impose_hereditary(&unknown_constraint, resolved, &mut HashSet::new())
+        } else {
+            unknown_constraint.are_satisfied_by(self.get(resolved))
         };
-        self.unifications.insert(unknown_id, resolved);
+        if !satisfiable {
+            let reason = match unknown_constraint.is_hereditary() {
+                true => unknown_constraint.reason(),
+                false => unknown_constraint.reason_against(&self.get(resolved).into()),
+            };
+            return Err(violation(reason));
+        }
+        if self.occurs(unknown_id, resolved) {
+            return Err("Occurs check failed, recursive unknown type implied".to_string());
+        }
+        let level = self.level_of(unknown_id);
+        self.lower_levels(resolved, level);
+        let resolution = self.unknowns.get_mut(unknown_id);
+        resolution.ty = resolved;
+        resolution.blame = Some(blame);
         Ok(())
     }
-    fn fulfills_constraints(unknown: &Rc<Type>, resolved: &Rc<Type>) -> bool {
+    fn lower_levels(&mut self, ty: TypeId, level: u32) {
         use Type::*;
-        match &**unknown {
-            Unknown(_, constraints) => constraints.borrow().are_satisfied_by(resolved),
-            _ => unreachable!("fulfills_constraints called on non-unknown type"),
+        let ty = self.resolve_type(ty);
+        match self.get(ty).clone() {
+            Unknown(id) => {
+                let resolution = self.unknowns.get_mut(id);
+                resolution.level = resolution.level.min(level);
+            }
+            Func { lhs, rhs } => {
+                self.lower_levels(lhs, level);
+                self.lower_levels(rhs, level);
+            }
+            Ref(inner) | Array { inner, .. } => self.lower_levels(inner, level),
+            Tuple(types) => types.into_iter().for_each(|t| self.lower_levels(t, level)),
+            _ => (),
         }
     }
-    fn occurs(&mut self, id: u32, ty: &Rc<Type>) -> bool {
+    fn blame_of(&self, ty: TypeId) -> Option<Blame> {
+        match self.get(ty) {
+            Type::Unknown(id) => self.unknowns.get(*id).blame.clone(),
+            _ => None,
+        }
+    }
+    fn impose_hereditary(
+        &mut self,
+        constraints: &Constraints,
+        ty: TypeId,
+        visited_customs: &mut HashSet<u32>,
+    ) -> bool {
         use Type::*;
-        match &**ty {
-            Array { inner, .. } | Ref(inner) => {
-                let resolved_inner = self.resolve_type(inner.clone());
-                self.is_or_occurs(id, &resolved_inner)
-            }
+        let ty = self.resolve_type(ty);
+        if let Unknown(id) = *self.get(ty) {
+            return self
+                .unknowns
+                .get_mut(id)
+                .constraints
+                .consolidate(constraints);
+        }
+        if !constraints.are_satisfied_by(self.get(ty)) {
+            return false;
+        }
+        match self.get(ty).clone() {
             Func { lhs, rhs } => {
-                let resolved_lhs = self.resolve_type(lhs.clone());
-                let resolved_rhs = self.resolve_type(rhs.clone());
-                self.is_or_occurs(id, &resolved_lhs) || self.is_or_occurs(id, &resolved_rhs)
+                self.impose_hereditary(constraints, lhs, visited_customs)
+                    && self.impose_hereditary(constraints, rhs, visited_customs)
+            }
+            Ref(inner) | Array { inner, .. } => {
+                self.impose_hereditary(constraints, inner, visited_customs)
+            }
+            Tuple(types) => types
+                .into_iter()
+                .all(|t| self.impose_hereditary(constraints, t, visited_customs)),
+            // The arguments of a type's constructors are known, and may only refer back to it.
+            Custom { uid } if visited_customs.insert(uid) => {
+                let tdef = self.get_custom_tdef(uid);
+                tdef.constrs.iter().all(|constr| {
+                    let mut constr_type = self
+                        .get_type(constr)
+                        .expect("constructors are given a type along with their type");
+                    (0..constr.types.len()).all(|_| {
+                        let Func { lhs, rhs } = *self.get(constr_type) else {
+                            unreachable!("a constructor's type takes its arguments")
+                        };
+                        constr_type = rhs;
+                        self.impose_hereditary(constraints, lhs, visited_customs)
+                    })
+                })
             }
-            Tuple(types) => types.iter().any(|ty| self.is_or_occurs(id, ty)),
+            _ => true,
+        }
+    }
+    fn occurs(&mut self, id: u32, ty: TypeId) -> bool {
+        use Type::*;
+        match self.get(ty).clone() {
+            Array { inner, .. } | Ref(inner) => self.is_or_occurs(id, inner),
+            Func { lhs, rhs } => self.is_or_occurs(id, lhs) || self.is_or_occurs(id, rhs),
+            Tuple(types) => types.into_iter().any(|ty| self.is_or_occurs(id, ty)),
             _ => false,
         }
     }
     #[inline(always)]
-    fn is_or_occurs(&mut self, id: u32, ty: &Rc<Type>) -> bool {
+    fn is_or_occurs(&mut self, id: u32, ty: TypeId) -> bool {
         use Type::*;
-        matches!(&**ty, Unknown(id2, _) if id == *id2) || self.occurs(id, ty)
+        let ty = self.resolve_type(ty);
+        matches!(self.get(ty), Unknown(id2) if id == *id2) || self.occurs(id, ty)
     }
     fn unification_into_error(&mut self, unification: Unification<'a>, msg: &str) -> SemanticError {
-        let lhs_resolved = self.deep_resolve_type(unification.lhs.clone());
-        let rhs_resolved = self.deep_resolve_type(unification.rhs.clone());
+        // Named together, so that the unknowns the sides share are called the same.
+        let mut names = UnknownNames::default();
+        let lhs_resolved = Pretty::named(self, unification.lhs, &mut names, true).to_string();
+        let rhs_resolved = Pretty::named(self, unification.rhs, &mut names, true).to_string();
+        let lhs = Pretty::named(self, unification.lhs, &mut names, false).to_string();
+        let rhs = Pretty::named(self, unification.rhs, &mut names, false).to_string();
         SemanticError::InferenceError {
             msg: msg.to_string(),
-            lhs_resolved,
-            rhs_resolved,
-            lhs: unification.lhs,
-            rhs: unification.rhs,
             span: unification.span.clone(),
             unification_reason: unification.msg.to_owned(),
+            sides: Box::new(Sides {
+                lhs,
+                rhs,
+                lhs_resolved,
+                rhs_resolved,
+                lhs_blame: unification
+                    .lhs_blame
+                    .clone()
+                    .unwrap_or_else(|| unification.blame()),
+                rhs_blame: unification
+                    .rhs_blame
+                    .clone()
+                    .unwrap_or_else(|| unification.blame()),
+            }),
         }
     }
 }
 pub trait InfererHelpers<'a> {
     fn unify(&mut self, unification: Unification<'a>) -> SemResult<()>;
-    /// resolves the type and then resolves all the types inside of it.
-    fn deep_resolve_type(&self, ty: Rc<Type>) -> Rc<Type>;
-    /// follows the chain of unifications until it finds a type that is not an unknown type.
-    fn resolve_type(&self, ty: Rc<Type>) -> Rc<Type>;
+    /// resolves every unknown left in `ty` to the poison type.
+    fn poison_unknowns(&mut self, ty: TypeId);
+    /// what the set of unknowns `ty` is in has been resolved to, `ty` itself if it is not an unknown.
+    fn resolve_type(&self, ty: TypeId) -> TypeId;
     /// checks if type `ty` contains an unknown type with id `id`, to prevent recursive unknown types.
-    fn occurs(&mut self, id: u32, ty: &Rc<Type>) -> bool;
-    fn is_or_occurs(&mut self, id: u32, ty: &Rc<Type>) -> bool;
+    fn occurs(&mut self, id: u32, ty: TypeId) -> bool;
+    fn is_or_occurs(&mut self, id: u32, ty: TypeId) -> bool;
     ///
-    fn try_add_resolution(&mut self, unknown: Rc<Type>, resolved: Rc<Type>) -> Result<(), String>;
-    /// checks if `unknown` can resolve to `resolved`.
-    fn fulfills_constraints(unknown: &Rc<Type>, resolved: &Rc<Type>) -> bool;
+    /// `blame` is remembered as the reason `unknown` resolved to `resolved`, following `from`,
+    /// the blame of the type `resolved` if it has one.
+    fn try_add_resolution(
+        &mut self,
+        unknown: TypeId,
+        resolved: TypeId,
+        blame: Blame,
+        from: Option<Blame>,
+    ) -> Result<(), String>;
+    /// makes the unknowns in `ty` be of `level` at most, they are reachable from there now.
+    fn lower_levels(&mut self, ty: TypeId, level: u32);
+    /// why the set of unknowns `ty` is in resolved to what it did, none if it is not an
+    /// unknown that has been unified with anything.
+    fn blame_of(&self, ty: TypeId) -> Option<Blame>;
+    /// makes `ty` and everything in it satisfy `constraints`, returns false if it can't.
+    fn impose_hereditary(
+        &mut self,
+        constraints: &Constraints,
+        ty: TypeId,
+        visited_customs: &mut HashSet<u32>,
+    ) -> bool;
     /// converts a unification into a semantic error.
     fn unification_into_error(&mut self, unification: Unification<'a>, msg: &str) -> SemanticError;
 }
 #[derive(Debug)]
 pub struct Unification<'a> {
-    lhs: Rc<Type>,
-    rhs: Rc<Type>,
+    lhs: TypeId,
+    rhs: TypeId,
     span: &'a Span,
     msg: &'static str,
+    /// Where the types of the sides came from, when they are parts of resolved unknowns.
+    lhs_blame: Option<Blame>,
+    rhs_blame: Option<Blame>,
+    /// The result of the operation the unification checks, poisoned if it fails.
+    poisons: Option<TypeId>,
+}
+impl<'a> Unification<'a> {
+    /// Unifies parts of the sides, for the same reason.
+    fn sub(&self, lhs: TypeId, rhs: TypeId) -> Self {
+        Self {
+            lhs,
+            rhs,
+            span: self.span,
+            msg: self.msg,
+            lhs_blame: self.lhs_blame.clone(),
+            rhs_blame: self.rhs_blame.clone(),
+            poisons: self.poisons,
+        }
+    }
+    fn blame(&self) -> Blame {
+        Blame {
+            reason: self.msg,
+            span: self.span.clone(),
+            from: None,
+        }
+    }
+}
+/// What a set of unknowns resolved to, the unknown at its root while it is still unresolved.
+#[derive(Debug)]
+pub struct Resolution {
+    pub ty: TypeId,
+    pub blame: Option<Blame>,
+    /// The shallowest `let` its unknowns can be reached from, they are generic in the
+    /// definitions of deeper ones.
+    pub level: u32,
+    /// What the unknowns may resolve to, kept as long as they are unresolved.
+    pub constraints: Constraints,
+}
+/// The unification that resolved an unknown, or where a type was given.
+#[derive(Debug, Clone)]
+pub struct Blame {
+    pub reason: &'static str,
+    pub span: Span,
+    /// The blame of the type it was resolved to, a chain back to where that type came from.
+    pub from: Option<Rc<Blame>>,
+}
+impl Blame {
+    /// Where the type came from in the first place, the end of the chain.
+    pub fn origin(&self) -> &Blame {
+        let mut blame = self;
+        while let Some(from) = &blame.from {
+            blame = from;
+        }
+        blame
+    }
+}
+/// Shows the origin, which is what an error should point at.
+impl std::fmt::Display for Blame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let origin = self.origin();
+        write!(f, "{} at {}", origin.reason, origin.span)
+    }
 }
 #[derive(Debug)]
 pub struct InferenceGroup<'a>(Vec<Unification<'a>>);
+impl<'a> Default for InferenceGroup<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl<'a> InferenceGroup<'a> {
     pub fn new() -> Self {
         Self(Vec::new())
     }
     pub fn insert_unification(
         &mut self,
-        lhs: Rc<Type>,
-        rhs: Rc<Type>,
+        lhs: TypeId,
+        rhs: TypeId,
         msg: &'static str,
         span: &'a Span,
     ) {
-        trace!("Inserting unification pair: {} = {} ({})", lhs, rhs, msg);
+        trace!(
+            "Inserting unification pair: {:?} = {:?} ({})",
+            lhs,
+            rhs,
+            msg
+        );
         self.0.push(Unification {
             lhs,
             rhs,
             msg,
             span,
+            lhs_blame: None,
+            rhs_blame: None,
+            poisons: None,
         });
     }
+    /// How many unifications have been inserted, see `poison_on_failure`.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// Makes the unifications inserted since there were `since` of them poison the unknown
+    /// `result` if they fail, so that the uses of an operation that failed don't fail too.
+    pub fn poison_on_failure(&mut self, since: usize, result: TypeId) {
+        for unification in &mut self.0[since..] {
+            unification.poisons = Some(result);
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub enum ArrayDims {
     Known(u32),
     LowerBounded(u32),
 }
 impl ArrayDims {
-    pub fn are_compatible(&self, other: &Self) -> bool {
+    /// The most precise count that both allow, none if they contradict each other.
+    pub fn merge(lhs: Self, rhs: Self) -> Option<Self> {
         use ArrayDims::*;
-        match (self, other) {
-            (a, b) if a == b => true,
-            (Known(a), LowerBounded(b)) | (LowerBounded(b), Known(a)) => *b <= *a,
-            (LowerBounded(_), LowerBounded(_)) => true,
-            _ => false,
-        }
-    }
-    pub fn consolidate(lhs: &RefCell<Rc<RefCell<Self>>>, rhs: &RefCell<Rc<RefCell<Self>>>) {
-        use ArrayDims::*;
-        let cloned_ref: Option<_> = match (&*lhs.borrow().borrow(), &*rhs.borrow().borrow()) {
-            (Known(a), Known(b)) if a == b => return,
-            (Known(_), Known(_)) => unreachable!("Should have been called after `are_compatible`"),
-            (LowerBounded(a), LowerBounded(b)) => {
-                if a >= b {
-                    Some(lhs.borrow().clone())
-                } else {
-                    Some(rhs.borrow().clone())
-                }
+        match (lhs, rhs) {
+            (Known(a), Known(b)) => (a == b).then_some(Known(a)),
+            (Known(a), LowerBounded(b)) | (LowerBounded(b), Known(a)) => {
+                (b <= a).then_some(Known(a))
             }
-            _ => None,
-        };
-        if let Some(cloned_ref) = cloned_ref {
-            *lhs.borrow_mut() = cloned_ref;
-            return;
-        }
-        let (to_change, new_val) = match (&*lhs.borrow().borrow(), &*rhs.borrow().borrow()) {
-            (Known(a), LowerBounded(b)) if a >= b => (rhs.borrow().clone(), *a),
-            (LowerBounded(b), Known(a)) if a >= b => (lhs.borrow().clone(), *a),
-            _ => unreachable!("Should have been called after `are_compatible`"),
-        };
-        *to_change.borrow_mut() = Known(new_val);
+            (LowerBounded(a), LowerBounded(b)) => Some(LowerBounded(a.max(b))),
+        }
     }
 }
 #[derive(Debug, Clone)]
 pub struct Constraints {
     allowed: HashSet<TypeKind>,
     disallowed: HashSet<TypeKind>,
+    /// Whether the types a resolution contains must satisfy them too.
+    hereditary: bool,
+    /// The rule they enforce, reported when they are violated.
+    reason: Option<&'static str>,
+}
+impl Default for Constraints {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Constraints {
     pub fn new() -> Self {
         Self {
             allowed: HashSet::new(),
             disallowed: HashSet::new(),
+            hereditary: false,
+            reason: None,
         }
     }
     pub fn allow_numeric() -> Self {
         Self {
             allowed: vec![TypeKind::Int, TypeKind::Float].into_iter().collect(),
-            disallowed: HashSet::new(),
+            ..Self::new()
         }
     }
     pub fn allow_comparables() -> Self {
@@ -353,28 +526,75 @@ impl Constraints {
             allowed: vec![TypeKind::Int, TypeKind::Float, TypeKind::Char]
                 .into_iter()
                 .collect(),
-            disallowed: HashSet::new(),
+            ..Self::new()
         }
     }
+    /// What the operands of equality operators are constrained to, down to their components.
     pub fn disallow_array_and_func() -> Self {
         Self {
-            allowed: HashSet::new(),
             disallowed: vec![TypeKind::Array, TypeKind::Func].into_iter().collect(),
+            hereditary: true,
+            reason: Some(validity::EQUALITY_ON_ARRAY_OR_FUNC),
+            ..Self::new()
         }
     }
-    pub fn are_satisfied_by(&self, ty: &Rc<Type>) -> bool {
-        let type_kind: TypeKind = (&**ty).into();
-        if !self.allowed.is_empty() && !self.allowed.contains(&type_kind) {
+    /// `reason` is the rule that makes arrays invalid there.
+    pub fn disallow_array(reason: &'static str) -> Self {
+        Self {
+            disallowed: vec![TypeKind::Array].into_iter().collect(),
+            reason: Some(reason),
+            ..Self::new()
+        }
+    }
+    #[inline(always)]
+    pub fn is_hereditary(&self) -> bool {
+        self.hereditary
+    }
+    /// Whether only numeric types satisfy them.
+    pub fn is_numeric(&self) -> bool {
+        !self.allowed.is_empty()
+            && self
+                .allowed
+                .iter()
+                .all(|kind| matches!(kind, TypeKind::Int | TypeKind::Float))
+    }
+    #[inline(always)]
+    pub fn reason(&self) -> Option<&'static str> {
+        self.reason
+    }
+    /// The rule `kind` breaks, if it is one of the disallowed ones rather than not allowed.
+    pub fn reason_against(&self, kind: &TypeKind) -> Option<&'static str> {
+        self.reason.filter(|_| self.disallowed.contains(kind))
+    }
+    pub fn are_satisfied_by(&self, ty: &Type) -> bool {
+        self.are_satisfied_by_kind(&ty.into())
+    }
+    pub fn are_satisfied_by_kind(&self, type_kind: &TypeKind) -> bool {
+        if *type_kind == TypeKind::Poison {
+            return true;
+        }
+        if !self.allowed.is_empty() && !self.allowed.contains(type_kind) {
             return false;
         }
-        if self.disallowed.contains(&type_kind) {
+        if self.disallowed.contains(type_kind) {
             return false;
         }
         true
     }
-    pub fn consolidate(&mut self, other: &Self) {
-        self.allowed.extend(other.allowed.iter().cloned());
+    /// Makes `self` the constraints of both, returns whether any type kind still satisfies them.
+    pub fn consolidate(&mut self, other: &Self) -> bool {
+        let restricted = !self.allowed.is_empty() || !other.allowed.is_empty();
+        if self.allowed.is_empty() {
+            self.allowed = other.allowed.clone();
+        } else if !other.allowed.is_empty() {
+            self.allowed.retain(|kind| other.allowed.contains(kind));
+        }
         self.disallowed.extend(other.disallowed.iter().cloned());
+        self.hereditary |= other.hereditary;
+        self.reason = self.reason.or(other.reason);
+        let disallowed = &self.disallowed;
+        self.allowed.retain(|kind| !disallowed.contains(kind));
+        !restricted || !self.allowed.is_empty()
     }
 }
 impl PartialEq for ArrayDims {
@@ -401,18 +621,24 @@ impl std::fmt::Display for Constraints {
             return write!(f, "");
         }
 
+        // Sorted, so that the same constraints always print the same.
+        let kinds = |set: &HashSet<TypeKind>| {
+            let mut kinds: Vec<_> = set.iter().collect();
+            kinds.sort();
+            let kinds: Vec<_> = kinds
+                .into_iter()
+                .map(|kind| format!("{:?}", kind).to_lowercase())
+                .collect();
+            kinds.join(", ")
+        };
         if !self.allowed.is_empty() {
-            write!(f, "{}", format!(" allow{:?}", self.allowed).to_lowercase())?;
+            write!(f, " allow{{{}}}", kinds(&self.allowed))?;
             if !self.disallowed.is_empty() {
-                write!(f, ", ")?;
+                write!(f, ",")?;
             }
         }
         if !self.disallowed.is_empty() {
-            write!(
-                f,
-                "{}",
-                format!(" disallow{:?}", self.disallowed).to_lowercase()
-            )
+            write!(f, " disallow{{{}}}", kinds(&self.disallowed))
         } else {
             Ok(())
         }
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use super::{Type, TypeId};
+
+/// Stores every distinct type once, so that a type is named by a `TypeId`
+/// and equal types have equal ids.
+#[derive(Debug)]
+pub struct TypeInterner {
+    types: Vec<Type>,
+    ids: HashMap<Type, TypeId>,
+}
+impl Default for TypeInterner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl TypeInterner {
+    pub fn new() -> Self {
+        let mut interner = Self {
+            types: Vec::new(),
+            ids: HashMap::new(),
+        };
+        let primitives = [
+            (Type::Unit, TypeId::UNIT),
+            (Type::Int, TypeId::INT),
+            (Type::Char, TypeId::CHAR),
+            (Type::Bool, TypeId::BOOL),
+            (Type::Float, TypeId::FLOAT),
+            (Type::Poison, TypeId::POISON),
+        ];
+        for (ty, id) in primitives {
+            let interned = interner.intern(ty);
+            debug_assert_eq!(
+                interned, id,
+                "primitives are interned in the order of their ids"
+            );
+        }
+        interner
+    }
+    /// The id of `ty`, the one it was given before if it has been interned already.
+    pub fn intern(&mut self, ty: Type) -> TypeId {
+        if let Some(id) = self.ids.get(&ty) {
+            return *id;
+        }
+        let id = TypeId(self.types.len() as u32);
+        self.types.push(ty.clone());
+        self.ids.insert(ty, id);
+        id
+    }
+    #[inline(always)]
+    pub fn get(&self, id: TypeId) -> &Type {
+        &self.types[id.0 as usize]
+    }
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn equal_types_are_interned_once() {
+        let mut interner = TypeInterner::new();
+        let func = interner.intern(Type::Func {
+            lhs: TypeId::INT,
+            rhs: TypeId::BOOL,
+        });
+        let same_func = interner.intern(Type::Func {
+            lhs: TypeId::INT,
+            rhs: TypeId::BOOL,
+        });
+        let other_func = interner.intern(Type::Func {
+            lhs: TypeId::BOOL,
+            rhs: TypeId::INT,
+        });
+        assert_eq!(func, same_func);
+        assert_ne!(func, other_func);
+        assert_eq!(interner.intern(Type::Int), TypeId::INT);
+        assert_eq!(
+            interner.get(func),
+            &Type::Func {
+                lhs: TypeId::INT,
+                rhs: TypeId::BOOL
+            }
+        );
+        assert_eq!(interner.len(), 8);
+    }
+}
@@ -1,43 +1,91 @@
 pub mod inference;
+pub mod interner;
+pub mod scheme;
 pub mod type_map;
+pub mod union_find;
+pub mod validity;
 
-use crate::parse::ast::annotation::TypeAnnotation;
-use std::{cell::RefCell, rc::Rc};
+use std::collections::HashMap;
 use strum::EnumDiscriminants;
 
-use self::inference::{ArrayDims, Constraints};
+use self::{
+    inference::{ArrayDims, InfererHelpers},
+    type_map::TypeMap,
+};
 
 // ! Implementation notes:
 // !   Solve inference groups on every definition seperately.
-// !   Types left unknown after solving a group are generic.
+// !   Unknowns are kept in a union-find (see `TypeMap::unknowns`), unifying two of them joins
+// !   their sets, along with their constraints and levels.
+// !   Every unknown has the level of the `let` it was made in, lowered to that of any unknown
+// !   it is resolved into. Unknowns left after solving a group are generic, unless their level
+// !   is that of the environment, or the definition is restricted (see `is_generalizable`).
+// !   The members of a recursive group use each other monomorphically, and are only
+// !   generalized once the whole group has been solved.
+// !   In strict mode, numeric unknowns that would be generic are defaulted to int instead
+// !   (see `SemTable::strict`), as the float operators pin down the rest.
+// !   A generic definition's type is kept as a `TypeScheme`.
 // !     On lookup of a generic definition:
-// !       - Create an instantiation. An instantiation is an
+// !       - Create an instantiation, a fresh unknown for each quantified one.
+// !       - Record it at the use, for the stages that monomorphize.
+// !   A node that fails to check gets the poison type, and checking goes on with the rest.
+// !   Only that node is poisoned, the unknowns it is unified with are not.
+// !   Unknowns in a unification that fails are resolved to poison, so that it is reported once,
+// !   and so is the result of the operation it checks (see `InferenceGroup::poison_on_failure`).
+// !   A failing unification whose sides contain poison is a cascade, and is not reported.
+// !   Literals and annotations give their type through an unknown already resolved to it
+// !   (see `TypeMap::new_given`), so that an error blames where the type was given.
+// !   Types are interned (see `TypeInterner`), a `TypeId` names one and equal ids are equal types.
+// !   What is learned during inference is kept in side tables of the `TypeMap` instead:
+// !     - The constraints and resolution of every unknown, per set of unified unknowns.
+// !     - The dimension count of arrays, per set of arrays known to have the same one.
+// !   So ground types are compared in O(1), and types are read through `TypeMap::resolve_type`.
+// !   User defined types are nominal, every `TDef` gets its own uid (see `TypeMap::new_custom`),
+// !   so a type shadowing another one of the same name is a different type.
 
-#[derive(Debug, EnumDiscriminants)]
+/// Names an interned type, cheap to copy and to compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeId(u32);
+impl TypeId {
+    // *Note: Interned first, in this order, by `TypeInterner::new`.
+    pub const UNIT: Self = Self(0);
+    pub const INT: Self = Self(1);
+    pub const CHAR: Self = Self(2);
+    pub const BOOL: Self = Self(3);
+    pub const FLOAT: Self = Self(4);
+    pub const POISON: Self = Self(5);
+}
+
+/// A type whose parts are interned, the same type is always the same `Type`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EnumDiscriminants)]
 #[strum_discriminants(name(TypeKind))]
-#[strum_discriminants(derive(Hash))]
+#[strum_discriminants(derive(Hash, PartialOrd, Ord))]
 pub enum Type {
-    // *Note: Unknown types can carry their own constraints.
-    Unknown(u32, RefCell<Constraints>),
+    // *Note: The constraints of unknowns are kept by the `TypeMap`, per set of unified unknowns.
+    Unknown(u32),
     Unit,
     Int,
     Char,
     Bool,
     Float,
     Func {
-        lhs: Rc<Type>,
-        rhs: Rc<Type>,
+        lhs: TypeId,
+        rhs: TypeId,
     },
-    Ref(Rc<Type>),
-    // *Note: Store a possible lower bound for dim_cnt. Possibly with an enum.
+    Ref(TypeId),
     Array {
-        inner: Rc<Type>,
-        dim_cnt: RefCell<Rc<RefCell<ArrayDims>>>,
+        inner: TypeId,
+        /// The set of dimension counts in the `TypeMap` this array's is in.
+        dims: u32,
     },
-    Tuple(Vec<Rc<Type>>),
+    Tuple(Vec<TypeId>),
     Custom {
-        id: String,
+        /// Tells apart types of the same name, it is their identity.
+        uid: u32,
     },
+    /// The type of whatever failed to check, it unifies with anything so that
+    /// the error does not cascade.
+    Poison,
 }
 impl Type {
     pub fn unknown_id_to_name(mut u: u32) -> String {
@@ -48,149 +96,218 @@ impl Type {
             if u < 26 {
                 break;
             }
-            u = u / 26;
+            u /= 26;
         }
         acc.into_iter().rev().map(|c| c as char).collect()
     }
-    #[inline(always)]
-    pub fn new_ref(inner: Rc<Type>) -> Rc<Type> {
-        Rc::new(Type::Ref(inner))
-    }
-    #[inline(always)]
-    pub fn new_array(inner: Rc<Type>, dim_cnt: ArrayDims) -> Rc<Type> {
-        Rc::new(Type::Array {
-            inner,
-            dim_cnt: RefCell::new(Rc::new(RefCell::new(dim_cnt))),
-        })
-    }
-    #[inline(always)]
-    pub fn new_known_array(inner: Rc<Type>, dim_cnt: u32) -> Rc<Type> {
-        Self::new_array(inner, ArrayDims::Known(dim_cnt))
-    }
-    #[inline(always)]
-    pub fn new_bounded_array(inner: Rc<Type>, bound: u32) -> Rc<Type> {
-        Self::new_array(inner, ArrayDims::LowerBounded(bound))
-    }
-    #[inline(always)]
-    pub fn new_func(lhs: Rc<Type>, rhs: Rc<Type>) -> Rc<Type> {
-        Rc::new(Type::Func { lhs, rhs })
-    }
-    #[inline(always)]
-    pub fn new_multi_arg_func(args: Vec<Rc<Type>>, ret: Rc<Type>) -> Rc<Type> {
-        args.into_iter()
-            .rfold(ret, |acc, arg| Type::new_func(arg, acc))
-    }
-    #[inline(always)]
-    pub fn new_tuple(types: Vec<Rc<Type>>) -> Rc<Type> {
-        Rc::new(Type::Tuple(types))
+}
+
+/// Prints a type for debugging, with the constraints of its unknowns, see `TypeMap::show`.
+pub struct Shown<'t, 'a> {
+    types: &'t TypeMap<'a>,
+    ty: TypeId,
+    /// Whether unknowns are printed as what they have been resolved to.
+    resolve: bool,
+}
+impl<'t, 'a> Shown<'t, 'a> {
+    pub fn new(types: &'t TypeMap<'a>, ty: TypeId, resolve: bool) -> Self {
+        Self { types, ty, resolve }
     }
-    #[inline(always)]
-    pub fn new_custom(id: String) -> Rc<Type> {
-        Rc::new(Type::Custom { id })
+    fn part(&self, ty: TypeId) -> Self {
+        Self { ty, ..*self }
     }
-    pub fn is_fully_known(&self) -> bool {
-        use Type::*;
-        match self {
-            Unknown(_, _) => false,
-            Func { lhs, rhs } => lhs.is_fully_known() && rhs.is_fully_known(),
-            Ref(inner) => inner.is_fully_known(),
-            Array { inner, dim_cnt } => {
-                inner.is_fully_known() && matches!(*dim_cnt.borrow().borrow(), ArrayDims::Known(_))
+}
+impl std::fmt::Display for Shown<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ty = if self.resolve {
+            self.types.resolve_type(self.ty)
+        } else {
+            self.types.given_type(self.ty)
+        };
+        match self.types.get(ty) {
+            Type::Unknown(id) => write!(
+                f,
+                "'{}{}",
+                Type::unknown_id_to_name(*id),
+                self.types.constraints_of(*id)
+            ),
+            Type::Unit => write!(f, "unit"),
+            Type::Int => write!(f, "int"),
+            Type::Char => write!(f, "char"),
+            Type::Bool => write!(f, "bool"),
+            Type::Float => write!(f, "float"),
+            Type::Func { lhs, rhs } => write!(f, "{} -> ({})", self.part(*lhs), self.part(*rhs)),
+            Type::Ref(inner) => write!(f, "({} ref)", self.part(*inner)),
+            Type::Array { inner, dims } => {
+                write!(f, "{}[{}]", self.part(*inner), self.types.dims_of(*dims))
+            }
+            Type::Tuple(types) => {
+                write!(f, "(")?;
+                for (i, ty) in types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", self.part(*ty))?;
+                }
+                write!(f, ")")
             }
-            Tuple(types) => types.iter().all(|t| t.is_fully_known()),
-            _ => true,
+            Type::Custom { uid } => write!(f, "{}", self.types.get_custom_tdef(*uid).id),
+            Type::Poison => write!(f, "<error>"),
         }
     }
-    // #[inline(always)]
-    // pub fn get_return_type(ty: &Rc<Type>) -> Rc<Type> {
-    //     match &**ty {
-    //         Type::Func { rhs, .. } => Self::get_return_type(rhs),
-    //         _ => ty.clone(),
-    //     }
-    // }
 }
-impl PartialEq for Type {
-    fn eq(&self, other: &Self) -> bool {
-        use Type::*;
-        if std::mem::discriminant(self) != std::mem::discriminant(other) {
-            return false;
-        }
-        match (self, other) {
-            (Unknown(id1, _), Unknown(id2, _)) => id1 == id2,
-            (
-                Func {
-                    lhs: lhs1,
-                    rhs: rhs1,
-                },
-                Func {
-                    lhs: lhs2,
-                    rhs: rhs2,
-                },
-            ) => lhs1 == lhs2 && rhs1 == rhs2,
-            (Ref(inner1), Ref(inner2)) => inner1 == inner2,
-            (
-                Array {
-                    inner: i1,
-                    dim_cnt: d1,
-                },
-                Array {
-                    inner: i2,
-                    dim_cnt: d2,
-                },
-            ) => i1 == i2 && d1 == d2,
-            (Tuple(types1), Tuple(types2)) => types2 == types1,
-            (Custom { id: id1 }, Custom { id: id2 }) => id1 == id2,
-            _ => true,
+
+/// Names unknowns `'a`, `'b`, ... in order of appearance, and weak ones `'_a`, `'_b`, ...
+/// Types printed with the same names agree on them, as in the sides of a type error.
+#[derive(Debug, Default)]
+pub struct UnknownNames {
+    names: HashMap<u32, String>,
+    generic_cnt: u32,
+    weak_cnt: u32,
+}
+impl UnknownNames {
+    fn insert(&mut self, id: u32, generic: bool) {
+        if self.names.contains_key(&id) {
+            return;
         }
+        let name = if generic {
+            self.generic_cnt += 1;
+            format!("'{}", Type::unknown_id_to_name(self.generic_cnt - 1))
+        } else {
+            self.weak_cnt += 1;
+            format!("'_{}", Type::unknown_id_to_name(self.weak_cnt - 1))
+        };
+        self.names.insert(id, name);
     }
 }
 
-impl From<&TypeAnnotation> for Rc<Type> {
-    fn from(annotation: &TypeAnnotation) -> Self {
-        use TypeAnnotation::*;
-        match annotation {
-            Unit => Rc::new(Type::Unit),
-            Int => Rc::new(Type::Int),
-            Char => Rc::new(Type::Char),
-            Bool => Rc::new(Type::Bool),
-            Float => Rc::new(Type::Float),
-            Func { lhs, rhs } => Rc::new(Type::Func {
-                lhs: (&**lhs).into(),
-                rhs: (&**rhs).into(),
-            }),
-            Ref(inner) => Rc::new(Type::Ref((&**inner).into())),
-            Array { inner, dim_cnt } => Type::new_known_array((&**inner).into(), *dim_cnt),
-            Tuple(types) => Rc::new(Type::Tuple(types.iter().map(|t| (t).into()).collect())),
-            Custom { id } => Rc::new(Type::Custom { id: id.clone() }),
+/// Prints a type the way it would be written in a program, see `TypeMap::pretty`.
+pub struct Pretty<'t, 'a> {
+    types: &'t TypeMap<'a>,
+    ty: TypeId,
+    /// Unknowns are named in order of appearance, rather than by their ids.
+    names: HashMap<u32, String>,
+    /// Whether unknowns are printed as what they have been resolved to.
+    resolve: bool,
+    /// Whether unknowns are followed by their constraints, for diagnostics.
+    constraints: bool,
+}
+impl<'t, 'a> Pretty<'t, 'a> {
+    /// Unknowns that are not `generic` are weak, named with a leading underscore.
+    pub fn new(types: &'t TypeMap<'a>, ty: TypeId, generic: impl Fn(u32) -> bool) -> Self {
+        let mut names = UnknownNames::default();
+        types.visit_unknowns(ty, &mut |id| names.insert(id, generic(id)));
+        Self {
+            types,
+            ty,
+            names: names.names,
+            resolve: true,
+            constraints: false,
+        }
+    }
+    /// Prints `ty` along with the other types named by `names`, with the constraints of its
+    /// unknowns. Unless `resolve`, it is printed as it was made.
+    pub fn named(
+        types: &'t TypeMap<'a>,
+        ty: TypeId,
+        names: &mut UnknownNames,
+        resolve: bool,
+    ) -> Self {
+        Self::visit_unknowns(types, ty, resolve, &mut |id| names.insert(id, true));
+        Self {
+            types,
+            ty,
+            names: names.names.clone(),
+            resolve,
+            constraints: true,
+        }
+    }
+    fn visit_unknowns(types: &TypeMap, ty: TypeId, resolve: bool, f: &mut impl FnMut(u32)) {
+        if resolve {
+            return types.visit_unknowns(ty, f);
+        }
+        match types.get(types.given_type(ty)) {
+            Type::Unknown(id) => f(*id),
+            Type::Func { lhs, rhs } => {
+                Self::visit_unknowns(types, *lhs, resolve, f);
+                Self::visit_unknowns(types, *rhs, resolve, f);
+            }
+            Type::Ref(inner) | Type::Array { inner, .. } => {
+                Self::visit_unknowns(types, *inner, resolve, f)
+            }
+            Type::Tuple(tys) => tys
+                .iter()
+                .for_each(|t| Self::visit_unknowns(types, *t, resolve, f)),
+            _ => (),
         }
     }
 }
-impl std::fmt::Display for Type {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let type_str = match self {
-            Type::Unknown(id, constraints) => {
-                format!("'{}{}", Type::unknown_id_to_name(*id), constraints.borrow())
+impl Pretty<'_, '_> {
+    /// How tightly the context binds, parentheses are needed around looser types.
+    const TOP: u8 = 0;
+    const FUNC_LHS: u8 = 1;
+    const ARRAY_INNER: u8 = 2;
+    const REF_INNER: u8 = 3;
+    fn write(&self, ty: TypeId, context: u8, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ty = if self.resolve {
+            self.types.resolve_type(ty)
+        } else {
+            self.types.given_type(ty)
+        };
+        let (binds, parenthesize) = match self.types.get(ty) {
+            Type::Func { .. } => (Self::TOP, context > Self::TOP),
+            Type::Array { .. } => (Self::ARRAY_INNER, context > Self::ARRAY_INNER),
+            _ => (Self::REF_INNER, false),
+        };
+        if parenthesize {
+            write!(f, "(")?;
+        }
+        match self.types.get(ty) {
+            Type::Unknown(id) if self.constraints => {
+                write!(f, "{}{}", self.names[id], self.types.constraints_of(*id))?
+            }
+            Type::Unknown(id) => write!(f, "{}", self.names[id])?,
+            Type::Func { lhs, rhs } => {
+                self.write(*lhs, Self::FUNC_LHS, f)?;
+                write!(f, " -> ")?;
+                self.write(*rhs, binds, f)?;
+            }
+            Type::Ref(inner) => {
+                self.write(*inner, Self::REF_INNER, f)?;
+                write!(f, " ref")?;
+            }
+            Type::Array { inner, dims } => {
+                match self.types.dims_of(*dims) {
+                    ArrayDims::Known(1) => write!(f, "array of ")?,
+                    ArrayDims::Known(n) => {
+                        write!(f, "array [{}] of ", vec!["*"; n as usize].join(", "))?
+                    }
+                    ArrayDims::LowerBounded(n) => {
+                        write!(f, "array [{}..] of ", "*, ".repeat(n as usize))?
+                    }
+                }
+                self.write(*inner, binds, f)?;
             }
-            Type::Unit => "unit".to_string(),
-            Type::Int => "int".to_string(),
-            Type::Char => "char".to_string(),
-            Type::Bool => "bool".to_string(),
-            Type::Float => "float".to_string(),
-            Type::Func { lhs, rhs } => format!("{} -> ({})", lhs, rhs),
-            Type::Ref(inner) => format!("({} ref)", inner),
-            Type::Array { inner, dim_cnt } => format!("{}[{}]", inner, dim_cnt.borrow().borrow()),
             Type::Tuple(types) => {
-                format!(
-                    "({})",
-                    types
-                        .iter()
-                        .map(|t| format!("{}", t))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                )
+                write!(f, "(")?;
+                for (i, ty) in types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.write(*ty, Self::TOP, f)?;
+                }
+                write!(f, ")")?;
             }
-            Type::Custom { id } => format!("{}", id),
-        };
-        write!(f, "{}", type_str)
+            _ => write!(f, "{}", self.types.show(ty))?,
+        }
+        if parenthesize {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+impl std::fmt::Display for Pretty<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write(self.ty, Self::TOP, f)
     }
 }
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use super::{
+    inference::{Constraints, InfererHelpers},
+    type_map::TypeMap,
+    Pretty, Type, TypeId,
+};
+
+/// The type of a binding, along with the unknowns every use of it gets fresh copies of.
+#[derive(Debug, Clone)]
+pub struct TypeScheme {
+    /// In order of their first appearance in `body`.
+    pub quantified: Vec<u32>,
+    /// What each quantified unknown was constrained to when generalized, instances keep it.
+    pub constraints: Vec<Constraints>,
+    /// Read through the resolutions, the unknowns it shares with its environment may
+    /// have been resolved since it was generalized.
+    pub body: TypeId,
+}
+
+impl TypeScheme {
+    /// Quantifies the unknowns left in `body` that `quantify` accepts.
+    pub fn generalize(types: &TypeMap, body: TypeId, quantify: impl Fn(u32) -> bool) -> Self {
+        let mut quantified = Vec::new();
+        let mut constraints = Vec::new();
+        types.visit_unknowns(body, &mut |id| {
+            if quantify(id) && !quantified.contains(&id) {
+                quantified.push(id);
+                constraints.push(types.constraints_of(id).clone());
+            }
+        });
+        Self {
+            quantified,
+            constraints,
+            body,
+        }
+    }
+    /// A scheme that quantifies nothing, every use gets `body` itself.
+    pub fn monomorphic(types: &TypeMap, body: TypeId) -> Self {
+        Self::generalize(types, body, |_| false)
+    }
+    #[inline(always)]
+    pub fn is_generic(&self) -> bool {
+        !self.quantified.is_empty()
+    }
+    /// `body`, with the quantified unknowns replaced according to `mappings`.
+    pub fn substitute(&self, types: &mut TypeMap, mappings: &HashMap<u32, TypeId>) -> TypeId {
+        substitute(types, self.body, mappings)
+    }
+    /// Names the quantified unknowns `'a`, `'b`, ... and the rest `'_a`, `'_b`, ...
+    pub fn pretty<'t, 'a>(&self, types: &'t TypeMap<'a>) -> Pretty<'t, 'a> {
+        Pretty::new(types, self.body, |id| self.quantified.contains(&id))
+    }
+}
+
+fn substitute(types: &mut TypeMap, ty: TypeId, mappings: &HashMap<u32, TypeId>) -> TypeId {
+    use Type::*;
+    // Parts without quantified unknowns are shared as they are, keeping their unknowns and
+    // so the blame of where their types came from.
+    let mut quantified = false;
+    types.visit_unknowns(ty, &mut |id| quantified |= mappings.contains_key(&id));
+    if !quantified {
+        return ty;
+    }
+    let ty = types.resolve_type(ty);
+    match types.get(ty).clone() {
+        Unknown(id) => mappings.get(&id).copied().unwrap_or(ty),
+        Func { lhs, rhs } => {
+            let lhs = substitute(types, lhs, mappings);
+            let rhs = substitute(types, rhs, mappings);
+            types.new_func(lhs, rhs)
+        }
+        Ref(inner) => {
+            let inner = substitute(types, inner, mappings);
+            types.new_ref(inner)
+        }
+        Array { inner, dims } => {
+            let inner = substitute(types, inner, mappings);
+            let dims = types.dims_of(dims);
+            types.new_array(inner, dims)
+        }
+        Tuple(elems) => {
+            let elems = elems
+                .into_iter()
+                .map(|t| substitute(types, t, mappings))
+                .collect();
+            types.new_tuple(elems)
+        }
+        // Unit | Int | Char | Bool | Float | Custom { .. } | Poison => ty,
+        _ => ty,
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::collections::{HashMap, HashSet};
 
 use log::trace;
 
@@ -6,31 +6,47 @@ use crate::{
     parse::ast::{
         self,
         data_map::{DataMap, NodeRef, NodeRefInner},
+        def::TDef,
+        expr::Expr,
+        Span,
     },
     pass::sem::types::inference::InfererHelpers,
 };
 
-use super::{inference::Constraints, Type};
+use super::{
+    inference::{ArrayDims, Blame, Constraints, Resolution},
+    interner::TypeInterner,
+    scheme::TypeScheme,
+    union_find::UnionFind,
+    Pretty, Shown, Type, TypeId,
+};
 
 #[derive(Debug)]
 pub struct TypeMap<'a> {
     /// Attaches a type to every node in the AST (that makes sense to have a type).
-    node_type_map: DataMap<'a, Rc<Type>>,
-
-    /// Stores instantiations for each generic type.
-    /// TODO: The index of the instantiation can be stored at the call-site to help lookup during codegen.
-    instantiations: DataMap<'a, Vec<Rc<Type>>>,
-    /// Stores the resolved type unifications after inference.
-    pub unifications: HashMap<u32, Rc<Type>>,
+    node_type_map: DataMap<'a, TypeId>,
 
-    /// The id of the next unknown type to be created.
-    next_unknown_id: u32,
+    /// The type scheme of every generic binding.
+    schemes: DataMap<'a, TypeScheme>,
+    /// What each use of a generic binding instantiated its quantified unknowns with, in order.
+    instantiations: DataMap<'a, Vec<TypeId>>,
 
-    int_type: Rc<Type>,
-    char_type: Rc<Type>,
-    bool_type: Rc<Type>,
-    float_type: Rc<Type>,
-    unit_type: Rc<Type>,
+    /// Every type made so far.
+    interner: TypeInterner,
+    /// The sets of unknowns unified with each other, indexed by unknown id, along with
+    /// what each set has been resolved to.
+    pub unknowns: UnionFind<Resolution>,
+    /// The sets of arrays known to have the same dimension count, along with the count.
+    pub dims: UnionFind<ArrayDims>,
+    /// The set standing for each known dimension count, so that arrays of the same
+    /// (known) type are the same type.
+    known_dims: HashMap<u32, u32>,
+    /// How many `let`s deep the definitions being checked are, the level new unknowns get.
+    level: u32,
+    /// The definition of each user defined type, indexed by its uid.
+    custom_tdefs: Vec<&'a TDef>,
+    /// The unknowns made by `new_given`, which only stand for the type they are given.
+    given: HashSet<u32>,
 }
 
 impl<'a> TypeMap<'a> {
@@ -38,173 +54,314 @@ impl<'a> TypeMap<'a> {
         Self {
             node_type_map: DataMap::new(p),
 
+            schemes: DataMap::new(p),
             instantiations: DataMap::new(p),
-            unifications: HashMap::new(),
-
-            next_unknown_id: 0,
 
-            int_type: Rc::new(Type::Int),
-            char_type: Rc::new(Type::Char),
-            bool_type: Rc::new(Type::Bool),
-            float_type: Rc::new(Type::Float),
-            unit_type: Rc::new(Type::Unit),
+            interner: TypeInterner::new(),
+            unknowns: UnionFind::new(),
+            dims: UnionFind::new(),
+            known_dims: HashMap::new(),
+            level: 0,
+            given: HashSet::new(),
+            custom_tdefs: Vec::new(),
         }
     }
-    pub fn insert(&mut self, node: impl Into<NodeRef<'a>>, ty: Rc<Type>) {
+    pub fn insert(&mut self, node: impl Into<NodeRef<'a>>, ty: TypeId) {
         if self.node_type_map.insert(node.into(), ty).is_some() {
             panic!("Tried to insert type for node that already has one.");
         }
     }
     #[inline(always)]
-    pub fn get_type(&self, node: impl Into<NodeRef<'a>>) -> Option<Rc<Type>> {
-        self.node_type_map.get(node).cloned()
+    pub fn get_type(&self, node: impl Into<NodeRef<'a>>) -> Option<TypeId> {
+        self.node_type_map.get(node).copied()
     }
     #[inline(always)]
-    pub fn get_node_type(&self, node: &NodeRef<'a>) -> Option<Rc<Type>> {
+    pub fn get_node_type(&self, node: &NodeRef<'a>) -> Option<TypeId> {
         trace!("Getting type for node: {}", node);
-        self.node_type_map.get_node(node).cloned().map(|t| {
-            trace!("Got type: {}", t);
-            t
-        })
+        self.node_type_map
+            .get_node(node)
+            .copied()
+            .inspect(|t| trace!("Got type: {}", self.show(*t)))
     }
     #[inline(always)]
-    pub fn mark_generic(&mut self, node: impl Into<NodeRef<'a>>) {
+    pub fn insert_scheme(&mut self, node: impl Into<NodeRef<'a>>, scheme: TypeScheme) {
         let node = node.into();
         trace!("Marking node '{}' as generic", node);
-        self.instantiations.insert(node, Vec::new());
-    }
-    fn instantiate(&mut self, ty: &Rc<Type>) -> Rc<Type> {
-        // *Done: Fully traverse, instantiate each unknown type, and then create one using those mappings.
-        let mut mappings = HashMap::new();
-        self.traverse_and_instantiate(ty, &mut mappings);
-        self.instantiate_with_mappings(ty, &mappings)
-    }
-    fn instantiate_with_mappings(
-        &self,
-        ty: &Rc<Type>,
-        mappings: &HashMap<u32, Rc<Type>>,
-    ) -> Rc<Type> {
-        use Type::*;
-        match &**ty {
-            Unknown(id, _) => mappings
-                .get(&id)
-                .expect("instantiation should have been created")
-                .clone(),
-            Func { lhs, rhs } => Type::new_func(
-                self.instantiate_with_mappings(lhs, mappings),
-                self.instantiate_with_mappings(rhs, mappings),
-            ),
-            Ref(inner) => Type::new_ref(self.instantiate_with_mappings(inner, mappings)),
-            Array { inner, dim_cnt } => Type::new_array(
-                self.instantiate_with_mappings(inner, mappings),
-                dim_cnt.borrow().borrow().clone(),
-            ),
-            Tuple(types) => Type::new_tuple(
-                types
-                    .iter()
-                    .map(|t| self.instantiate_with_mappings(t, mappings))
-                    .collect(),
-            ),
-            // Unit | Int | Char | Bool | Float | Custom { .. } => ty.clone(),
-            _ => ty.clone(),
-        }
+        self.schemes.insert(node, scheme);
     }
-    fn traverse_and_instantiate(&mut self, ty: &Rc<Type>, mappings: &mut HashMap<u32, Rc<Type>>) {
-        use Type::*;
-        match &**ty {
-            Unknown(id, constraints) if !mappings.contains_key(id) => {
-                let new_ty = self.new_unknown_with_constraint(constraints.borrow().clone());
-                mappings.insert(*id, new_ty.clone());
-            }
-            Func { lhs, rhs } => {
-                self.traverse_and_instantiate(lhs, mappings);
-                self.traverse_and_instantiate(rhs, mappings);
-            }
-            Ref(inner) => self.traverse_and_instantiate(inner, mappings),
-            Array { inner, .. } => {
-                self.traverse_and_instantiate(inner, mappings);
-            }
-            Tuple(types) => {
-                for ty in types {
-                    self.traverse_and_instantiate(ty, mappings);
-                }
-            }
-            _ => {}
-        }
+    #[inline(always)]
+    pub fn is_generic(&self, node: impl Into<NodeRef<'a>>) -> bool {
+        self.schemes.get(node).is_some()
     }
-    pub fn get_node_type_or_instantiation(&mut self, node: &NodeRef<'a>) -> Rc<Type> {
-        let node_type = self
-            .get_node_type(node)
-            .expect("looked up node should have a type");
-        // TODO: Find a way to avoid two get_node_mut lookups. Currently not allowed cause get_node_mut returns a &mut ref which self.instantiate also wants.
-        if self.instantiations.get_node(node).is_none() {
-            return node_type;
-        }
-        let node_type = self.deep_resolve_type(node_type);
-        let instance = self.instantiate(&node_type);
-        trace!("Instantiating generic {} to {}", node_type, instance);
-        self.instantiations
-            .get_node_mut(node)
-            .expect("looked up node should have instantiation here")
-            .push(instance.clone());
+    #[inline(always)]
+    pub fn get_scheme(&self, node: &NodeRef<'a>) -> Option<&TypeScheme> {
+        self.schemes.get_node(node)
+    }
+    /// The scheme of a binding, one that quantifies nothing if the binding is not generic.
+    /// *Note: Its body is read through the resolutions, so it shows everything learned
+    /// about the binding's type since it was made.
+    pub fn resolved_scheme(&self, node: &NodeRef<'a>) -> Option<TypeScheme> {
+        Some(match self.get_scheme(node) {
+            Some(scheme) => scheme.clone(),
+            None => TypeScheme::monomorphic(self, self.get_node_type(node)?),
+        })
+    }
+    /// What the use `site` of a generic binding instantiated its scheme's quantified unknowns with.
+    #[inline(always)]
+    pub fn get_instantiation(&self, site: &'a Expr) -> Option<&[TypeId]> {
+        self.instantiations.get(site).map(|types| types.as_slice())
+    }
+    /// The type of the binding `node` at its use `site`, a fresh instance of it if it is generic.
+    pub fn get_node_type_or_instantiation(&mut self, node: &NodeRef<'a>, site: &'a Expr) -> TypeId {
+        let Some(scheme) = self.schemes.get_node(node).cloned() else {
+            return self
+                .get_node_type(node)
+                .expect("looked up node should have a type");
+        };
+        let instances: Vec<_> = scheme
+            .constraints
+            .iter()
+            .map(|constraints| self.new_unknown_with_constraint(constraints.clone()))
+            .collect();
+        let mappings = scheme
+            .quantified
+            .iter()
+            .copied()
+            .zip(instances.iter().copied())
+            .collect();
+        let instance = scheme.substitute(self, &mappings);
+        trace!(
+            "Instantiating generic {} to {}",
+            self.show(scheme.body),
+            self.show(instance)
+        );
+        self.instantiations.insert(site, instances);
         instance
     }
 
     pub fn print_node_types(&mut self, mut w: impl std::io::Write) -> std::io::Result<()> {
-        writeln!(
-            w,
-            "{}",
-            format!("{:^50}│{:^60}│{:^50}", "Node", "Type", "Location")
-        )?;
-        writeln!(w, "{}", format!("{:─^50}┼{:─^60}┼{:─^50}", "", "", ""))?;
+        writeln!(w, "{:^50}│{:^60}│{:^50}", "Node", "Type", "Location")?;
+        writeln!(w, "{:─^50}┼{:─^60}┼{:─^50}", "", "", "")?;
         for (node, ty) in self.node_type_map.iter() {
+            if let NodeRef::Builtin(_) = node {
+                continue;
+            }
             writeln!(
                 w,
                 "{:^50}│{:^60}│{:^50}",
                 node.to_string(),
-                self.deep_resolve_type(ty.clone()).to_string(),
+                self.pretty(*ty).to_string(),
                 node.get_span().start.to_string()
             )?;
         }
         Ok(())
     }
 
+    /// The type `ty` names, as it was made (see `InfererHelpers::resolve_type`).
+    #[inline(always)]
+    pub fn get(&self, ty: TypeId) -> &Type {
+        self.interner.get(ty)
+    }
+    #[inline(always)]
+    pub fn intern(&mut self, ty: Type) -> TypeId {
+        self.interner.intern(ty)
+    }
+    /// Prints `ty` for debugging, as it was made.
+    #[inline(always)]
+    pub fn show(&self, ty: TypeId) -> Shown<'_, 'a> {
+        Shown::new(self, ty, false)
+    }
+    /// Prints `ty` for debugging, with its unknowns resolved.
+    #[inline(always)]
+    pub fn show_resolved(&self, ty: TypeId) -> Shown<'_, 'a> {
+        Shown::new(self, ty, true)
+    }
+    /// Unlike `show`, which is for debugging, this leaves out constraints and extra parentheses.
+    #[inline(always)]
+    pub fn pretty(&self, ty: TypeId) -> Pretty<'_, 'a> {
+        Pretty::new(self, ty, |_| true)
+    }
+    /// The type an unknown made by `new_given` was given, `ty` itself for any other type.
+    pub fn given_type(&self, ty: TypeId) -> TypeId {
+        match self.get(ty) {
+            Type::Unknown(id) if self.given.contains(id) => self.unknowns.get(*id).ty,
+            _ => ty,
+        }
+    }
+    /// The constraints of the set of unknowns `id` is in.
+    #[inline(always)]
+    pub fn constraints_of(&self, id: u32) -> &Constraints {
+        &self.unknowns.get(id).constraints
+    }
+    /// The dimension count of the set of arrays `dims` is in.
+    #[inline(always)]
+    pub fn dims_of(&self, dims: u32) -> ArrayDims {
+        *self.dims.get(dims)
+    }
+    pub fn is_fully_known(&self, ty: TypeId) -> bool {
+        use Type::*;
+        match self.get(self.resolve_type(ty)) {
+            Unknown(_) => false,
+            Func { lhs, rhs } => self.is_fully_known(*lhs) && self.is_fully_known(*rhs),
+            Ref(inner) => self.is_fully_known(*inner),
+            Array { inner, dims } => {
+                self.is_fully_known(*inner) && matches!(self.dims_of(*dims), ArrayDims::Known(_))
+            }
+            Tuple(types) => types.iter().all(|t| self.is_fully_known(*t)),
+            _ => true,
+        }
+    }
+    /// Whether (the resolution of) `ty` contains the poison type.
+    pub fn contains_poison(&self, ty: TypeId) -> bool {
+        use Type::*;
+        match self.get(self.resolve_type(ty)) {
+            Poison => true,
+            Func { lhs, rhs } => self.contains_poison(*lhs) || self.contains_poison(*rhs),
+            Ref(inner) | Array { inner, .. } => self.contains_poison(*inner),
+            Tuple(types) => types.iter().any(|t| self.contains_poison(*t)),
+            _ => false,
+        }
+    }
+    /// Calls `f` on every unknown left in (the resolution of) `ty`, left to right.
+    pub fn visit_unknowns(&self, ty: TypeId, f: &mut impl FnMut(u32)) {
+        use Type::*;
+        match self.get(self.resolve_type(ty)) {
+            Unknown(id) => f(*id),
+            Func { lhs, rhs } => {
+                self.visit_unknowns(*lhs, f);
+                self.visit_unknowns(*rhs, f);
+            }
+            Ref(inner) | Array { inner, .. } => self.visit_unknowns(*inner, f),
+            Tuple(types) => types.iter().for_each(|t| self.visit_unknowns(*t, f)),
+            _ => (),
+        }
+    }
+
+    pub fn new_unknown_with_constraint(&mut self, constraints: Constraints) -> TypeId {
+        let ty = self.intern(Type::Unknown(self.unknowns.len()));
+        self.unknowns.push(Resolution {
+            ty,
+            blame: None,
+            level: self.level,
+            constraints,
+        });
+        ty
+    }
+    #[inline(always)]
+    pub fn new_unknown(&mut self) -> TypeId {
+        self.new_unknown_with_constraint(Constraints::new())
+    }
+    /// An unknown already resolved to `ty`, for the places a type is given rather than inferred
+    /// (literals and annotations), so that unifying it blames where it was given.
+    pub fn new_given(&mut self, ty: TypeId, reason: &'static str, span: &Span) -> TypeId {
+        let id = self.unknowns.len();
+        self.given.insert(id);
+        let unknown = self.intern(Type::Unknown(id));
+        self.unknowns.push(Resolution {
+            ty,
+            blame: Some(Blame {
+                reason,
+                span: span.clone(),
+                from: None,
+            }),
+            level: self.level,
+            constraints: Constraints::new(),
+        });
+        unknown
+    }
+    /// Unknowns made until the matching `exit_level` belong to a deeper `let`.
     #[inline(always)]
-    fn get_and_advance_unknown_id(&mut self) -> u32 {
-        let id = self.next_unknown_id;
-        self.next_unknown_id += 1;
-        id
+    pub fn enter_level(&mut self) {
+        self.level += 1;
     }
-    pub fn new_unknown_with_constraint(&mut self, constraints: Constraints) -> Rc<Type> {
-        let id = self.get_and_advance_unknown_id();
-        Rc::new(Type::Unknown(id, RefCell::new(constraints)))
+    #[inline(always)]
+    pub fn exit_level(&mut self) {
+        self.level -= 1;
+    }
+    #[inline(always)]
+    pub fn level(&self) -> u32 {
+        self.level
     }
-    pub fn new_unknown(&mut self) -> Rc<Type> {
-        let id = self.get_and_advance_unknown_id();
-        Rc::new(Type::Unknown(id, RefCell::new(Constraints::new())))
+    /// The level of the set of unknowns `id` is in.
+    #[inline(always)]
+    pub fn level_of(&self, id: u32) -> u32 {
+        self.unknowns.get(id).level
+    }
+    /// A type distinct from every other one, even those of the same name.
+    pub fn new_custom(&mut self, tdef: &'a TDef) -> TypeId {
+        self.custom_tdefs.push(tdef);
+        let uid = self.custom_tdefs.len() as u32 - 1;
+        self.intern(Type::Custom { uid })
+    }
+    #[inline(always)]
+    pub fn get_custom_tdef(&self, uid: u32) -> &'a TDef {
+        self.custom_tdefs[uid as usize]
+    }
+    #[inline(always)]
+    pub fn new_unknown_ref(&mut self) -> TypeId {
+        let inner = self.new_unknown();
+        self.new_ref(inner)
+    }
+    #[inline(always)]
+    pub fn new_ref(&mut self, inner: TypeId) -> TypeId {
+        self.intern(Type::Ref(inner))
+    }
+    pub fn new_array(&mut self, inner: TypeId, dims: ArrayDims) -> TypeId {
+        let dims = match dims {
+            ArrayDims::Known(n) => match self.known_dims.get(&n) {
+                Some(dims) => *dims,
+                None => {
+                    let dims = self.dims.push(ArrayDims::Known(n));
+                    self.known_dims.insert(n, dims);
+                    dims
+                }
+            },
+            ArrayDims::LowerBounded(_) => self.dims.push(dims),
+        };
+        self.intern(Type::Array { inner, dims })
+    }
+    #[inline(always)]
+    pub fn new_known_array(&mut self, inner: TypeId, dim_cnt: u32) -> TypeId {
+        self.new_array(inner, ArrayDims::Known(dim_cnt))
+    }
+    #[inline(always)]
+    pub fn new_bounded_array(&mut self, inner: TypeId, bound: u32) -> TypeId {
+        self.new_array(inner, ArrayDims::LowerBounded(bound))
+    }
+    #[inline(always)]
+    pub fn new_func(&mut self, lhs: TypeId, rhs: TypeId) -> TypeId {
+        self.intern(Type::Func { lhs, rhs })
+    }
+    pub fn new_multi_arg_func(&mut self, args: Vec<TypeId>, ret: TypeId) -> TypeId {
+        args.into_iter()
+            .rfold(ret, |acc, arg| self.new_func(arg, acc))
+    }
+    #[inline(always)]
+    pub fn new_tuple(&mut self, types: Vec<TypeId>) -> TypeId {
+        self.intern(Type::Tuple(types))
     }
     #[inline(always)]
-    pub fn new_unknown_ref(&mut self) -> Rc<Type> {
-        Rc::new(Type::Ref(self.new_unknown()))
+    pub fn get_int(&self) -> TypeId {
+        TypeId::INT
     }
     #[inline(always)]
-    pub fn get_int(&self) -> Rc<Type> {
-        self.int_type.clone()
+    pub fn get_char(&self) -> TypeId {
+        TypeId::CHAR
     }
     #[inline(always)]
-    pub fn get_char(&self) -> Rc<Type> {
-        self.char_type.clone()
+    pub fn get_bool(&self) -> TypeId {
+        TypeId::BOOL
     }
     #[inline(always)]
-    pub fn get_bool(&self) -> Rc<Type> {
-        self.bool_type.clone()
+    pub fn get_float(&self) -> TypeId {
+        TypeId::FLOAT
     }
     #[inline(always)]
-    pub fn get_float(&self) -> Rc<Type> {
-        self.float_type.clone()
+    pub fn get_unit(&self) -> TypeId {
+        TypeId::UNIT
     }
     #[inline(always)]
-    pub fn get_unit(&self) -> Rc<Type> {
-        self.unit_type.clone()
+    pub fn get_poison(&self) -> TypeId {
+        TypeId::POISON
     }
 }
@@ -0,0 +1,111 @@
+use std::cell::Cell;
+
+/// Disjoint sets of the ids `0..len`, each set holding one value.
+///
+/// Unions are by rank and lookups compress the paths they walk, so any sequence of
+/// operations takes nearly linear time.
+#[derive(Debug)]
+pub struct UnionFind<T> {
+    /// *Note: Cells so that lookups can compress paths through a shared reference.
+    parents: Vec<Cell<u32>>,
+    ranks: Vec<u8>,
+    /// Only the value of a set's root is the value of the set.
+    values: Vec<T>,
+}
+impl<T> Default for UnionFind<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T> UnionFind<T> {
+    pub fn new() -> Self {
+        Self {
+            parents: Vec::new(),
+            ranks: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+    #[inline(always)]
+    pub fn len(&self) -> u32 {
+        self.parents.len() as u32
+    }
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+    /// Makes a new set of its own, returns its id.
+    pub fn push(&mut self, value: T) -> u32 {
+        let id = self.len();
+        self.parents.push(Cell::new(id));
+        self.ranks.push(0);
+        self.values.push(value);
+        id
+    }
+    /// The root of the set `id` is in, every id on the way is pointed straight at it.
+    pub fn find(&self, id: u32) -> u32 {
+        let mut root = id;
+        while self.parents[root as usize].get() != root {
+            root = self.parents[root as usize].get();
+        }
+        let mut id = id;
+        while id != root {
+            id = self.parents[id as usize].replace(root);
+        }
+        root
+    }
+    #[inline(always)]
+    pub fn get(&self, id: u32) -> &T {
+        &self.values[self.find(id) as usize]
+    }
+    #[inline(always)]
+    pub fn get_mut(&mut self, id: u32) -> &mut T {
+        let root = self.find(id);
+        &mut self.values[root as usize]
+    }
+    /// Joins the sets of `a` and `b`, `merge` is given the value of the new root
+    /// and the value of the other one. Returns the new root.
+    pub fn union(&mut self, a: u32, b: u32, merge: impl FnOnce(&mut T, &T)) -> u32 {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return a;
+        }
+        let (root, child) = if self.ranks[a as usize] >= self.ranks[b as usize] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        if self.ranks[a as usize] == self.ranks[b as usize] {
+            self.ranks[root as usize] += 1;
+        }
+        self.parents[child as usize].set(root);
+        let (root_value, child_value) = if root < child {
+            let (head, tail) = self.values.split_at_mut(child as usize);
+            (&mut head[root as usize], &tail[0])
+        } else {
+            let (head, tail) = self.values.split_at_mut(root as usize);
+            (&mut tail[0], &head[child as usize])
+        };
+        merge(root_value, child_value);
+        root
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unions_share_a_root_and_merge_values() {
+        let mut sets = UnionFind::new();
+        let ids: Vec<_> = (0..8).map(|i| sets.push(vec![i])).collect();
+        for pair in ids.chunks(2) {
+            sets.union(pair[0], pair[1], |root, other| root.extend(other));
+        }
+        sets.union(ids[1], ids[7], |root, other| root.extend(other));
+        assert_eq!(sets.find(ids[0]), sets.find(ids[6]));
+        assert_ne!(sets.find(ids[0]), sets.find(ids[2]));
+        let mut merged = sets.get(ids[7]).clone();
+        merged.sort();
+        assert_eq!(merged, vec![0, 1, 6, 7]);
+    }
+}
@@ -0,0 +1,64 @@
+use super::{inference::InfererHelpers, type_map::TypeMap, Type, TypeId};
+
+// ! Implementation notes:
+// !   The Llama spec restricts which types are valid:
+// !     - Array elements cannot be arrays (§3, Data types).
+// !     - Functions cannot return arrays (§3, Data types).
+// !     - `new` cannot allocate an array (§4, Program structure: dynamic memory allocation).
+// !     - Equality operators cannot compare arrays or functions, nor values containing them
+// !       (§4, Program structure: operators).
+// !   Known types (those of annotations) are checked directly with `check_validity`.
+// !   Inferred ones are checked by the constraints put on their unknowns, so a rule is also
+// !   enforced on types resolved after the construct that needs it has been sem'ed.
+
+pub const ARRAY_OF_ARRAYS: &str = "the Llama spec (§3, Data types) forbids arrays of arrays";
+pub const FUNCTION_RETURNS_ARRAY: &str =
+    "the Llama spec (§3, Data types) forbids functions that return arrays";
+pub const NEW_ARRAY: &str = "the Llama spec (§4, Program structure: dynamic memory allocation) \
+     forbids allocating arrays with `new`";
+pub const EQUALITY_ON_ARRAY_OR_FUNC: &str = "the Llama spec (§4, Program structure: operators) \
+     forbids comparing arrays or functions, or values containing them";
+
+/// Checks the rules every type must follow, returns the first one `ty` breaks.
+pub fn check_validity(types: &TypeMap, ty: TypeId) -> Result<(), &'static str> {
+    use Type::*;
+    let is_array = |ty| matches!(types.get(types.resolve_type(ty)), Array { .. });
+    match types.get(types.resolve_type(ty)) {
+        Array { inner, .. } if is_array(*inner) => Err(ARRAY_OF_ARRAYS),
+        Func { rhs, .. } if is_array(*rhs) => Err(FUNCTION_RETURNS_ARRAY),
+        Func { lhs, rhs } => check_validity(types, *lhs).and_then(|_| check_validity(types, *rhs)),
+        Ref(inner) | Array { inner, .. } => check_validity(types, *inner),
+        Tuple(elems) => elems.iter().try_for_each(|t| check_validity(types, *t)),
+        Unknown(..) | Unit | Int | Char | Bool | Float | Custom { .. } | Poison => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::ast;
+
+    #[test]
+    fn nested_arrays_and_functions_returning_arrays_are_invalid() {
+        let program = ast::Program {
+            definitions: Vec::new(),
+            attributes: Vec::new(),
+        };
+        let mut types = TypeMap::new(&program);
+        let int = types.get_int();
+        let array = types.new_known_array(int, 1);
+        assert_eq!(check_validity(&types, array), Ok(()));
+        let nested = types.new_known_array(array, 2);
+        assert_eq!(check_validity(&types, nested), Err(ARRAY_OF_ARRAYS));
+        let takes_array = types.new_func(array, int);
+        assert_eq!(check_validity(&types, takes_array), Ok(()));
+        let returns_array = types.new_func(int, array);
+        assert_eq!(
+            check_validity(&types, returns_array),
+            Err(FUNCTION_RETURNS_ARRAY)
+        );
+        let ref_ = types.new_ref(returns_array);
+        let tuple = types.new_tuple(vec![int, ref_]);
+        assert_eq!(check_validity(&types, tuple), Err(FUNCTION_RETURNS_ARRAY));
+    }
+}
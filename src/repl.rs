@@ -0,0 +1,182 @@
+use std::io::{BufRead, Write};
+
+use crate::{
+    diagnostic::{Diagnostic, Sources},
+    lex::{token::TokenKind, IntoLexer},
+    log_error_in,
+    parse::{
+        ast::{
+            def::{DefKind, Definition},
+            Program, ReplInput,
+        },
+        IntoParser, ParseErr,
+    },
+    pass::{
+        interp::{value::Value, Interpreter, RuntimeError},
+        sem::{sem_table::SemTable, SemIncremental},
+    },
+    scan::Scanner,
+    CompilerError, CompilerResult,
+};
+
+// ! Implementation notes:
+// !   Every input is parsed on its own and then leaked, since the `SemTable` and the
+// !   interpreter keep referring to its nodes for the rest of the session.
+// !   Definitions that fail to check or to evaluate leave no bindings behind.
+// !   The interpreter owns the input and output, so that programs reading and printing
+// !   share them with the REPL itself.
+// !   Input is submitted as soon as it parses, so only a line that leaves the input
+// !   incomplete (e.g. ending in `=`, `then` or `else`) continues on the next one.
+// !   Every input is kept under a name of its own, since what it is blamed on may be
+// !   in an earlier one.
+
+const PROMPT: &str = "# ";
+const CONTINUATION_PROMPT: &str = "  ";
+/// Stands in for the filename in positions, numbered per input so that spans into earlier
+/// inputs still point at theirs.
+fn input_name(n: usize) -> String {
+    format!("<repl:{}>", n)
+}
+
+pub struct Repl {
+    sem: SemTable<'static>,
+    interp: Interpreter<'static>,
+    /// Whether to print prompts, they are only noise when input is not typed in.
+    prompts: bool,
+    /// Every input submitted so far, which diagnostics may point into.
+    sources: Sources,
+    /// How many inputs have been submitted.
+    inputs: usize,
+}
+
+impl Repl {
+    pub fn new(
+        input: Box<dyn BufRead + 'static>,
+        output: Box<dyn Write + 'static>,
+        prompts: bool,
+    ) -> Self {
+        let empty: &'static Program = Box::leak(Box::new(Program {
+            definitions: Vec::new(),
+            attributes: Vec::new(),
+        }));
+        Self {
+            sem: SemTable::new(empty),
+            interp: Interpreter::new(input, output),
+            prompts,
+            sources: Sources::default(),
+            inputs: 0,
+        }
+    }
+    /// Runs until the end of input, returns the code the session exited with.
+    pub fn run(&mut self) -> CompilerResult<i32> {
+        let mut source = String::new();
+        loop {
+            let prompt = if source.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            if self.prompts {
+                io(write!(self.interp.output(), "{}", prompt))?;
+                io(self.interp.output().flush())?;
+            }
+            if io(self.interp.input().read_line(&mut source))? == 0 {
+                return Ok(0);
+            }
+            let name = input_name(self.inputs + 1);
+            let input = parse(&name, &source);
+            if let Err(error) = &input {
+                if error.found().is_none_or(|t| t.kind == TokenKind::EOF) {
+                    // The input goes on in the next line.
+                    continue;
+                }
+            }
+            // Diagnostics point into the input they came from, which may be an earlier one.
+            self.inputs += 1;
+            self.sources.insert(&name, &source);
+            source.clear();
+            let res = input
+                .map_err(CompilerError::from)
+                .and_then(|input| self.eval(Box::leak(Box::new(input))));
+            self.sem
+                .warnings
+                .drain(..)
+                .for_each(|w| Diagnostic::from(&w).log(&mut self.sources));
+            match res {
+                Ok(()) => (),
+                Err(CompilerError::RuntimeError(RuntimeError::Exit { code })) => return Ok(code),
+                Err(err) => log_error_in(&err, &mut self.sources),
+            }
+        }
+    }
+    fn eval(&mut self, input: &'static ReplInput) -> CompilerResult<()> {
+        match input {
+            ReplInput::Definitions(program) => program
+                .definitions
+                .iter()
+                .try_for_each(|def| self.eval_definition(def)),
+            ReplInput::Expr(expr) => {
+                let ty = self.sem.sem_top_level_expr(expr)?;
+                let value = self.interp.eval_expr(expr, &self.sem)?;
+                io(writeln!(
+                    self.interp.output(),
+                    "- : {} = {}",
+                    self.sem.types.pretty(ty),
+                    value
+                ))
+            }
+        }
+    }
+    fn eval_definition(&mut self, def: &'static Definition) -> CompilerResult<()> {
+        let checkpoint = self.sem.checkpoint();
+        self.sem.sem_definition(def)?;
+        if let Err(err) = self.interp.eval_definition(def, &self.sem) {
+            self.sem.rollback(checkpoint);
+            return Err(err.into());
+        }
+        match def {
+            Definition::Let(letdef) => {
+                for def in &letdef.defs {
+                    let scheme = self
+                        .sem
+                        .types
+                        .resolved_scheme(&def.into())
+                        .expect("def has a type");
+                    io(write!(
+                        self.interp.output(),
+                        "val {} : {}",
+                        def.id,
+                        scheme.pretty(&self.sem.types)
+                    ))?;
+                    match (&def.kind, self.interp.lookup(&def.id)) {
+                        (DefKind::Const { .. }, Some(value))
+                            if !matches!(value, Value::Func(_)) =>
+                        {
+                            io(writeln!(self.interp.output(), " = {}", value))?
+                        }
+                        _ => io(writeln!(self.interp.output()))?,
+                    }
+                }
+            }
+            Definition::Type(typedef) => {
+                for tdef in &typedef.tdefs {
+                    io(writeln!(self.interp.output(), "type {}", tdef.id))?;
+                }
+            }
+            Definition::Error(_) => unreachable!("REPL inputs with syntax errors are not run"),
+        }
+        Ok(())
+    }
+}
+
+fn parse(name: &str, source: &str) -> Result<ReplInput, ParseErr> {
+    Scanner::from_source(name, source.to_string())
+        .preprocess()
+        .into_lexer(true)
+        .into_parser()
+        .repl_input()
+}
+/// The REPL talks through the interpreter's input and output, so it fails the same way.
+fn io<T>(res: std::io::Result<T>) -> CompilerResult<T> {
+    Ok(res.map_err(RuntimeError::from)?)
+}
@@ -6,8 +6,11 @@ use std::iter::{FusedIterator, Peekable};
 use std::rc::Rc;
 use std::str::SplitWhitespace;
 
-use log::error;
 use thiserror::Error;
+
+use crate::diagnostic::{codes, Diagnostic, Sources};
+use crate::lex::token::Position;
+use crate::parse::ast::Span;
 // !Consider Scanners that can read from stdin.
 /// Simple file scanner that can read line by line and preprocess files.
 pub struct Scanner {
@@ -29,6 +32,18 @@ impl Scanner {
         scanner.included_files.insert(Rc::clone(&filename), None);
         Ok(scanner)
     }
+    /// Scans `source` instead of a file, `name` stands in for the filename in positions.
+    pub fn from_source(name: &str, source: String) -> Self {
+        let name = Rc::new(name.to_string());
+        let mut scanner = Scanner {
+            buffers: vec![Buffer::from_source(Rc::clone(&name), source)],
+            preprocess: false,
+            included_files: HashMap::new(),
+            first_call: true,
+        };
+        scanner.included_files.insert(name, None);
+        scanner
+    }
     // TODO: Consider removing ability to *not* preprocess.
     pub fn preprocess(mut self) -> Self {
         self.preprocess = true;
@@ -74,7 +89,7 @@ impl Scanner {
             Directive::Include(path) => {
                 let path = Rc::new(path);
                 self.push_file(Rc::clone(&path))?;
-                return Ok(Some(Line::change_file(Rc::clone(&path))));
+                Ok(Some(Line::change_file(Rc::clone(&path))))
             }
         }
     }
@@ -84,7 +99,8 @@ impl Scanner {
             return Err(ScanErr::IncludeCycle {
                 included_file: to_include.to_string(),
                 in_file: current_buf.filename.to_string(),
-                at_line: current_buf.lineno,
+                // The directive's own line is not counted yet.
+                at_line: current_buf.lineno + 1,
                 prev_included_at: self.included_files[&to_include]
                     .as_ref()
                     .map(|s| s.to_string()),
@@ -109,7 +125,7 @@ impl Iterator for Scanner {
         match self.read_line() {
             Ok(any) => any,
             Err(e) => {
-                error!("{}", e);
+                Diagnostic::from(&e).log(&mut Sources::default());
                 std::process::exit(1);
             }
         }
@@ -123,7 +139,7 @@ pub enum Line {
     Line { text: Vec<u8>, lineno: usize },
 }
 impl Line {
-    pub fn line(text: Vec<u8>, lineno: usize) -> Self {
+    pub fn new(text: Vec<u8>, lineno: usize) -> Self {
         Line::Line { text, lineno }
     }
     pub fn change_file(path: Rc<String>) -> Self {
@@ -141,9 +157,8 @@ impl fmt::Display for Line {
         }
     }
 }
-#[derive(Debug)]
 struct Buffer {
-    inner: BufReader<File>,
+    inner: Box<dyn BufRead>,
     filename: Rc<String>,
     lineno: usize,
     ows_empty: bool,
@@ -153,12 +168,20 @@ impl Buffer {
         let file = File::open(filename.as_ref())
             .map_err(|e| ScanErr::FileOpen(e, Rc::clone(&filename)))?;
         Ok(Self {
-            inner: BufReader::new(file),
+            inner: Box::new(BufReader::new(file)),
             filename,
             lineno: 0,
             ows_empty: false,
         })
     }
+    fn from_source(filename: Rc<String>, source: String) -> Self {
+        Self {
+            inner: Box::new(io::Cursor::new(source.into_bytes())),
+            filename,
+            lineno: 0,
+            ows_empty: false,
+        }
+    }
     fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
         if self.ows_empty {
             self.ows_empty = false;
@@ -174,7 +197,7 @@ impl Buffer {
     }
     fn finalize_line(&mut self, line: String) -> Line {
         self.lineno += 1;
-        Line::line(line.into_bytes(), self.lineno)
+        Line::new(line.into_bytes(), self.lineno)
     }
 }
 
@@ -183,7 +206,7 @@ enum Directive {
     Include(String),
 }
 
-fn to_directive(s: &String) -> ScanResult<Option<Directive>> {
+fn to_directive(s: &str) -> ScanResult<Option<Directive>> {
     let mut words = s.split_whitespace().peekable();
     match words.next() {
         Some(word) if !word.starts_with("#") => Ok(None),
@@ -230,3 +253,34 @@ pub enum ScanErr {
         prev_included_at: Option<String>,
     },
 }
+impl From<&ScanErr> for Diagnostic {
+    fn from(error: &ScanErr) -> Self {
+        match error {
+            ScanErr::IncludeCycle {
+                included_file,
+                in_file,
+                at_line,
+                prev_included_at,
+            } => {
+                let in_file = Rc::new(in_file.clone());
+                let at = Position::new(*at_line, 1, Rc::clone(&in_file));
+                let line_end = Position::new(*at_line + 1, 1, in_file);
+                Diagnostic::new(
+                    &codes::INCLUDE_CYCLE,
+                    format!("Include cycle found: {} is included again", included_file),
+                )
+                .with_primary(&Span::new(at, line_end), "included again here")
+                .with_note(match prev_included_at {
+                    Some(file) => format!("it was previously included in {}", file),
+                    None => "it's possibly also the source file given to the compiler".to_string(),
+                })
+            }
+            ScanErr::IO(_) | ScanErr::FileOpen(..) => {
+                Diagnostic::new(&codes::IO_ERROR, error.to_string())
+            }
+            ScanErr::UnknownDirective(_) | ScanErr::IncludeTrailingArgs | ScanErr::IncludeEmpty => {
+                Diagnostic::new(&codes::INVALID_DIRECTIVE, error.to_string())
+            }
+        }
+    }
+}
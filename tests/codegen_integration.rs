@@ -0,0 +1,108 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use llamac::cli::{Cli, Emit, StopAfter};
+use llamac::diagnostic::ErrorFormat;
+
+/// Programs in `testfiles/strict` are compiled in strict mode.
+fn make_args_struct(input_filename: String, out: String, emit: Emit) -> Cli {
+    Cli {
+        strict: input_filename.contains("testfiles/strict"),
+        filename: Some(input_filename),
+        stop_after: StopAfter::Codegen,
+        out: Some(out),
+        emit,
+        warnings: Default::default(),
+        error_format: ErrorFormat::Human,
+        explain: None,
+        verbose: false,
+        command: None,
+    }
+}
+
+fn temp_path(path: &Path, suffix: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "llamac-test-{}-{}{}",
+        std::process::id(),
+        path.file_stem().unwrap().to_str().unwrap(),
+        suffix
+    ))
+}
+
+/// Runs the executable, its output must match the `.out` file next to `path` (if any).
+fn check_run(path: &Path, exe: &Path) -> datatest_stable::Result<()> {
+    let output = Command::new(exe).output();
+    let _ = std::fs::remove_file(exe);
+    let output = output?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} exited with {}:\n{}",
+            path.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+    let expected = path.with_extension("out");
+    if expected.exists() {
+        let expected = std::fs::read_to_string(expected)?;
+        let actual = String::from_utf8_lossy(&output.stdout);
+        if actual != expected {
+            return Err(format!("expected output:\n{}\ngot:\n{}", expected, actual).into());
+        }
+    }
+    Ok(())
+}
+
+fn run_native(path: &Path) -> datatest_stable::Result<()> {
+    let exe = temp_path(path, "-native");
+    let args = make_args_struct(
+        path.to_str().unwrap().to_string(),
+        exe.to_str().unwrap().to_string(),
+        Emit::Exe,
+    );
+    llamac::run_compiler(&args)?;
+    check_run(path, &exe)
+}
+
+fn run_through_c(path: &Path) -> datatest_stable::Result<()> {
+    let source = temp_path(path, ".c");
+    let exe = temp_path(path, "-c");
+    let args = make_args_struct(
+        path.to_str().unwrap().to_string(),
+        source.to_str().unwrap().to_string(),
+        Emit::C,
+    );
+    llamac::run_compiler(&args)?;
+    let cc = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg("-std=c11")
+        .arg("-Werror")
+        .arg(&source)
+        .arg("-o")
+        .arg(&exe)
+        .arg("-lm")
+        .output();
+    let _ = std::fs::remove_file(&source);
+    let cc = cc?;
+    if !cc.status.success() {
+        return Err(String::from_utf8_lossy(&cc.stderr).into_owned().into());
+    }
+    check_run(path, &exe)
+}
+
+datatest_stable::harness!(
+    run_native,
+    "./testfiles/end-to-end",
+    r".*\.lla",
+    run_through_c,
+    "./testfiles/end-to-end",
+    r".*\.lla",
+    run_native,
+    "./testfiles/strict",
+    r".*\.lla",
+    run_through_c,
+    "./testfiles/strict",
+    r".*\.lla"
+);
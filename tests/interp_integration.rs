@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use llamac::{diagnostic::Diagnostic, CompilerError};
+
+/// Interprets the program, returns its exit code and what it printed.
+/// Programs in `testfiles/strict` are interpreted in strict mode.
+fn interpret(path: &Path) -> Result<(i32, String), (CompilerError, String)> {
+    let mut output = Vec::new();
+    let res = llamac::run_interpreter(
+        path.to_str().unwrap(),
+        path.to_str().unwrap().contains("testfiles/strict"),
+        &Default::default(),
+        Box::new(std::io::empty()),
+        Box::new(&mut output),
+    );
+    let output = String::from_utf8_lossy(&output).into_owned();
+    match res {
+        Ok(code) => Ok((code, output)),
+        Err(err) => Err((err, output)),
+    }
+}
+
+/// The output must match the `.out` file next to `path` (if any).
+fn check_output(path: &Path, actual: &str) -> datatest_stable::Result<()> {
+    let expected = path.with_extension("out");
+    if expected.exists() {
+        let expected = std::fs::read_to_string(expected)?;
+        if actual != expected {
+            return Err(format!("expected output:\n{}\ngot:\n{}", expected, actual).into());
+        }
+    }
+    Ok(())
+}
+
+/// The interpreter is the oracle for compiled programs, so it shares their expected outputs.
+fn run_interpreted(path: &Path) -> datatest_stable::Result<()> {
+    match interpret(path) {
+        Ok((0, output)) => check_output(path, &output),
+        Ok((code, _)) => Err(format!("{} exited with {}", path.display(), code).into()),
+        Err((err, _)) => Err(Box::new(err) as _),
+    }
+}
+
+/// The program must fail with the error in the `.err` file, positions are given without the filename.
+/// Its JSON form must have a runtime error code and point into the program.
+fn runtime_error(path: &Path) -> datatest_stable::Result<()> {
+    match interpret(path) {
+        Ok(_) => Err(format!("{} ran without errors", path.display()).into()),
+        Err((CompilerError::RuntimeError(err), output)) => {
+            let expected = std::fs::read_to_string(path.with_extension("err"))?;
+            let actual = err
+                .to_string()
+                .replace(&format!("{}:", path.to_str().unwrap()), "");
+            if actual != expected.trim_end() {
+                return Err(format!("expected error:\n{}\ngot:\n{}", expected, actual).into());
+            }
+            let json: serde_json::Value =
+                serde_json::from_str(&Diagnostic::from(&err).to_json().to_string())?;
+            let span = &json["spans"][0];
+            if !json["code"]
+                .as_str()
+                .is_some_and(|code| code.starts_with("E04"))
+                || span["file"] != path.to_str().unwrap()
+                || span["is_primary"] != true
+            {
+                return Err(format!("{} does not point at {}", json, err).into());
+            }
+            check_output(path, &output)
+        }
+        Err((err, _)) => Err(Box::new(err) as _),
+    }
+}
+
+datatest_stable::harness!(
+    run_interpreted,
+    "./testfiles/end-to-end",
+    r".*\.lla",
+    runtime_error,
+    "./testfiles/runtime-errors",
+    r".*\.lla",
+    run_interpreted,
+    "./testfiles/strict",
+    r".*\.lla"
+);
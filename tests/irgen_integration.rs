@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use llamac::cli::{Cli, Emit, StopAfter};
+use llamac::diagnostic::ErrorFormat;
+use llamac::lex::IntoLexer;
+use llamac::parse::IntoParser;
+use llamac::pass::{irgen::irgen, sem::sem};
+use llamac::scan::Scanner;
+
+fn make_args_struct(input_filename: String) -> Cli {
+    Cli {
+        filename: Some(input_filename),
+        stop_after: StopAfter::IrGen,
+        out: None,
+        emit: Emit::Exe,
+        strict: false,
+        warnings: Default::default(),
+        error_format: ErrorFormat::Human,
+        explain: None,
+        verbose: false,
+        command: None,
+    }
+}
+
+/// IR generation must succeed, and print the contents of the `.ir` file if there is one.
+fn irgen_fully(path: &Path) -> datatest_stable::Result<()> {
+    let path_str = path.to_str().unwrap().to_string();
+    match llamac::run_compiler(&make_args_struct(path_str.clone())) {
+        Ok(_) | Err(llamac::CompilerError::EarlyExit(_)) => (),
+        Err(err) => return Err(Box::new(err) as _),
+    }
+    let Ok(expected) = std::fs::read_to_string(path.with_extension("ir")) else {
+        return Ok(());
+    };
+    let ast = Scanner::new(&path_str)?
+        .preprocess()
+        .into_lexer(true)
+        .into_parser()
+        .program()?;
+    let sem_results = sem(&ast, false)?;
+    let actual = irgen(&ast, &sem_results).to_string();
+    if actual != expected {
+        return Err(format!("expected IR:\n{}\ngot:\n{}", expected, actual).into());
+    }
+    Ok(())
+}
+
+datatest_stable::harness!(irgen_fully, "./testfiles/end-to-end", r".*\.lla");
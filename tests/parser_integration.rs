@@ -1,14 +1,27 @@
 use std::path::Path;
 
-use llamac::cli::{Cli, StopAfter};
+use serde_json::json;
+
+use llamac::{
+    cli::{Cli, Emit, StopAfter},
+    diagnostic::{Diagnostic, ErrorFormat},
+    lex::IntoLexer,
+    parse::IntoParser,
+    scan::Scanner,
+};
 
 fn make_args_struct(input_filename: String) -> Cli {
     Cli {
-        filename: input_filename,
+        filename: Some(input_filename),
         stop_after: StopAfter::Parsing,
-        out: "".to_string(),
+        out: None,
+        emit: Emit::Exe,
+        strict: false,
+        warnings: Default::default(),
+        error_format: ErrorFormat::Human,
+        explain: None,
         verbose: false,
-        print: None,
+        command: None,
     }
 }
 fn parse_fully(path: &Path) -> datatest_stable::Result<()> {
@@ -20,4 +33,93 @@ fn parse_fully(path: &Path) -> datatest_stable::Result<()> {
     }
     Ok(())
 }
-datatest_stable::harness!(parse_fully, "./testfiles/syntax", r".*\.lla");
+/// Parsing must go on past every error, and find the ones in the `.err` file (one per line,
+/// positions given without the filename). The definitions after the last error must be kept,
+/// and the ones that failed to parse must leave error nodes.
+fn parse_errors(path: &Path) -> datatest_stable::Result<()> {
+    let path_str = path.to_str().unwrap();
+    let (program, errors) = Scanner::new(path_str)?
+        .preprocess()
+        .into_lexer(true)
+        .into_parser()
+        .program_recovering();
+    let actual: String = errors
+        .iter()
+        .map(|e| match e.hint() {
+            Some(hint) => format!("{}\n  hint: {}\n", e, hint),
+            None => format!("{}\n", e),
+        })
+        .map(|e| e.replace(&format!("{}:", path_str), ""))
+        .collect();
+    let expected = std::fs::read_to_string(path.with_extension("err"))?;
+    if actual != expected {
+        return Err(format!("expected errors:\n{}\ngot:\n{}", expected, actual).into());
+    }
+    // Definitions that failed to parse are kept as error nodes, so none goes missing.
+    let source = std::fs::read_to_string(path)?;
+    let definitions = source
+        .lines()
+        .filter(|line| line.starts_with("let ") || line.starts_with("type "))
+        .count();
+    if program.definitions.len() < definitions {
+        return Err(format!(
+            "expected at least {} definitions, got {}",
+            definitions,
+            program.definitions.len()
+        )
+        .into());
+    }
+    match program.definitions.last() {
+        Some(llamac::parse::ast::def::Definition::Let(letdef)) if letdef.defs[0].id == "ok" => {
+            Ok(())
+        }
+        _ => Err("the definition of `ok` at the end was not kept".into()),
+    }
+}
+/// Every error's JSON form points where its `Display` does, and suggests inserting the
+/// missing token when there is one.
+fn json_errors(path: &Path) -> datatest_stable::Result<()> {
+    let path_str = path.to_str().unwrap();
+    let (_, errors) = Scanner::new(path_str)?
+        .preprocess()
+        .into_lexer(true)
+        .into_parser()
+        .program_recovering();
+    for error in &errors {
+        // Printed and read back, as a tool would.
+        let json: serde_json::Value =
+            serde_json::from_str(&Diagnostic::from(error).to_json().to_string())?;
+        if json["code"] != error.code().code || json["severity"] != "error" {
+            return Err(format!("{} does not match {}", json, error).into());
+        }
+        if let Some(found) = error.found() {
+            let span = &json["spans"][0];
+            let position = (&span["file"], &span["line_start"], &span["column_start"]);
+            if position
+                != (
+                    &json!(path_str),
+                    &json!(found.from.line),
+                    &json!(found.from.column),
+                )
+                || span["is_primary"] != true
+            {
+                return Err(format!("{} does not point at {}", json, found.from).into());
+            }
+            if error.hint().is_some() && json["suggestions"] == json!([]) {
+                return Err(format!("{} suggests no fix", json).into());
+            }
+        }
+    }
+    Ok(())
+}
+datatest_stable::harness!(
+    parse_fully,
+    "./testfiles/syntax",
+    r".*\.lla",
+    parse_errors,
+    "./testfiles/syntax-errors",
+    r".*\.lla",
+    json_errors,
+    "./testfiles/syntax-errors",
+    r".*\.lla"
+);
@@ -0,0 +1,35 @@
+use std::{cell::RefCell, io::Write, path::Path, rc::Rc};
+
+use llamac::repl::Repl;
+
+/// Collects what the REPL prints, while the REPL owns the writer.
+#[derive(Clone, Default)]
+struct Transcript(Rc<RefCell<Vec<u8>>>);
+impl Write for Transcript {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Feeds the session to the REPL, what it prints must match the `.out` file.
+fn session(path: &Path) -> datatest_stable::Result<()> {
+    let input = std::fs::read(path)?;
+    let transcript = Transcript::default();
+    Repl::new(
+        Box::new(std::io::Cursor::new(input)),
+        Box::new(transcript.clone()),
+        false,
+    )
+    .run()?;
+    let actual = String::from_utf8_lossy(&transcript.0.borrow()).into_owned();
+    let expected = std::fs::read_to_string(path.with_extension("out"))?;
+    if actual != expected {
+        return Err(format!("expected transcript:\n{}\ngot:\n{}", expected, actual).into());
+    }
+    Ok(())
+}
+
+datatest_stable::harness!(session, "./testfiles/repl", r".*\.lla");
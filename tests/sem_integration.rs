@@ -1,17 +1,30 @@
 use std::path::Path;
 
 use llamac::{
-    cli::{Cli, StopAfter},
-    pass::sem::SemanticError,
+    cli::{Cli, Emit, StopAfter},
+    diagnostic::ErrorFormat,
+    lex::IntoLexer,
+    parse::IntoParser,
+    pass::{
+        lint::{lint, Allowances},
+        sem::{sem, sem_recovering, SemanticError},
+    },
+    scan::Scanner,
+    CompilerError,
 };
 
 fn make_args_struct(input_filename: String) -> Cli {
     Cli {
-        filename: input_filename,
+        filename: Some(input_filename),
         stop_after: StopAfter::Sem,
-        out: "".to_string(),
+        out: None,
+        emit: Emit::Exe,
+        strict: false,
+        warnings: Default::default(),
+        error_format: ErrorFormat::Human,
+        explain: None,
         verbose: false,
-        print: None,
+        command: None,
     }
 }
 
@@ -20,10 +33,10 @@ fn sem_fully(path: &Path) -> datatest_stable::Result<()> {
     let compilation_result = llamac::run_compiler(&make_args_struct(path_str));
     match compilation_result {
         Ok(_) | Err(llamac::CompilerError::EarlyExit(_)) => Ok(()),
-        Err(llamac::CompilerError::SemanticError(SemanticError::InferenceError {
-            msg, ..
-        })) if msg.contains("Occurs")
-            && path
+        Err(llamac::CompilerError::SemanticError(errors))
+            if errors.iter().all(|err| {
+                matches!(err, SemanticError::InferenceError { msg, .. } if msg.contains("Occurs"))
+            }) && path
                 .as_os_str()
                 .to_string_lossy()
                 .as_ref()
@@ -35,4 +48,176 @@ fn sem_fully(path: &Path) -> datatest_stable::Result<()> {
     }
 }
 
-datatest_stable::harness!(sem_fully, "./testfiles/end-to-end", r".*\.lla");
+/// Sem must fail with exactly the errors in the `.err` file (one per line, positions given
+/// without the filename, followed by their count). Unknowns are named by ids that change along
+/// with the checker, so both sides name them in the order they first appear instead.
+fn sem_error(path: &Path) -> datatest_stable::Result<()> {
+    let path_str = path.to_str().unwrap().to_string();
+    match llamac::run_compiler(&make_args_struct(path_str)) {
+        Err(llamac::CompilerError::SemanticError(err)) => {
+            let expected = std::fs::read_to_string(path.with_extension("err"))?;
+            let actual = err
+                .to_string()
+                .replace(&format!("{}:", path.to_str().unwrap()), "");
+            if renumber_unknowns(&actual) != renumber_unknowns(expected.trim_end()) {
+                return Err(format!("expected error:\n{}\ngot:\n{}", expected, actual).into());
+            }
+            Ok(())
+        }
+        Ok(_) | Err(llamac::CompilerError::EarlyExit(_)) => {
+            Err(format!("{} passed sem", path.display()).into())
+        }
+        Err(err) => Err(Box::new(err) as _),
+    }
+}
+
+/// Renames the unknowns (`'` followed by lowercase letters, unlike char literals) to `'0`, `'1`,
+/// ... in the order they first appear.
+fn renumber_unknowns(errors: &str) -> String {
+    let mut names: Vec<String> = Vec::new();
+    let mut out = String::new();
+    let mut rest = errors;
+    while let Some(quote) = rest.find('\'') {
+        out += &rest[..=quote];
+        rest = &rest[quote + 1..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_lowercase())
+            .unwrap_or(rest.len());
+        if len == 0 || rest[len..].starts_with('\'') {
+            continue;
+        }
+        let name = &rest[..len];
+        let index = match names.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                names.push(name.to_string());
+                names.len() - 1
+            }
+        };
+        out += &index.to_string();
+        rest = &rest[len..];
+    }
+    out + rest
+}
+
+/// Sem must succeed with the warnings in the `.warn` file, one per line without the filename.
+/// Warnings that an attribute allows are left out.
+fn sem_warnings(path: &Path) -> datatest_stable::Result<()> {
+    check_warnings(path, false)
+}
+/// Same as `sem_warnings` in strict mode, for the programs that have a `.warn` file.
+fn strict_warnings(path: &Path) -> datatest_stable::Result<()> {
+    if !path.with_extension("warn").exists() {
+        return Ok(());
+    }
+    check_warnings(path, true)
+}
+fn check_warnings(path: &Path, strict: bool) -> datatest_stable::Result<()> {
+    let path_str = path.to_str().unwrap();
+    let ast = Scanner::new(path_str)?
+        .preprocess()
+        .into_lexer(true)
+        .strict(strict)
+        .into_parser()
+        .program()?;
+    let table = sem(&ast, strict)?;
+    let allowances = Allowances::new(&ast, &table);
+    let actual: String = table
+        .warnings
+        .iter()
+        .filter(|w| !allowances.allows(w.code(), w.span()))
+        .map(|w| format!("{}\n", w).replace(&format!("{}:", path_str), ""))
+        .collect();
+    let expected = std::fs::read_to_string(path.with_extension("warn"))?;
+    if actual != expected {
+        return Err(format!("expected warnings:\n{}\ngot:\n{}", expected, actual).into());
+    }
+    Ok(())
+}
+
+/// Sem's warnings are found even if it fails, those in the `.warn` file of the programs that
+/// have one.
+fn failed_sem_warnings(path: &Path) -> datatest_stable::Result<()> {
+    if !path.with_extension("warn").exists() {
+        return Ok(());
+    }
+    let path_str = path.to_str().unwrap();
+    let ast = Scanner::new(path_str)?
+        .preprocess()
+        .into_lexer(true)
+        .into_parser()
+        .program()?;
+    let (table, errors) = sem_recovering(&ast, false);
+    if errors.is_ok() {
+        return Err(format!("{} passed sem", path.display()).into());
+    }
+    let actual: String = table
+        .warnings
+        .iter()
+        .map(|w| format!("{}\n", w).replace(&format!("{}:", path_str), ""))
+        .collect();
+    let expected = std::fs::read_to_string(path.with_extension("warn"))?;
+    if actual != expected {
+        return Err(format!("expected warnings:\n{}\ngot:\n{}", expected, actual).into());
+    }
+    Ok(())
+}
+
+/// Lints must find the warnings in the `.warn` file, one per line without the filename.
+fn lint_warnings(path: &Path) -> datatest_stable::Result<()> {
+    let path_str = path.to_str().unwrap();
+    let ast = Scanner::new(path_str)?
+        .preprocess()
+        .into_lexer(true)
+        .into_parser()
+        .program()?;
+    let table = sem(&ast, false)?;
+    let actual: String = lint(&ast, &table)
+        .iter()
+        .map(|w| format!("{}\n", w).replace(&format!("{}:", path_str), ""))
+        .collect();
+    let expected = std::fs::read_to_string(path.with_extension("warn"))?;
+    if actual != expected {
+        return Err(format!("expected warnings:\n{}\ngot:\n{}", expected, actual).into());
+    }
+    Ok(())
+}
+
+/// With `--deny-warnings`, each of the lints in the `.warn` file fails compilation.
+fn denied_warnings(path: &Path) -> datatest_stable::Result<()> {
+    let mut args = make_args_struct(path.to_str().unwrap().to_string());
+    args.warnings.deny_warnings = true;
+    let expected = std::fs::read_to_string(path.with_extension("warn"))?
+        .lines()
+        .count();
+    match llamac::run_compiler(&args) {
+        Err(CompilerError::DeniedWarnings(denied)) if denied.0 == expected => Ok(()),
+        Err(CompilerError::EarlyExit(_)) if expected == 0 => Ok(()),
+        Err(err) => Err(Box::new(err) as _),
+        Ok(_) => Err("expected compilation to stop".into()),
+    }
+}
+
+datatest_stable::harness!(
+    sem_fully,
+    "./testfiles/end-to-end",
+    r".*\.lla",
+    sem_error,
+    "./testfiles/sem-errors",
+    r".*\.lla",
+    failed_sem_warnings,
+    "./testfiles/sem-errors",
+    r".*\.lla",
+    sem_warnings,
+    "./testfiles/sem-warnings",
+    r".*\.lla",
+    strict_warnings,
+    "./testfiles/strict",
+    r".*\.lla",
+    lint_warnings,
+    "./testfiles/lints",
+    r".*\.lla",
+    denied_warnings,
+    "./testfiles/lints",
+    r".*\.lla"
+);